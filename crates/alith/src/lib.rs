@@ -16,11 +16,20 @@ pub use core::llm::{
     ExecutionProviderDispatch, FastEmbeddingsModel, FastEmbeddingsModelName,
     FastEmbeddingsModelOptions,
 };
+#[cfg(feature = "telemetry")]
+pub use core::telemetry::{
+    TelemetryConfig, TelemetryError, TelemetryExporter, TelemetryGuard, init_telemetry,
+};
 pub use core::{
     agent::Agent,
+    approval::{ApprovalDecision, ToolApprover},
+    budget::{Budget, BudgetExceeded, BudgetLimit},
+    cassette::{Cassette, CassetteCompletion, CassetteError, MatchMode, RecordedResponse},
     chat::{
-        Chat, Completion, CompletionError, Message as ChatMessage, Request, ResponseContent,
-        ResponseTokenUsage, ResponseToolCalls, ToolCall,
+        CallOptions, CancellationToken, Chat, Completion, CompletionDelta, CompletionError,
+        CompletionStream, Interrupted, Message as ChatMessage, Request, ResponseContent,
+        ResponseFormat, ResponseTokenUsage, ResponseToolCalls, StreamingCompletion, ToolCall,
+        ToolCallDelta,
     },
     chunking::{
         ChunkError, Chunker, ChunkerConfig, ChunkerResult, DEFAULT_CHUNK_SIZE, TextChunker,
@@ -30,30 +39,49 @@ pub use core::{
         TextCleaner, normalize_whitespace, reduce_to_single_whitespace, strip_unwanted_chars,
     },
     concatenator::{TextConcatenator, TextConcatenatorTrait},
+    delegation::AgentTool,
     embeddings::{Embed, EmbedError, Embeddings, EmbeddingsBuilder, EmbeddingsData, TextEmbedder},
+    executor::{RunOutput, RunStep, RunTrace, ToolCallTrace},
     extractor::{ExtractionError, Extractor},
     flow::{
-        Action, Content, DefaultNode, EmptyAction, EnvVar, Graph, InChannels, Node, NodeId,
-        NodeName, NodeTable, OutChannels, Output, RecvErr, SendErr, auto_node, dependencies,
+        Action, AgentNode, Content, DefaultNode, EmptyAction, EnvVar, Graph, InChannels, Node,
+        NodeId, NodeMessage, NodeName, NodeTable, OutChannels, Output, RecvErr, ResultCollector,
+        SendErr, auto_node, dependencies,
     },
     json::{
         JsonParseError, parse_and_check_json_markdown, parse_json_markdown, parse_partial_json,
     },
-    knowledge::{FileKnowledge, Knowledge, KnowledgeError},
+    knowledge::{FileKnowledge, Knowledge, KnowledgeError, KnowledgeIndex},
     llm::{ClientConfig, EmbeddingsModel, LLM},
     mcp::{
         ClientCapabilities, ClientInfo, MCPClient, MCPConfig, MCPError, MCPServerConfig,
-        SseTransport, StdioTransport, Transport, setup_mcp_clients, sse_client, stdio_client,
+        SseTransport, StdioTransport, Transport, read_mcp_config, setup_mcp_clients, sse_client,
+        start_mcp_clients, stdio_client,
+    },
+    memory::{
+        JsonlMemory, Memory, Message, MessageType, SummaryBufferMemory, TokenBufferMemory,
+        VectorStoreMemory, WindowBufferMemory,
     },
-    memory::{Memory, Message, MessageType, WindowBufferMemory},
+    middleware::{Middleware, ModelResponse},
+    mock::{MockCompletion, MockResponse},
     parser::{JsonParser, MarkdownParser, Parser, ParserError, StringParser, TrimParser},
+    react::{ReActCompletion, ReActResponse},
+    retriever::{Reranker, RetrievalPipeline, Retriever, ScoredDocument},
+    spec::{AgentSpec, MemorySpec, ModelSpec, SpecError, SpecRegistry, StoreIndexSpec},
     splitting::{
         Separator, SeparatorGroup, SplitError, TextSplit, TextSplitter, split_markdown, split_text,
         split_text_into_indices,
     },
     store::{DocumentId, InMemoryStorage, Storage, TopNResults, VectorStoreError},
-    task::{Task, TaskError, TaskMetadata},
-    tool::{StructureTool, Tool, ToolChoice, ToolDefinition, ToolError},
+    task::{Task, TaskError, TaskMetadata, TaskQueue, TaskRecord, TaskStatus},
+    tool::{
+        RegisteredTool, RetryPolicy, StructureTool, Tool, ToolChoice, ToolDefinition, ToolError,
+        ToolRegistry, ToolRegistryError, ToolSource,
+    },
+    usage::{
+        ModelPrice, PricingTable, UsageContext, UsageGroup, UsageKind, UsageLedger, UsageQuery,
+        UsageRecord, UsageSummary,
+    },
 };
 
 pub use knowledge::{
@@ -68,6 +96,8 @@ pub use store::milvus::*;
 pub use store::pgvector::*;
 #[cfg(feature = "qdrant")]
 pub use store::qdrant::*;
+#[cfg(feature = "sqlite")]
+pub use store::sqlite::*;
 pub use tools::search::{Search, SearchProvider, SearchResult, SearchResults, SearchTool};
 
 pub use client::{
@@ -97,4 +127,8 @@ pub use prompt::{
 #[cfg(feature = "lazai")]
 pub use alith_lazai as lazai;
 
+pub use alith_macros::{Tool, tool};
 pub use async_trait::async_trait;
+
+#[doc(hidden)]
+pub use core::__private;
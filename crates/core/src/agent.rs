@@ -1,29 +1,57 @@
-use crate::chat::{Chat, Completion, Document, Message, Request};
-use crate::executor::Executor;
-use crate::knowledge::Knowledge;
-use crate::mcp::{MCPClient, MCPError, setup_mcp_clients, sse_client, stdio_client};
+use crate::approval::ToolApprover;
+use crate::budget::{Budget, BudgetExceeded};
+use crate::chat::{
+    CallOptions, Chat, Completion, CompletionDelta, Interrupted, Message, Request, ResponseFormat,
+    StreamingCompletion,
+};
+use crate::executor::{DEFAULT_MAX_CONCURRENT_TOOLS, DEFAULT_MAX_STEPS, Executor, RunOutput};
+use crate::json::parse_json_markdown;
+use crate::knowledge::{Knowledge, KnowledgeIndex};
+use crate::mcp::{
+    MCPClient, MCPError, MCPServerConfig, read_mcp_config, sse_client, start_mcp_clients,
+    stdio_client,
+};
 use crate::memory::Memory;
-use crate::store::{Storage, VectorStoreError};
+use crate::middleware::Middleware;
+use crate::retriever::{RetrievalPipeline, Retriever};
+use crate::store::Storage;
 use crate::task::TaskError;
-use crate::tool::Tool;
+use crate::telemetry;
+use crate::tool::{Tool, ToolDefinition, ToolRegistry, ToolRegistryError};
+use crate::usage::UsageLedger;
 use crate::{Ref, make_ref};
 use async_trait::async_trait;
-use futures::{StreamExt, TryStreamExt, stream};
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use schemars::{JsonSchema, schema_for};
+use serde::de::DeserializeOwned;
 use std::collections::HashMap;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::RwLock;
+use tracing::{Instrument, Span};
 use uuid::Uuid;
 
+/// The default number of times an invalid typed output is sent back to the model.
+pub const DEFAULT_OUTPUT_RETRIES: usize = 2;
+
+/// The tool the model calls with its typed output when the model has no native
+/// structured output mode.
+const OUTPUT_TOOL_NAME: &str = "final_output";
+
 pub struct Agent<M: Completion> {
     /// The model to use.
     pub model: Ref<M>,
-    /// Indexed storage for the agent.
-    pub store_indices: Vec<(usize, Box<dyn Storage + Send>)>,
-    /// The tools to use.
-    pub tools: Ref<Vec<Box<dyn Tool>>>,
+    /// The retrieval pipeline over the store indices of the agent.
+    pub retrieval: RetrievalPipeline,
+    /// The local and MCP tools to use.
+    pub tools: Ref<ToolRegistry>,
     /// Knowledge sources for the agent.
     pub knowledges: Arc<Vec<Box<dyn Knowledge>>>,
+    /// Index injecting only the knowledge chunks relevant to the prompt, the whole knowledge
+    /// sources are injected when it is not set.
+    pub knowledge_index: Option<Arc<KnowledgeIndex>>,
     /// Agent memory.
     pub memory: Option<Ref<dyn Memory>>,
     /// The unique ID of the agent.
@@ -36,8 +64,28 @@ pub struct Agent<M: Completion> {
     pub temperature: Option<f32>,
     /// Maximum number of tokens for the completion.
     pub max_tokens: Option<usize>,
-    /// The MCP client used to communicate with the MCP server
-    mcp_clients: Ref<Vec<MCPClient>>,
+    /// Maximum number of model calls in one prompt, each tool calling round counts as one step.
+    pub max_steps: usize,
+    /// Maximum number of tool calls of one model step running at the same time.
+    pub max_concurrent_tools: usize,
+    /// Hard limits of one prompt on tokens, cost, wall time and tool calls.
+    pub budget: Budget,
+    /// Timeout of the tools which do not declare their own.
+    pub tool_timeout: Option<Duration>,
+    /// Approver consulted before running tools which require approval.
+    pub approver: Option<Arc<dyn ToolApprover>>,
+    /// Middlewares run in order around model and tool calls.
+    pub middlewares: Vec<Arc<dyn Middleware>>,
+    /// Number of times an invalid typed output is sent back to the model before giving up.
+    pub output_retries: usize,
+    /// Ledger recording the token usage and cost of the agent runs.
+    pub ledger: Option<Arc<UsageLedger>>,
+    /// The session the usage of the agent runs is attributed to.
+    pub session_id: Option<String>,
+    /// The configs of the MCP servers started by the agent, keyed by the server name.
+    mcp_servers: HashMap<String, MCPServerConfig>,
+    /// The last message handed off to each agent with a memory, keyed by the agent ID.
+    pub(crate) handoffs: Mutex<HashMap<Uuid, crate::memory::Message>>,
 }
 
 impl<M: Completion> Agent<M>
@@ -48,58 +96,116 @@ where
     pub fn new(name: impl ToString, model: M) -> Agent<M> {
         Agent {
             model: Arc::new(RwLock::new(model)),
-            tools: make_ref(vec![]),
-            store_indices: vec![],
+            tools: make_ref(ToolRegistry::new()),
+            retrieval: RetrievalPipeline::new(),
             id: Uuid::new_v4(),
             name: name.to_string(),
             preamble: String::new(),
             temperature: None,
             max_tokens: None,
+            max_steps: DEFAULT_MAX_STEPS,
+            max_concurrent_tools: DEFAULT_MAX_CONCURRENT_TOOLS,
+            budget: Budget::default(),
+            tool_timeout: None,
+            approver: None,
+            middlewares: Vec::new(),
+            output_retries: DEFAULT_OUTPUT_RETRIES,
+            ledger: None,
+            session_id: None,
             knowledges: Arc::new(Vec::new()),
+            knowledge_index: None,
             memory: None,
-            mcp_clients: make_ref(vec![]),
+            mcp_servers: HashMap::new(),
+            handoffs: Mutex::new(HashMap::new()),
         }
     }
 
     /// Creates a new agent with some tools
+    ///
+    /// # Panics
+    ///
+    /// Panics if two tools have the same name, see [`Agent::try_new_with_tools`].
     pub fn new_with_tools<I>(name: impl ToString, model: M, tools: I) -> Agent<M>
     where
         I: IntoIterator<Item = Box<dyn Tool>>,
     {
-        Agent {
-            model: Arc::new(RwLock::new(model)),
-            tools: make_ref(tools.into_iter().collect()),
-            store_indices: vec![],
-            id: Uuid::new_v4(),
-            name: name.to_string(),
-            preamble: String::new(),
-            temperature: None,
-            max_tokens: None,
-            knowledges: Arc::new(Vec::new()),
-            memory: None,
-            mcp_clients: make_ref(vec![]),
+        match Agent::try_new_with_tools(name, model, tools) {
+            Ok(agent) => agent,
+            Err(err) => panic!("{err}"),
         }
     }
 
+    /// Creates a new agent with some tools, failing if two tools have the same name.
+    pub fn try_new_with_tools<I>(
+        name: impl ToString,
+        model: M,
+        tools: I,
+    ) -> Result<Agent<M>, ToolRegistryError>
+    where
+        I: IntoIterator<Item = Box<dyn Tool>>,
+    {
+        let mut registry = ToolRegistry::new();
+        for tool in tools {
+            registry.register(tool)?;
+        }
+        let agent = Agent::new(name, model);
+        Ok(Agent {
+            tools: make_ref(registry),
+            ..agent
+        })
+    }
+
     /// Add a tool into the agent
+    ///
+    /// # Panics
+    ///
+    /// Panics if a tool with the same name is already registered, see [`Agent::try_tool`].
     pub async fn tool(self, tool: impl Tool + 'static) -> Self {
-        let mut self_tools = self.tools.write().await;
-        self_tools.push(Box::new(tool));
-        drop(self_tools);
-        self
+        match self.try_tool(tool).await {
+            Ok(agent) => agent,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    /// Add a tool into the agent, failing if a tool with the same name is already registered.
+    pub async fn try_tool(self, tool: impl Tool + 'static) -> Result<Self, ToolRegistryError> {
+        self.tools.write().await.register(Box::new(tool))?;
+        Ok(self)
     }
 
     /// Add some tools into the agent
+    ///
+    /// # Panics
+    ///
+    /// Panics if a tool with the same name is already registered, see [`Agent::try_tools`].
     pub async fn tools<I>(self, tools: I) -> Self
+    where
+        I: IntoIterator<Item = Box<dyn Tool>>,
+    {
+        match self.try_tools(tools).await {
+            Ok(agent) => agent,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    /// Add some tools into the agent, failing if a tool with the same name is already
+    /// registered. The tools before the conflicting one stay registered.
+    pub async fn try_tools<I>(self, tools: I) -> Result<Self, ToolRegistryError>
     where
         I: IntoIterator<Item = Box<dyn Tool>>,
     {
         let mut self_tools = self.tools.write().await;
         for tool in tools.into_iter() {
-            self_tools.push(tool);
+            self_tools.register(tool)?;
         }
         drop(self_tools);
-        self
+        Ok(self)
+    }
+
+    /// Disable a tool by its qualified name, so that it is no longer offered to the model.
+    pub async fn disable_tool(self, name: &str) -> Result<Self, ToolRegistryError> {
+        self.tools.write().await.disable(name)?;
+        Ok(self)
     }
 
     /// Adds a memory to the agent.
@@ -108,9 +214,40 @@ where
         self
     }
 
-    /// Adds a storage index to the agent.
-    pub fn store_index(mut self, sample: usize, store: impl Storage + 'static) -> Self {
-        self.store_indices.push((sample, Box::new(store)));
+    /// Adds a storage index to the agent, retrieving up to `sample` documents for each prompt.
+    pub fn store_index(self, sample: usize, store: impl Storage + 'static) -> Self {
+        self.retriever(Retriever::new(store).top_k(sample))
+    }
+
+    /// Adds a retriever over a storage index to the retrieval pipeline of the agent.
+    pub fn retriever(mut self, retriever: Retriever) -> Self {
+        self.retrieval = self.retrieval.retriever(retriever);
+        self
+    }
+
+    /// Sets the retrieval pipeline of the agent, replacing the added storage indices.
+    pub fn retrieval(mut self, retrieval: RetrievalPipeline) -> Self {
+        self.retrieval = retrieval;
+        self
+    }
+
+    /// Injects only the knowledge chunks relevant to the prompt, retrieved from the index,
+    /// instead of the whole knowledge sources.
+    pub fn knowledge_index(mut self, index: KnowledgeIndex) -> Self {
+        self.knowledge_index = Some(Arc::new(index));
+        self
+    }
+
+    /// Records the token usage and cost of the agent runs into a ledger, which may be shared
+    /// with other agents.
+    pub fn ledger(mut self, ledger: Arc<UsageLedger>) -> Self {
+        self.ledger = Some(ledger);
+        self
+    }
+
+    /// Sets the session the usage of the agent runs is attributed to.
+    pub fn session_id(mut self, session_id: impl ToString) -> Self {
+        self.session_id = Some(session_id.to_string());
         self
     }
 
@@ -120,36 +257,96 @@ where
         self
     }
 
-    /// Set the MCP client.
-    pub async fn mcp_client(self, mcp_client: MCPClient) -> Self {
-        let mut mcp_clients = self.mcp_clients.write().await;
-        mcp_clients.push(mcp_client);
-        drop(mcp_clients);
+    /// Set the maximum number of model calls in one prompt.
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
         self
     }
 
+    /// Set the hard limits of one prompt, the prompt fails with [`TaskError::BudgetExceeded`]
+    /// when one is hit.
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Set the maximum number of tool calls of one model step running at the same time.
+    pub fn max_concurrent_tools(mut self, max_concurrent_tools: usize) -> Self {
+        self.max_concurrent_tools = max_concurrent_tools;
+        self
+    }
+
+    /// Set the timeout of the tools which do not declare their own.
+    pub fn tool_timeout(mut self, tool_timeout: Duration) -> Self {
+        self.tool_timeout = Some(tool_timeout);
+        self
+    }
+
+    /// Set the approver consulted before running tools which require approval.
+    pub fn approver(mut self, approver: impl ToolApprover + 'static) -> Self {
+        self.approver = Some(Arc::new(approver));
+        self
+    }
+
+    /// Add a middleware run after the already registered ones.
+    pub fn middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// Set the number of times an invalid typed output is sent back to the model.
+    pub fn output_retries(mut self, output_retries: usize) -> Self {
+        self.output_retries = output_retries;
+        self
+    }
+
+    /// Set the MCP client, its tools keep their names since the client has no server name.
+    pub async fn mcp_client(self, mcp_client: MCPClient) -> anyhow::Result<Self, MCPError> {
+        self.tools.write().await.register_mcp(None, mcp_client)?;
+        Ok(self)
+    }
+
     /// Set the MCP server config path.
     pub async fn mcp_config_path<P: AsRef<Path>>(self, path: P) -> anyhow::Result<Self, MCPError> {
-        let clients = setup_mcp_clients(path).await?;
-        let mut mcp_clients = self.mcp_clients.write().await;
-        for (_, client) in clients {
-            mcp_clients.push(client);
+        let config = read_mcp_config(path).await?;
+        self.mcp_servers(config.mcp_servers).await
+    }
+
+    /// Start the MCP servers and add their clients, keyed by the server name.
+    pub async fn mcp_servers(
+        mut self,
+        servers: HashMap<String, MCPServerConfig>,
+    ) -> anyhow::Result<Self, MCPError> {
+        let clients = start_mcp_clients(&servers).await?;
+        let mut tools = self.tools.write().await;
+        for (server, client) in clients {
+            tools.register_mcp(Some(&server), client)?;
         }
-        drop(mcp_clients);
+        drop(tools);
+        self.mcp_servers.extend(servers);
         Ok(self)
     }
 
+    /// Returns the configs of the MCP servers started by the agent, keyed by the server name.
+    #[inline]
+    pub fn mcp_server_configs(&self) -> &HashMap<String, MCPServerConfig> {
+        &self.mcp_servers
+    }
+
     /// Set the MCP server config path.
     pub async fn start_mcp_servers<P: AsRef<Path>>(
         &mut self,
         path: P,
     ) -> anyhow::Result<(), MCPError> {
-        let clients = setup_mcp_clients(path).await?;
-        let mut mcp_clients = self.mcp_clients.write().await;
-        mcp_clients.clear();
-        for (_, client) in clients {
-            mcp_clients.push(client);
+        let config = read_mcp_config(path).await?;
+        let clients = start_mcp_clients(&config.mcp_servers).await?;
+        let mut tools = self.tools.write().await;
+        tools.remove_mcp_tools();
+        for (server, client) in clients {
+            tools.register_mcp(Some(&server), client)?;
         }
+        drop(tools);
+        self.mcp_servers = config.mcp_servers;
         Ok(())
     }
 
@@ -160,7 +357,7 @@ where
         sse_url: S,
         env: HashMap<String, String>,
     ) -> anyhow::Result<Self> {
-        Ok(self.mcp_client(sse_client(sse_url, env).await?).await)
+        Ok(self.mcp_client(sse_client(sse_url, env).await?).await?)
     }
 
     /// Set the MCP sse client.
@@ -173,82 +370,438 @@ where
     ) -> anyhow::Result<Self> {
         Ok(self
             .mcp_client(stdio_client(command, args, env).await?)
-            .await)
+            .await?)
     }
 }
 
-#[async_trait]
-impl<M: Completion + Send + Sync> Chat for Agent<M> {
-    /// Processes a prompt using the agent.
-    async fn prompt(&self, prompt: &str) -> Result<String, TaskError> {
-        // Add chat conversion history.
-        let history = if let Some(memory) = &self.memory {
+impl<M: Completion + Send + Sync> Agent<M> {
+    /// Processes a prompt using the agent and returns the output with the run trace.
+    pub async fn prompt_with_trace(&self, prompt: &str) -> Result<RunOutput, TaskError> {
+        let history = self.memory_history(prompt).await;
+        self.chat_with_trace(prompt, history).await
+    }
+
+    /// Processes a prompt and history using the agent and returns the output with the run trace.
+    pub async fn chat_with_trace(
+        &self,
+        prompt: &str,
+        history: Vec<Message>,
+    ) -> Result<RunOutput, TaskError> {
+        self.invoke(prompt, history, CallOptions::default()).await
+    }
+
+    /// Processes a prompt and history using the agent and returns the output with the run
+    /// trace, stopping the model and tool calls when the call is cancelled or its deadline
+    /// passes.
+    pub async fn invoke(
+        &self,
+        prompt: &str,
+        history: Vec<Message>,
+        options: CallOptions,
+    ) -> Result<RunOutput, TaskError> {
+        let span = self.chat_span().await;
+        let result = async {
+            let mut req = self.build_request(prompt, history).await?;
+            req.options = options;
+            self.executor().invoke(req).await.map_err(execution_error)
+        }
+        .instrument(span.clone())
+        .await;
+        match &result {
+            Ok(run) => telemetry::record_trace(&span, &run.trace),
+            Err(err) => telemetry::record_error(&span, err),
+        }
+        result
+    }
+
+    /// Processes a prompt using the agent and parses the output into `T`.
+    ///
+    /// The JSON schema of `T` is sent through the native structured output mode of the model
+    /// when it supports one, otherwise as a tool the model calls with its output. When the
+    /// output does not match `T`, the error is sent back to the model up to `output_retries`
+    /// times before giving up. Only the prompt and the valid output are added to the memory,
+    /// the failed attempts are kept in the request history.
+    pub async fn prompt_typed<T: JsonSchema + DeserializeOwned>(
+        &self,
+        prompt: &str,
+    ) -> Result<T, TaskError> {
+        let span = self.chat_span().await;
+        self.typed_output(prompt)
+            .instrument(span.clone())
+            .await
+            .inspect_err(|err| telemetry::record_error(&span, err))
+    }
+
+    async fn typed_output<T: JsonSchema + DeserializeOwned>(
+        &self,
+        prompt: &str,
+    ) -> Result<T, TaskError> {
+        let schema = serde_json::to_value(schema_for!(T))
+            .map_err(|err| TaskError::ExecutionError(err.to_string()))?;
+        let name: String = schema
+            .get("title")
+            .and_then(|title| title.as_str())
+            .unwrap_or("output")
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+            .collect();
+        let native = self.model.read().await.supports_response_format();
+        let mut history = self.memory_history(prompt).await;
+        let original = prompt;
+        let mut prompt = prompt.to_string();
+        let mut retries = 0;
+        loop {
+            let mut req = self.build_request(&prompt, history.clone()).await?;
+            let mut executor = self.executor().memory(None);
+            if native {
+                req.response_format = Some(ResponseFormat::json_schema(&name, schema.clone()));
+            } else {
+                req.preamble.push_str(&format!(
+                    "\nYou MUST call the tool `{OUTPUT_TOOL_NAME}` with your final answer."
+                ));
+                executor = executor.output_tool(ToolDefinition {
+                    name: OUTPUT_TOOL_NAME.to_string(),
+                    description: "Submit the final answer.".to_string(),
+                    parameters: schema.clone(),
+                });
+            }
+            let run = executor.invoke(req).await.map_err(execution_error)?;
+            let err = match parse_typed_output(&run.output) {
+                Ok(output) => {
+                    if let Some(memory) = &self.memory {
+                        let mut memory = memory.write().await;
+                        memory.add_user_message(original);
+                        memory.add_ai_message(&run.output);
+                    }
+                    return Ok(output);
+                }
+                Err(err) => err,
+            };
+            if retries >= self.output_retries {
+                return Err(TaskError::InvalidOutput(format!(
+                    "{err} (after {retries} retries)"
+                )));
+            }
+            retries += 1;
+            history.push(Message::user(&prompt));
+            history.push(Message::assistant(&run.output));
+            prompt = format!(
+                "The output is invalid: {err}\nAnswer again with only the corrected output matching the JSON schema."
+            );
+        }
+    }
+
+    /// Creates the span of a run of the agent.
+    async fn chat_span(&self) -> Span {
+        let model = self.model.read().await.model_id();
+        telemetry::agent_span(
+            &self.name,
+            &self.id.to_string(),
+            &model,
+            self.session_id.as_deref(),
+        )
+    }
+
+    /// Returns the chat conversion history stored in the agent memory which is relevant
+    /// to the prompt.
+    async fn memory_history(&self, prompt: &str) -> Vec<Message> {
+        if let Some(memory) = &self.memory {
             let memory = memory.read().await;
             memory
-                .messages()
-                .iter()
-                .map(|m| Message {
-                    role: m.message_type.type_string(),
-                    content: m.content.clone(),
-                })
+                .relevant_messages(prompt)
+                .await
+                .into_iter()
+                .map(Message::from)
                 .collect()
         } else {
             vec![]
-        };
-        self.chat(prompt, history).await
+        }
     }
 
-    /// Processes a prompt using the agent.
-    async fn chat(&self, prompt: &str, history: Vec<Message>) -> Result<String, TaskError> {
-        let mut executor = Executor::new(
+    /// Creates an executor sharing the model, tools and memory of the agent.
+    fn executor(&self) -> Executor<M> {
+        Executor::new(
             self.model.clone(),
             self.knowledges.clone(),
             self.tools.clone(),
             self.memory.clone(),
-            self.mcp_clients.clone(),
-        );
+        )
+        .knowledge_index(self.knowledge_index.clone())
+        .max_steps(self.max_steps)
+        .max_concurrent_tools(self.max_concurrent_tools)
+        .budget(self.budget.clone())
+        .tool_timeout(self.tool_timeout)
+        .approver(self.approver.clone())
+        .middlewares(self.middlewares.clone())
+        .ledger(self.ledger.clone(), &self.name, self.session_id.clone())
+    }
+
+    /// Builds the completion request with the agent settings, tools and the documents
+    /// retrieved by the retrieval pipeline.
+    async fn build_request(
+        &self,
+        prompt: &str,
+        history: Vec<Message>,
+    ) -> Result<Request, TaskError> {
         let mut req = Request::new(prompt.to_string(), self.preamble.clone());
         req.history = history;
         req.max_tokens = self.max_tokens;
         req.temperature = self.temperature;
-        let tools = self.tools.read().await;
-        req.tools = tools
-            .iter()
-            .map(|tool| tool.definition())
-            .collect::<Vec<_>>();
-        let mcp_clients = self.mcp_clients.read().await;
-        for client in mcp_clients.iter() {
-            for tool in client.tools.values() {
-                req.tools.push(tool.clone());
-            }
+        req.tools = self.tools.read().await.definitions();
+        if !self.retrieval.is_empty() {
+            let span = telemetry::retrieval_span("documents");
+            req.documents = match self
+                .retrieval
+                .retrieve(prompt)
+                .instrument(span.clone())
+                .await
+            {
+                Ok(documents) => {
+                    span.record("retrieval.results", documents.len() as u64);
+                    documents
+                }
+                Err(err) => {
+                    telemetry::record_error(&span, &err);
+                    return Err(TaskError::ExecutionError(err.to_string()));
+                }
+            };
         }
-        req.documents = stream::iter(self.store_indices.iter())
-            .then(|(num_sample, storage)| async {
-                Ok::<_, VectorStoreError>(
-                    storage
-                        .search(prompt, *num_sample, 0.5)
-                        .await?
-                        .into_iter()
-                        .map(|(id, text, _)| Document {
-                            id,
-                            text,
-                            additional_props: HashMap::new(),
-                        })
-                        .collect::<Vec<_>>(),
-                )
-            })
-            .try_fold(vec![], |mut acc, docs| async {
-                acc.extend(docs);
-                Ok(acc)
-            })
-            .await
-            .map_err(|err| TaskError::ExecutionError(err.to_string()))?;
+        Ok(req)
+    }
+}
+
+/// Converts an executor error into a task error, keeping budget and interruption errors
+/// typed.
+fn execution_error(err: anyhow::Error) -> TaskError {
+    let err = match err.downcast::<BudgetExceeded>() {
+        Ok(BudgetExceeded { limit, trace }) => return TaskError::BudgetExceeded { limit, trace },
+        Err(err) => err,
+    };
+    match err.downcast::<Interrupted>() {
+        Ok(interrupted) => interrupted.into(),
+        Err(err) => TaskError::ExecutionError(err.to_string()),
+    }
+}
+
+/// Parses a typed output, either plain JSON or JSON in a markdown code block.
+fn parse_typed_output<T: DeserializeOwned>(output: &str) -> Result<T, String> {
+    match serde_json::from_str(output) {
+        Ok(output) => Ok(output),
+        Err(err) => match parse_json_markdown(output) {
+            Ok(value) => serde_json::from_value(value).map_err(|err| err.to_string()),
+            Err(_) => Err(err.to_string()),
+        },
+    }
+}
+
+impl<M: StreamingCompletion + Send + Sync + 'static> Agent<M> {
+    /// Processes a prompt using the agent and streams the model output as it is generated.
+    pub async fn prompt_stream(
+        &self,
+        prompt: &str,
+    ) -> Result<BoxStream<'static, Result<CompletionDelta, TaskError>>, TaskError> {
+        let history = self.memory_history(prompt).await;
+        self.chat_stream(prompt, history).await
+    }
+
+    /// Processes a prompt and history using the agent and streams the model output as it
+    /// is generated.
+    pub async fn chat_stream(
+        &self,
+        prompt: &str,
+        history: Vec<Message>,
+    ) -> Result<BoxStream<'static, Result<CompletionDelta, TaskError>>, TaskError> {
+        let req = self.build_request(prompt, history).await?;
+        Ok(self
+            .executor()
+            .invoke_stream(req)
+            .map_err(execution_error)
+            .boxed())
+    }
+}
+
+#[async_trait]
+impl<M: Completion + Send + Sync> Chat for Agent<M> {
+    /// Processes a prompt using the agent.
+    async fn prompt(&self, prompt: &str) -> Result<String, TaskError> {
+        Ok(self.prompt_with_trace(prompt).await?.output)
+    }
 
-        let response = executor
-            .invoke(req)
+    /// Processes a prompt using the agent.
+    async fn chat(&self, prompt: &str, history: Vec<Message>) -> Result<String, TaskError> {
+        Ok(self.chat_with_trace(prompt, history).await?.output)
+    }
+
+    /// Processes a prompt using the agent, stopping when the call is cancelled or its
+    /// deadline passes.
+    async fn prompt_with_options(
+        &self,
+        prompt: &str,
+        options: CallOptions,
+    ) -> Result<String, TaskError> {
+        let history = self.memory_history(prompt).await;
+        Ok(self.invoke(prompt, history, options).await?.output)
+    }
+
+    /// Processes a prompt and history using the agent, stopping when the call is cancelled
+    /// or its deadline passes.
+    async fn chat_with_options(
+        &self,
+        prompt: &str,
+        history: Vec<Message>,
+        options: CallOptions,
+    ) -> Result<String, TaskError> {
+        Ok(self.invoke(prompt, history, options).await?.output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::WindowBufferMemory;
+    use crate::mock::{MockCompletion, MockResponse};
+    use crate::tool::{StructureTool, ToolError};
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    struct AddTool;
+
+    #[derive(JsonSchema, Serialize, Deserialize)]
+    struct AddInput {
+        x: usize,
+        y: usize,
+    }
+
+    #[async_trait]
+    impl StructureTool for AddTool {
+        type Input = AddInput;
+        type Output = usize;
+
+        fn name(&self) -> &str {
+            "add"
+        }
+
+        async fn run_with_args(&self, input: Self::Input) -> Result<Self::Output, ToolError> {
+            Ok(input.x + input.y)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_agent_tool_loop() {
+        let mock = MockCompletion::new()
+            .respond(MockResponse::tool_call(
+                "call_1",
+                "add",
+                json!({"x": 1, "y": 2}),
+            ))
+            .respond("The sum is 3.");
+        let agent = Agent::new("calculator", mock.clone())
+            .preamble("You add numbers.")
+            .tool(AddTool)
+            .await;
+
+        let run = agent.prompt_with_trace("Add 1 and 2").await.unwrap();
+        assert_eq!(run.output, "The sum is 3.");
+        assert_eq!(run.trace.steps.len(), 2);
+        assert_eq!(run.trace.steps[0].tool_calls[0].output, "3");
+
+        mock.assert_calls(2);
+        mock.assert_done();
+        mock.assert_preamble(0, "You add numbers.");
+        mock.assert_tools(0, &["add"]);
+        mock.assert_history(0, &[]);
+        mock.assert_history(
+            1,
+            &[("user", "Add 1 and 2"), ("assistant", ""), ("tool", "3")],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_agent_memory() {
+        let mock = MockCompletion::new().respond("Hello!").respond("Bye!");
+        let agent = Agent::new("greeter", mock.clone()).memory(WindowBufferMemory::new(10));
+
+        assert_eq!(agent.prompt("Hi").await.unwrap(), "Hello!");
+        assert_eq!(agent.prompt("Goodbye").await.unwrap(), "Bye!");
+        mock.assert_history(1, &[("user", "Hi"), ("assistant", "Hello!")]);
+        mock.assert_prompt_contains(1, "Goodbye");
+    }
+
+    #[tokio::test]
+    async fn test_agent_tool_conflicts() {
+        let mock = MockCompletion::new();
+        assert!(matches!(
+            Agent::try_new_with_tools(
+                "calculator",
+                mock.clone(),
+                [Box::new(AddTool) as Box<dyn Tool>, Box::new(AddTool)],
+            ),
+            Err(ToolRegistryError::Conflict { .. })
+        ));
+
+        let agent = Agent::new("calculator", mock)
+            .try_tool(AddTool)
             .await
-            .map_err(|err| TaskError::ExecutionError(err.to_string()))?;
+            .unwrap();
+        assert!(matches!(
+            agent.try_tool(AddTool).await,
+            Err(ToolRegistryError::Conflict { .. })
+        ));
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+    struct Person {
+        name: String,
+        age: u8,
+    }
+
+    #[tokio::test]
+    async fn test_agent_typed_output_retry() {
+        let mock = MockCompletion::new()
+            .respond(MockResponse::tool_call(
+                "call_1",
+                "final_output",
+                json!({"name": "Alice"}),
+            ))
+            .respond(MockResponse::tool_call(
+                "call_2",
+                "final_output",
+                json!({"name": "Alice", "age": 30}),
+            ));
+        let agent = Agent::new("extractor", mock.clone()).memory(WindowBufferMemory::new(10));
+
+        let person: Person = agent.prompt_typed("Alice is 30 years old.").await.unwrap();
+        assert_eq!(
+            person,
+            Person {
+                name: "Alice".to_string(),
+                age: 30
+            }
+        );
+        mock.assert_calls(2);
+        mock.assert_prompt_contains(1, "missing field `age`");
+        mock.assert_history(
+            1,
+            &[
+                ("user", "Alice is 30 years old."),
+                ("assistant", r#"{"name":"Alice"}"#),
+            ],
+        );
+        // The failed attempt stays out of the memory.
+        let memory = agent.memory.as_ref().unwrap().read().await.messages();
+        assert_eq!(memory.len(), 2);
+        assert_eq!(memory[0].content, "Alice is 30 years old.");
+        assert_eq!(memory[1].content, r#"{"age":30,"name":"Alice"}"#);
+    }
 
-        Ok(response)
+    #[tokio::test]
+    async fn test_agent_model_error() {
+        let mock = MockCompletion::new().fail("overloaded");
+        let agent = Agent::new("failing", mock.clone());
+        assert!(matches!(
+            agent.prompt("Hi").await,
+            Err(TaskError::ExecutionError(message)) if message.contains("overloaded")
+        ));
+        mock.assert_calls(1);
     }
 }
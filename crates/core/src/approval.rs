@@ -0,0 +1,51 @@
+use crate::chat::ToolCall;
+use async_trait::async_trait;
+
+/// The decision of a [`ToolApprover`] on a tool call requested by the model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApprovalDecision {
+    /// Run the tool call as requested.
+    Approve,
+    /// Do not run the tool call, the reason is sent back to the model as the tool result.
+    Deny { reason: String },
+    /// Run the tool call with the given JSON encoded arguments instead.
+    Edit { arguments: String },
+}
+
+impl ApprovalDecision {
+    /// Creates a denial with the given reason.
+    #[inline]
+    pub fn deny(reason: impl ToString) -> Self {
+        Self::Deny {
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Creates an approval with edited arguments.
+    #[inline]
+    pub fn edit(arguments: impl ToString) -> Self {
+        Self::Edit {
+            arguments: arguments.to_string(),
+        }
+    }
+}
+
+/// A trait to gate the execution of tool calls, e.g. by asking a human.
+///
+/// The executor consults the approver before running any MCP tool or any local tool
+/// whose [`Tool::requires_approval`](crate::tool::Tool::requires_approval) returns true.
+#[async_trait]
+pub trait ToolApprover: Send + Sync {
+    /// Decides whether the tool call may run.
+    async fn approve(&self, call: &ToolCall) -> ApprovalDecision;
+}
+
+#[async_trait]
+impl<F> ToolApprover for F
+where
+    F: Fn(&ToolCall) -> ApprovalDecision + Send + Sync,
+{
+    async fn approve(&self, call: &ToolCall) -> ApprovalDecision {
+        self(call)
+    }
+}
@@ -0,0 +1,175 @@
+use crate::chat::TokenUsage;
+use crate::executor::RunTrace;
+use crate::usage::{PricingTable, UsageContext, UsageRecord};
+use std::fmt;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// Hard limits of one executor run, all limits are disabled by default.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Budget {
+    /// The maximum number of prompt and completion tokens over all model calls.
+    pub max_total_tokens: Option<u64>,
+    /// The maximum cost of the model calls, priced with the pricing table of the ledger.
+    pub max_cost: Option<f64>,
+    /// The maximum wall time of the run.
+    pub max_duration: Option<Duration>,
+    /// The maximum number of tool calls.
+    pub max_tool_calls: Option<usize>,
+}
+
+impl Budget {
+    /// Creates a budget without any limit.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of prompt and completion tokens over all model calls.
+    pub fn max_total_tokens(mut self, max_total_tokens: u64) -> Self {
+        self.max_total_tokens = Some(max_total_tokens);
+        self
+    }
+
+    /// Sets the maximum cost of the model calls.
+    ///
+    /// The cost is computed with the pricing table of the agent ledger, the limit is not
+    /// enforced for models without a price.
+    pub fn max_cost(mut self, max_cost: f64) -> Self {
+        self.max_cost = Some(max_cost);
+        self
+    }
+
+    /// Sets the maximum wall time of the run.
+    pub fn max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+
+    /// Sets the maximum number of tool calls.
+    pub fn max_tool_calls(mut self, max_tool_calls: usize) -> Self {
+        self.max_tool_calls = Some(max_tool_calls);
+        self
+    }
+}
+
+/// The budget limit which aborted a run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BudgetLimit {
+    TotalTokens(u64),
+    Cost(f64),
+    Duration(Duration),
+    ToolCalls(usize),
+}
+
+impl fmt::Display for BudgetLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BudgetLimit::TotalTokens(limit) => write!(f, "maximum of {limit} total tokens"),
+            BudgetLimit::Cost(limit) => write!(f, "maximum cost of {limit}"),
+            BudgetLimit::Duration(limit) => write!(f, "maximum wall time of {limit:?}"),
+            BudgetLimit::ToolCalls(limit) => write!(f, "maximum of {limit} tool calls"),
+        }
+    }
+}
+
+/// The error of a run aborted by its budget, with the trace of the steps done so far.
+#[derive(Debug, thiserror::Error)]
+#[error("Budget exceeded: {limit}")]
+pub struct BudgetExceeded {
+    pub limit: BudgetLimit,
+    pub trace: RunTrace,
+}
+
+/// Tracks the consumption of a run against its budget.
+pub(crate) struct BudgetTracker {
+    budget: Budget,
+    pricing: Option<PricingTable>,
+    started: Instant,
+    total_tokens: u64,
+    cost: f64,
+    tool_calls: usize,
+}
+
+impl BudgetTracker {
+    /// Starts tracking a run.
+    pub(crate) fn new(budget: Budget, pricing: Option<PricingTable>) -> Self {
+        Self {
+            budget,
+            pricing,
+            started: Instant::now(),
+            total_tokens: 0,
+            cost: 0.0,
+            tool_calls: 0,
+        }
+    }
+
+    /// Adds the usage of a model call.
+    pub(crate) fn add_usage(&mut self, context: &UsageContext, usage: &TokenUsage) {
+        self.total_tokens += usage.prompt_tokens as u64 + usage.completion_tokens as u64;
+        if let Some(pricing) = &self.pricing {
+            self.cost += pricing
+                .cost(&UsageRecord::completion(context, usage))
+                .unwrap_or_default();
+        }
+    }
+
+    /// Checks the limits before running the next tool calls of the run.
+    pub(crate) fn check(&mut self, tool_calls: usize) -> Result<(), BudgetLimit> {
+        if let Some(limit) = self.budget.max_total_tokens {
+            if self.total_tokens > limit {
+                return Err(BudgetLimit::TotalTokens(limit));
+            }
+        }
+        if let Some(limit) = self.budget.max_cost {
+            if self.cost > limit {
+                return Err(BudgetLimit::Cost(limit));
+            }
+        }
+        if let Some(limit) = self.budget.max_duration {
+            if self.started.elapsed() >= limit {
+                return Err(BudgetLimit::Duration(limit));
+            }
+        }
+        if let Some(limit) = self.budget.max_tool_calls {
+            if self.tool_calls + tool_calls > limit {
+                return Err(BudgetLimit::ToolCalls(limit));
+            }
+        }
+        self.tool_calls += tool_calls;
+        Ok(())
+    }
+
+    /// Awaits a model or tool call, failing when it exceeds the remaining wall time.
+    pub(crate) async fn limit_time<F: Future>(&self, future: F) -> Result<F::Output, BudgetLimit> {
+        match self.budget.max_duration {
+            Some(limit) => {
+                let remaining = limit.saturating_sub(self.started.elapsed());
+                tokio::time::timeout(remaining, future)
+                    .await
+                    .map_err(|_| BudgetLimit::Duration(limit))
+            }
+            None => Ok(future.await),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_tracker_limits() {
+        let mut tracker =
+            BudgetTracker::new(Budget::new().max_total_tokens(100).max_tool_calls(3), None);
+        let usage = TokenUsage {
+            prompt_tokens: 40,
+            completion_tokens: 20,
+            ..Default::default()
+        };
+        tracker.add_usage(&UsageContext::default(), &usage);
+        assert_eq!(tracker.check(2), Ok(()));
+        assert_eq!(tracker.check(2), Err(BudgetLimit::ToolCalls(3)));
+        tracker.add_usage(&UsageContext::default(), &usage);
+        assert_eq!(tracker.check(0), Err(BudgetLimit::TotalTokens(100)));
+    }
+}
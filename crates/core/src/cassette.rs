@@ -0,0 +1,213 @@
+pub use alith_interface::cassette::{
+    Cassette, CassetteError, CassetteMode, Interaction, MatchMode, request_hash,
+};
+
+use crate::chat::{
+    Completion, CompletionError, Document, Message, Request, ResponseContent, ResponseFormat,
+    ResponseTokenUsage, ResponseToolCalls, TokenUsage, ToolCall, ToolDefinition,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// A completion engine recording the calls of another one into a cassette, or replaying them
+/// from it without calling any model.
+///
+/// The requests are matched by the hash of their prompt, preamble, history, tools, documents
+/// and sampling parameters, so that a prompt change is detected in strict replay mode.
+pub struct CassetteCompletion<M: Completion> {
+    model: Option<M>,
+    model_id: String,
+    supports_response_format: bool,
+    cassette: Arc<Cassette>,
+}
+
+/// The response of a [`CassetteCompletion`], as recorded in the cassette.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub content: String,
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(default)]
+    pub usage: TokenUsage,
+}
+
+/// The parts of a request matched against the recordings.
+#[derive(Serialize)]
+struct RecordedRequest<'a> {
+    prompt: &'a str,
+    preamble: &'a str,
+    knowledges: &'a [String],
+    history: &'a [Message],
+    tools: &'a [ToolDefinition],
+    documents: &'a [Document],
+    max_tokens: Option<usize>,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    top_k: Option<usize>,
+    response_format: &'a Option<ResponseFormat>,
+}
+
+impl<M: Completion> CassetteCompletion<M> {
+    /// Wraps a model, its calls are recorded or replayed according to the cassette mode.
+    pub fn new(model: M, cassette: Arc<Cassette>) -> Self {
+        Self {
+            model_id: model.model_id(),
+            supports_response_format: model.supports_response_format(),
+            model: Some(model),
+            cassette,
+        }
+    }
+
+    /// Replays the calls recorded in the cassette without any model to call, the token usage
+    /// is attributed to `model_id`.
+    ///
+    /// `supports_response_format` must match the recorded model, it changes the requests
+    /// built by the agents.
+    pub fn replay(
+        model_id: impl ToString,
+        supports_response_format: bool,
+        cassette: Arc<Cassette>,
+    ) -> Self {
+        Self {
+            model: None,
+            model_id: model_id.to_string(),
+            supports_response_format,
+            cassette,
+        }
+    }
+
+    #[inline]
+    pub fn cassette(&self) -> &Arc<Cassette> {
+        &self.cassette
+    }
+}
+
+impl<M> Completion for CassetteCompletion<M>
+where
+    M: Completion + Send + Sync,
+    M::Response: Send,
+{
+    type Response = RecordedResponse;
+
+    async fn completion(&mut self, request: Request) -> Result<Self::Response, CompletionError> {
+        let key = serde_json::to_value(RecordedRequest {
+            prompt: &request.prompt,
+            preamble: &request.preamble,
+            knowledges: &request.knowledges,
+            history: &request.history,
+            tools: &request.tools,
+            documents: &request.documents,
+            max_tokens: request.max_tokens,
+            temperature: request.temperature,
+            top_p: request.top_p,
+            top_k: request.top_k,
+            response_format: &request.response_format,
+        })?;
+        if !self.cassette.is_recording() {
+            let response = self
+                .cassette
+                .replay_interaction(&key)
+                .map_err(|err| CompletionError::Normal(err.to_string()))?;
+            return Ok(serde_json::from_value(response)?);
+        }
+        let Some(model) = self.model.as_mut() else {
+            return Err(CompletionError::Normal(
+                "A recording cassette needs a model to call".to_string(),
+            ));
+        };
+        let response = model.completion(request).await?;
+        let response = RecordedResponse {
+            content: response.content(),
+            tool_calls: response.toolcalls(),
+            usage: response.token_usage(),
+        };
+        self.cassette
+            .record_interaction(key, serde_json::to_value(&response)?)
+            .map_err(|err| CompletionError::Normal(err.to_string()))?;
+        Ok(response)
+    }
+
+    fn supports_response_format(&self) -> bool {
+        self.supports_response_format
+    }
+
+    fn model_id(&self) -> String {
+        self.model_id.clone()
+    }
+}
+
+impl ResponseContent for RecordedResponse {
+    fn content(&self) -> String {
+        self.content.clone()
+    }
+}
+
+impl ResponseToolCalls for RecordedResponse {
+    fn toolcalls(&self) -> Vec<ToolCall> {
+        self.tool_calls.clone()
+    }
+}
+
+impl ResponseTokenUsage for RecordedResponse {
+    fn token_usage(&self) -> TokenUsage {
+        self.usage.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockCompletion, MockResponse};
+    use serde_json::json;
+
+    fn request(prompt: &str) -> Request {
+        Request::new(prompt.to_string(), "You are a calculator".to_string())
+    }
+
+    #[tokio::test]
+    async fn test_cassette_completion_record_replay() {
+        let path = std::env::temp_dir().join(format!(
+            "alith-cassette-completion-{}.jsonl",
+            std::process::id()
+        ));
+        let mock = MockCompletion::new()
+            .response_format(true)
+            .respond(MockResponse::tool_call(
+                "call_1",
+                "add",
+                json!({"a": 1, "b": 2}),
+            ))
+            .respond("3");
+        let mut model = CassetteCompletion::new(mock.clone(), Arc::new(Cassette::record(&path)));
+        model.completion(request("1 + 2")).await.unwrap();
+        model.completion(request("And the result?")).await.unwrap();
+        mock.assert_done();
+
+        let replay = |match_mode| {
+            CassetteCompletion::<MockCompletion>::replay(
+                "mock",
+                true,
+                Arc::new(Cassette::replay(&path, match_mode).unwrap()),
+            )
+        };
+        let mut strict = replay(MatchMode::Strict);
+        assert!(strict.supports_response_format());
+        let response = strict.completion(request("1 + 2")).await.unwrap();
+        assert_eq!(response.tool_calls[0].function.name, "add");
+        // A changed prompt does not match any recording.
+        assert!(strict.completion(request("1 + 3")).await.is_err());
+        let response = strict.completion(request("And the result?")).await.unwrap();
+        assert_eq!(response.content, "3");
+
+        let mut lenient = replay(MatchMode::Lenient);
+        let response = lenient.completion(request("1 + 3")).await.unwrap();
+        assert_eq!(response.tool_calls[0].function.name, "add");
+        let response = lenient
+            .completion(request("And the result?"))
+            .await
+            .unwrap();
+        assert_eq!(response.content, "3");
+        assert_eq!(lenient.cassette().remaining(), 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -3,10 +3,17 @@ pub use crate::client::{
 };
 use crate::store::DocumentId;
 use crate::task::TaskError;
-pub use alith_interface::requests::completion::{TokenUsage, ToolDefinition};
+pub use alith_interface::requests::completion::{
+    CompletionDelta, CompletionDeltaAccumulator, ResponseFormat, TokenUsage, ToolCallDelta,
+    ToolDefinition,
+};
 use async_trait::async_trait;
+use futures::stream::BoxStream;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+pub use tokio_util::sync::CancellationToken;
 
 /// A trait representing a prompt-based interaction mechanism.
 ///
@@ -33,13 +40,149 @@ pub trait Chat: Send + Sync {
     async fn prompt(&self, prompt: &str) -> Result<String, TaskError>;
     /// Processes the given prompt and history and returns a response asynchronously.
     async fn chat(&self, prompt: &str, history: Vec<Message>) -> Result<String, TaskError>;
+    /// Processes the given prompt, stopping when the call is cancelled or its deadline passes.
+    async fn prompt_with_options(
+        &self,
+        prompt: &str,
+        options: CallOptions,
+    ) -> Result<String, TaskError> {
+        options.guard(self.prompt(prompt)).await?
+    }
+    /// Processes the given prompt and history, stopping when the call is cancelled or its
+    /// deadline passes.
+    async fn chat_with_options(
+        &self,
+        prompt: &str,
+        history: Vec<Message>,
+        options: CallOptions,
+    ) -> Result<String, TaskError> {
+        options.guard(self.chat(prompt, history)).await?
+    }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+/// The cancellation token and the deadline of a call.
+#[derive(Debug, Clone, Default)]
+pub struct CallOptions {
+    /// The token cancelling the call, e.g. when the user leaves.
+    pub cancellation: CancellationToken,
+    /// The instant after which the call fails.
+    pub deadline: Option<Instant>,
+}
+
+impl CallOptions {
+    /// Creates the options of a call which is never cancelled and has no deadline.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the token cancelling the call.
+    pub fn cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Sets the instant after which the call fails.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets the deadline of the call to `timeout` from now.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.deadline(Instant::now() + timeout)
+    }
+
+    /// Returns why the call must stop, `None` while it may go on.
+    pub fn interruption(&self) -> Option<Interrupted> {
+        if self.cancellation.is_cancelled() {
+            Some(Interrupted::Cancelled)
+        } else if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            Some(Interrupted::DeadlineExceeded)
+        } else {
+            None
+        }
+    }
+
+    /// Awaits a future, dropping it when the call is cancelled or its deadline passes.
+    pub async fn guard<F: Future>(&self, future: F) -> Result<F::Output, Interrupted> {
+        let deadline = async {
+            match self.deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            biased;
+            _ = self.cancellation.cancelled() => Err(Interrupted::Cancelled),
+            _ = deadline => Err(Interrupted::DeadlineExceeded),
+            output = future => Ok(output),
+        }
+    }
+}
+
+/// The reason a call stopped before completing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum Interrupted {
+    #[error("The call was cancelled")]
+    Cancelled,
+    #[error("The call deadline has passed")]
+    DeadlineExceeded,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Message {
     /// "system", "user", "tool", or "assistant"
     pub role: String,
     pub content: String,
+    /// The tool calls requested by an assistant message.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// The ID of the tool call that a tool message answers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl Message {
+    /// Creates a new user message.
+    pub fn user(content: impl ToString) -> Self {
+        Self {
+            role: "user".to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a new assistant message.
+    pub fn assistant(content: impl ToString) -> Self {
+        Self {
+            role: "assistant".to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a new assistant message which requests the given tool calls.
+    pub fn assistant_with_tool_calls(content: impl ToString, tool_calls: Vec<ToolCall>) -> Self {
+        Self {
+            role: "assistant".to_string(),
+            content: content.to_string(),
+            tool_calls,
+            ..Default::default()
+        }
+    }
+
+    /// Creates a new tool message holding the result of the tool call `tool_call_id`.
+    pub fn tool(content: impl ToString, tool_call_id: impl ToString) -> Self {
+        Self {
+            role: "tool".to_string(),
+            content: content.to_string(),
+            tool_call_id: Some(tool_call_id.to_string()),
+            ..Default::default()
+        }
+    }
 }
 
 /// Represents a document with an ID, text, and additional properties.
@@ -140,6 +283,18 @@ pub struct Request {
     /// These documents can be used by the model to generate more accurate and informed responses.
     /// Examples include research papers, policy documents, or reference materials.
     pub documents: Vec<Document>,
+
+    /// Optional: The format the model must output, e.g. JSON matching a schema.
+    ///
+    /// Only honored by completion engines whose
+    /// [`Completion::supports_response_format`] returns true.
+    pub response_format: Option<ResponseFormat>,
+
+    /// The cancellation token and the deadline of the request.
+    ///
+    /// Completion engines may stop the in-flight model call when the request is cancelled,
+    /// the executor also stops the model and tool calls of the run.
+    pub options: CallOptions,
 }
 
 impl Request {
@@ -163,6 +318,8 @@ impl Request {
             temperature: None,
             tools: Vec::new(),
             documents: Vec::new(),
+            response_format: None,
+            options: CallOptions::default(),
         }
     }
 
@@ -186,6 +343,18 @@ impl Request {
         }
     }
 
+    /// Moves the effective prompt into the history as a user message and clears
+    /// the prompt, so that follow-up messages such as tool results can be appended
+    /// after it in the history.
+    pub fn move_prompt_into_history(&mut self) {
+        if !self.prompt.is_empty() {
+            self.history.push(Message::user(self.effective_prompt()));
+            self.prompt.clear();
+            self.knowledges.clear();
+            self.documents.clear();
+        }
+    }
+
     pub fn effective_prompt(&self) -> String {
         let mut input = self.prompt.clone();
         // Add knowledge sources if provided
@@ -221,10 +390,12 @@ impl Request {
                 ("content".to_string(), m.content.clone()),
             ]));
         }
-        messages.push(HashMap::from([
-            ("role".to_string(), "user".to_string()),
-            ("content".to_string(), self.effective_prompt()),
-        ]));
+        if !self.prompt.is_empty() {
+            messages.push(HashMap::from([
+                ("role".to_string(), "user".to_string()),
+                ("content".to_string(), self.effective_prompt()),
+            ]));
+        }
         messages
     }
 }
@@ -253,6 +424,7 @@ pub trait ResponseTokenUsage {
 }
 
 /// Represents a call to a specific tool in a response.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct ToolCall {
     /// The unique identifier for the tool call.
     pub id: String,
@@ -263,6 +435,7 @@ pub struct ToolCall {
 }
 
 /// Represents a callable function within a tool interaction.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct CallFunction {
     /// The name of the function being invoked.
     pub name: String,
@@ -294,6 +467,31 @@ pub trait Completion {
         &mut self,
         request: Request,
     ) -> impl std::future::Future<Output = Result<Self::Response, CompletionError>> + Send;
+
+    /// Whether the completion engine honors [`Request::response_format`].
+    fn supports_response_format(&self) -> bool {
+        false
+    }
+
+    /// The ID of the model, used to attribute and price the token usage.
+    fn model_id(&self) -> String {
+        String::new()
+    }
+}
+
+/// A stream of incremental completion updates.
+pub type CompletionStream = BoxStream<'static, Result<CompletionDelta, CompletionError>>;
+
+/// A trait defining the behavior of a completion engine that can stream its response.
+///
+/// The returned stream yields the generated text and tool call fragments as soon as
+/// they are produced, the last deltas carry the finish reason and token usage.
+pub trait StreamingCompletion: Completion {
+    /// Processes a `Request` and streams the generated response as incremental deltas.
+    fn completion_stream(
+        &mut self,
+        request: Request,
+    ) -> impl std::future::Future<Output = Result<CompletionStream, CompletionError>> + Send;
 }
 
 /// An enumeration of possible errors that may occur during completion operations.
@@ -311,3 +509,30 @@ pub enum CompletionError {
     #[error("JSON error: {0}")]
     JsonError(#[from] serde_json::Error),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_call_options_guard() {
+        let options = CallOptions::new();
+        assert_eq!(options.guard(async { 1 }).await, Ok(1));
+
+        let options = CallOptions::new().timeout(Duration::from_millis(10));
+        let pending = std::future::pending::<()>();
+        assert_eq!(
+            options.guard(pending).await,
+            Err(Interrupted::DeadlineExceeded)
+        );
+
+        let token = CancellationToken::new();
+        let options = CallOptions::new().cancellation(token.clone());
+        token.cancel();
+        assert_eq!(options.interruption(), Some(Interrupted::Cancelled));
+        assert_eq!(
+            options.guard(async { 1 }).await,
+            Err(Interrupted::Cancelled)
+        );
+    }
+}
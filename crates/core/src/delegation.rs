@@ -0,0 +1,330 @@
+use crate::agent::Agent;
+use crate::chat::{Completion, Interrupted, Message};
+use crate::executor::{RunOutput, RunTrace};
+use crate::task::TaskError;
+use crate::tool::{Tool, ToolDefinition, ToolError};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::sync::Arc;
+
+/// An agent wrapped as a tool, so that a supervisor agent can delegate subtasks to it.
+///
+/// By default the tool takes a `task` string which is sent to the agent as its prompt. With
+/// a custom input schema, the JSON arguments of the call are sent as the prompt instead.
+/// The trace of the agent run is nested into the tool call trace of the supervisor run.
+pub struct AgentTool<M: Completion> {
+    agent: Arc<Agent<M>>,
+    name: String,
+    description: String,
+    parameters: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct TaskInput {
+    task: String,
+}
+
+impl<M: Completion> AgentTool<M> {
+    /// Wraps an agent as a tool with the name and description offered to the model.
+    pub fn new(
+        agent: impl Into<Arc<Agent<M>>>,
+        name: impl ToString,
+        description: impl ToString,
+    ) -> Self {
+        Self {
+            agent: agent.into(),
+            name: name.to_string(),
+            description: description.to_string(),
+            parameters: None,
+        }
+    }
+
+    /// Sets the JSON schema of the tool input, the arguments are then sent to the agent as
+    /// its prompt.
+    pub fn input_schema(mut self, parameters: Value) -> Self {
+        self.parameters = Some(parameters);
+        self
+    }
+
+    /// The wrapped agent.
+    #[inline]
+    pub fn agent(&self) -> &Arc<Agent<M>> {
+        &self.agent
+    }
+
+    /// Returns the prompt sent to the agent for the tool input.
+    fn prompt(&self, input: &str) -> Result<String, ToolError> {
+        if self.parameters.is_some() {
+            Ok(input.to_string())
+        } else {
+            Ok(serde_json::from_str::<TaskInput>(input)?.task)
+        }
+    }
+}
+
+#[async_trait]
+impl<M: Completion + Send + Sync + 'static> Tool for AgentTool<M> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            parameters: self.parameters.clone().unwrap_or_else(|| {
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "task": {
+                            "type": "string",
+                            "description": "The subtask to delegate, with all the context needed to complete it."
+                        }
+                    },
+                    "required": ["task"]
+                })
+            }),
+        }
+    }
+
+    async fn run(&self, input: &str) -> Result<String, ToolError> {
+        Ok(self.run_with_trace(input).await?.0)
+    }
+
+    async fn run_with_trace(&self, input: &str) -> Result<(String, Option<RunTrace>), ToolError> {
+        let prompt = self.prompt(input)?;
+        let run = self
+            .agent
+            .prompt_with_trace(&prompt)
+            .await
+            .map_err(|err| match err {
+                TaskError::Cancelled => ToolError::Interrupted(Interrupted::Cancelled),
+                TaskError::DeadlineExceeded => {
+                    ToolError::Interrupted(Interrupted::DeadlineExceeded)
+                }
+                TaskError::BudgetExceeded { limit, .. } => ToolError::BudgetExceeded(limit),
+                err => ToolError::Unknown(err.to_string()),
+            })?;
+        Ok((run.output, Some(run.trace)))
+    }
+}
+
+impl<M: Completion> Agent<M> {
+    /// Wraps the agent as a tool, see [`AgentTool`].
+    pub fn into_tool(self, name: impl ToString, description: impl ToString) -> AgentTool<M> {
+        AgentTool::new(self, name, description)
+    }
+}
+
+impl<M: Completion + Send + Sync> Agent<M> {
+    /// Hands the conversation off to another agent, which answers the prompt.
+    ///
+    /// The messages in the memory of this agent are appended to the memory of the other
+    /// agent, or sent as the history of the prompt when the other agent has no memory. A
+    /// later handoff to the same agent only appends the messages added since the previous
+    /// one, all messages are appended again when they were evicted from this memory.
+    pub async fn handoff<N: Completion + Send + Sync>(
+        &self,
+        to: &Agent<N>,
+        prompt: &str,
+    ) -> Result<RunOutput, TaskError> {
+        let mut messages = match &self.memory {
+            Some(memory) => memory.read().await.messages(),
+            None => Vec::new(),
+        };
+        match &to.memory {
+            Some(memory) => {
+                let shared = self
+                    .memory
+                    .as_ref()
+                    .is_some_and(|from| Arc::ptr_eq(from, memory));
+                if !shared {
+                    let start = {
+                        let mut handoffs = self.handoffs.lock().unwrap();
+                        let start = handoffs
+                            .get(&to.id)
+                            .and_then(|last| messages.iter().rposition(|message| message == last))
+                            .map_or(0, |i| i + 1);
+                        if let Some(last) = messages.last() {
+                            handoffs.insert(to.id, last.clone());
+                        }
+                        start
+                    };
+                    let mut memory = memory.write().await;
+                    for message in messages.drain(start..) {
+                        memory.add_message(message);
+                    }
+                }
+                to.prompt_with_trace(prompt).await
+            }
+            None => {
+                let history = messages.into_iter().map(Message::from).collect();
+                to.chat_with_trace(prompt, history).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::{Budget, BudgetLimit};
+    use crate::chat::Chat;
+    use crate::memory::WindowBufferMemory;
+    use crate::mock::{MockCompletion, MockResponse};
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_agent_tool_trace() {
+        let researcher = MockCompletion::new().respond("Seoul is in Korea.");
+        let supervisor = MockCompletion::new()
+            .respond(MockResponse::tool_call(
+                "call_1",
+                "research",
+                json!({"task": "Where is Seoul?"}),
+            ))
+            .respond("Korea.");
+        let agent = Agent::new("supervisor", supervisor)
+            .tool(Agent::new("researcher", researcher.clone()).into_tool("research", "Researches."))
+            .await;
+
+        let run = agent.prompt_with_trace("Where is Seoul?").await.unwrap();
+        assert_eq!(run.output, "Korea.");
+        let call = &run.trace.steps[0].tool_calls[0];
+        assert_eq!(call.output, "Seoul is in Korea.");
+        let agent_trace = call.agent_trace.as_ref().unwrap();
+        assert_eq!(agent_trace.steps.len(), 1);
+        assert_eq!(agent_trace.steps[0].content, "Seoul is in Korea.");
+        researcher.assert_prompt_contains(0, "Where is Seoul?");
+    }
+
+    #[tokio::test]
+    async fn test_agent_tool_budget_exceeded() {
+        let researcher =
+            MockCompletion::new().respond(MockResponse::tool_call("call_2", "search", json!({})));
+        let supervisor = MockCompletion::new().respond(MockResponse::tool_call(
+            "call_1",
+            "research",
+            json!({"task": "Where is Seoul?"}),
+        ));
+        let researcher = Agent::new("researcher", researcher)
+            .budget(Budget::new().max_tool_calls(0))
+            .into_tool("research", "Researches.");
+        let agent = Agent::new("supervisor", supervisor.clone())
+            .tool(researcher)
+            .await;
+
+        // The budget error of the delegated run stops the supervisor run.
+        assert!(matches!(
+            agent.prompt_with_trace("Where is Seoul?").await,
+            Err(TaskError::BudgetExceeded {
+                limit: BudgetLimit::ToolCalls(0),
+                trace,
+            }) if trace.steps.len() == 1
+        ));
+        supervisor.assert_calls(1);
+    }
+
+    #[tokio::test]
+    async fn test_agent_tool_budget_exceeded_stream() {
+        let researcher =
+            MockCompletion::new().respond(MockResponse::tool_call("call_2", "search", json!({})));
+        let supervisor = MockCompletion::new().respond(MockResponse::tool_call(
+            "call_1",
+            "research",
+            json!({"task": "Where is Seoul?"}),
+        ));
+        let researcher = Agent::new("researcher", researcher)
+            .budget(Budget::new().max_tool_calls(0))
+            .into_tool("research", "Researches.");
+        let agent = Agent::new("supervisor", supervisor).tool(researcher).await;
+
+        // The streamed run stops with its own trace, like a run without streaming.
+        let deltas: Vec<_> = agent
+            .prompt_stream("Where is Seoul?")
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert!(matches!(
+            deltas.last(),
+            Some(Err(TaskError::BudgetExceeded {
+                limit: BudgetLimit::ToolCalls(0),
+                trace,
+            })) if trace.steps.len() == 1
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_handoff() {
+        let triage = MockCompletion::new().respond("Let me transfer you.");
+        let billing = MockCompletion::new()
+            .respond("Your invoice is sent.")
+            .respond("Anything else?");
+        let triage = Agent::new("triage", triage).memory(WindowBufferMemory::new(10));
+        triage.prompt("I need my invoice").await.unwrap();
+
+        let with_memory =
+            Agent::new("billing", billing.clone()).memory(WindowBufferMemory::new(10));
+        triage.handoff(&with_memory, "Send it").await.unwrap();
+        let without_memory = Agent::new("billing", billing.clone());
+        triage.handoff(&without_memory, "Thanks").await.unwrap();
+
+        let history = [
+            ("user", "I need my invoice"),
+            ("assistant", "Let me transfer you."),
+        ];
+        billing.assert_history(0, &history);
+        billing.assert_history(1, &history);
+        // The messages are copied into the memory of the other agent.
+        assert_eq!(
+            with_memory
+                .memory
+                .as_ref()
+                .unwrap()
+                .read()
+                .await
+                .messages()
+                .len(),
+            4
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repeated_handoff() {
+        let triage = MockCompletion::new()
+            .respond("Let me transfer you.")
+            .respond("Let me transfer you again.");
+        let billing = MockCompletion::new()
+            .respond("Your invoice is sent.")
+            .respond("Your receipt is sent.");
+        let triage = Agent::new("triage", triage).memory(WindowBufferMemory::new(10));
+        let billing = Agent::new("billing", billing.clone()).memory(WindowBufferMemory::new(10));
+        triage.prompt("I need my invoice").await.unwrap();
+        triage.handoff(&billing, "Send it").await.unwrap();
+        triage.prompt("I need my receipt").await.unwrap();
+        triage.handoff(&billing, "Send it too").await.unwrap();
+
+        // The second handoff only copies the messages added since the first one.
+        let messages = billing.memory.as_ref().unwrap().read().await.messages();
+        let contents: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            vec![
+                "I need my invoice",
+                "Let me transfer you.",
+                "Send it",
+                "Your invoice is sent.",
+                "I need my receipt",
+                "Let me transfer you again.",
+                "Send it too",
+                "Your receipt is sent.",
+            ]
+        );
+    }
+}
@@ -1,19 +1,133 @@
 use crate::Ref;
-use crate::chat::{Completion, Request, ResponseContent, ResponseToolCalls, ToolCall};
-use crate::knowledge::Knowledge;
-use crate::mcp::MCPClient;
+use crate::approval::{ApprovalDecision, ToolApprover};
+use crate::budget::{Budget, BudgetExceeded, BudgetLimit, BudgetTracker};
+use crate::chat::{
+    CallFunction, Completion, CompletionDelta, CompletionDeltaAccumulator, Message as ChatMessage,
+    Request, ResponseContent, ResponseTokenUsage, ResponseToolCalls, StreamingCompletion,
+    TokenUsage, ToolCall,
+};
+use crate::knowledge::{Knowledge, KnowledgeIndex};
 use crate::memory::{Memory, Message};
-use crate::tool::Tool;
+use crate::middleware::{Middleware, ModelResponse};
+use crate::telemetry;
+use crate::tool::{ToolDefinition, ToolError, ToolRegistry, ToolSource};
+use crate::usage::{UsageContext, UsageLedger, UsageRecord};
+use futures::stream::{self, BoxStream};
+use futures::{Future, StreamExt};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::time::Duration;
+use tracing::{Instrument, Span};
+use uuid::Uuid;
+
+/// The default maximum number of model calls in one executor run.
+pub const DEFAULT_MAX_STEPS: usize = 10;
+
+/// The default maximum number of tool calls of one model step running at the same time.
+pub const DEFAULT_MAX_CONCURRENT_TOOLS: usize = 4;
+
+/// The final output of an executor run together with its trace.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunOutput {
+    /// The final text answer of the model.
+    pub output: String,
+    /// The structured trace of the run.
+    pub trace: RunTrace,
+}
+
+/// A structured trace of an executor run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunTrace {
+    /// The unique ID of the run, which attributes its usage records.
+    #[serde(default)]
+    pub run_id: String,
+    /// The model calls made during the run, in order.
+    pub steps: Vec<RunStep>,
+    /// Whether the run stopped because the step limit was reached while the
+    /// model was still requesting tool calls.
+    pub max_steps_reached: bool,
+}
+
+impl RunTrace {
+    /// Returns the token usage summed over all steps.
+    pub fn usage(&self) -> TokenUsage {
+        let mut usage = TokenUsage::default();
+        for step in &self.steps {
+            usage.prompt_tokens += step.usage.prompt_tokens;
+            usage.completion_tokens += step.usage.completion_tokens;
+            usage.total_tokens += step.usage.total_tokens;
+            if let Some(cached) = step.usage.tokens_cached {
+                usage.tokens_cached = Some(usage.tokens_cached.unwrap_or_default() + cached);
+            }
+        }
+        usage
+    }
+
+    /// Returns all tool calls made during the run, in order.
+    pub fn tool_calls(&self) -> impl Iterator<Item = &ToolCallTrace> {
+        self.steps.iter().flat_map(|step| step.tool_calls.iter())
+    }
+}
+
+/// A single model call in an executor run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunStep {
+    /// The text content returned by the model.
+    pub content: String,
+    /// The tool calls requested by the model and their results.
+    pub tool_calls: Vec<ToolCallTrace>,
+    /// The token usage of the model call.
+    pub usage: TokenUsage,
+}
+
+/// A tool call made during an executor run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ToolCallTrace {
+    /// The unique identifier of the tool call.
+    pub id: String,
+    /// The name of the called tool.
+    pub name: String,
+    /// The arguments passed to the tool.
+    pub arguments: String,
+    /// The output of the tool, or the structured error message if the call failed.
+    pub output: String,
+    /// Whether the tool call failed or timed out.
+    #[serde(default)]
+    pub is_error: bool,
+    /// The source which served the call, `None` when no registered tool has the called name.
+    #[serde(default)]
+    pub source: Option<ToolSource>,
+    /// The trace of the agent run the call was delegated to, if the tool wraps an agent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_trace: Option<RunTrace>,
+}
 
 /// Manages the execution of tasks using an LLM, tools, and (optionally) memory components.
 pub struct Executor<M: Completion> {
     model: Ref<M>,
     knowledges: Arc<Vec<Box<dyn Knowledge>>>,
-    tools: Ref<Vec<Box<dyn Tool>>>,
+    /// The index injecting only the relevant knowledge chunks instead of whole sources.
+    knowledge_index: Option<Arc<KnowledgeIndex>>,
+    tools: Ref<ToolRegistry>,
     memory: Option<Ref<dyn Memory>>,
-    /// The MCP client used to communicate with the MCP server
-    mcp_clients: Ref<Vec<MCPClient>>,
+    /// The maximum number of model calls in one run.
+    max_steps: usize,
+    /// The maximum number of tool calls running at the same time.
+    max_concurrent_tools: usize,
+    /// The timeout of tools which do not declare their own.
+    tool_timeout: Option<Duration>,
+    /// The approver consulted before running tools which require approval.
+    approver: Option<Arc<dyn ToolApprover>>,
+    /// The middlewares run in order around model and tool calls.
+    middlewares: Vec<Arc<dyn Middleware>>,
+    /// The tool the model calls with its final output, ending the run without running it.
+    output_tool: Option<ToolDefinition>,
+    /// The ledger recording the usage of the runs.
+    ledger: Option<Arc<UsageLedger>>,
+    /// The agent and session the usage is attributed to.
+    usage_context: UsageContext,
+    /// The hard limits of one run.
+    budget: Budget,
 }
 
 impl<M: Completion> Executor<M> {
@@ -21,46 +135,407 @@ impl<M: Completion> Executor<M> {
     pub fn new(
         model: Ref<M>,
         knowledges: Arc<Vec<Box<dyn Knowledge>>>,
-        tools: Ref<Vec<Box<dyn Tool>>>,
+        tools: Ref<ToolRegistry>,
         memory: Option<Ref<dyn Memory>>,
-        mcp_clients: Ref<Vec<MCPClient>>,
     ) -> Self {
         Self {
             model,
             knowledges,
+            knowledge_index: None,
             tools,
             memory,
-            mcp_clients,
+            max_steps: DEFAULT_MAX_STEPS,
+            max_concurrent_tools: DEFAULT_MAX_CONCURRENT_TOOLS,
+            tool_timeout: None,
+            approver: None,
+            middlewares: Vec::new(),
+            output_tool: None,
+            ledger: None,
+            usage_context: UsageContext::default(),
+            budget: Budget::default(),
         }
     }
 
+    /// Sets the index retrieving the knowledge chunks relevant to the prompt, the whole
+    /// knowledge sources are injected when it is not set.
+    pub fn knowledge_index(mut self, knowledge_index: Option<Arc<KnowledgeIndex>>) -> Self {
+        self.knowledge_index = knowledge_index;
+        self
+    }
+
+    /// Records the usage of the runs into the ledger, attributed to the agent and session.
+    pub fn ledger(
+        mut self,
+        ledger: Option<Arc<UsageLedger>>,
+        agent: impl ToString,
+        session_id: Option<String>,
+    ) -> Self {
+        self.ledger = ledger;
+        self.usage_context.agent = agent.to_string();
+        self.usage_context.session_id = session_id;
+        self
+    }
+
+    /// Sets the hard limits of one run, the run is aborted with [`BudgetExceeded`] when
+    /// one is hit.
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Sets the maximum number of model calls in one run.
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Sets the maximum number of tool calls of one model step running at the same time.
+    pub fn max_concurrent_tools(mut self, max_concurrent_tools: usize) -> Self {
+        self.max_concurrent_tools = max_concurrent_tools;
+        self
+    }
+
+    /// Sets the timeout of tools which do not declare their own, including MCP tools.
+    pub fn tool_timeout(mut self, tool_timeout: Option<Duration>) -> Self {
+        self.tool_timeout = tool_timeout;
+        self
+    }
+
+    /// Sets the memory recording the conversation, `None` leaves the memory untouched.
+    pub fn memory(mut self, memory: Option<Ref<dyn Memory>>) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    /// Sets the approver consulted before running tools which require approval.
+    pub fn approver(mut self, approver: Option<Arc<dyn ToolApprover>>) -> Self {
+        self.approver = approver;
+        self
+    }
+
+    /// Sets the middlewares run in order around model and tool calls.
+    pub fn middlewares(mut self, middlewares: Vec<Arc<dyn Middleware>>) -> Self {
+        self.middlewares = middlewares;
+        self
+    }
+
+    /// Adds a middleware run after the already registered ones.
+    pub fn middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// Sets the tool the model calls with its final output.
+    ///
+    /// The tool is offered to the model but never run: when the model calls it, the run
+    /// ends and the call arguments become the run output.
+    pub fn output_tool(mut self, output_tool: ToolDefinition) -> Self {
+        self.output_tool = Some(output_tool);
+        self
+    }
+
     /// Executes the task by managing interactions between the LLM and tools.
-    pub async fn invoke(&mut self, mut request: Request) -> anyhow::Result<String> {
-        request.knowledges = {
-            let mut enriched_knowledges = Vec::new();
-            for knowledge in self.knowledges.iter() {
-                let enriched = knowledge.enrich(&request.prompt)?;
-                enriched_knowledges.push(enriched);
-            }
-            enriched_knowledges
-        };
+    ///
+    /// The model is called repeatedly: each requested tool call is executed and its
+    /// result is sent back to the model, until the model answers without calling any
+    /// tool or the step limit is reached.
+    ///
+    /// The budget is checked before each round of tool calls and bounds the wall time of
+    /// the model and tool calls. When a limit is hit, the run fails with [`BudgetExceeded`]
+    /// holding the trace of the steps done so far. When the request is cancelled or its
+    /// deadline passes, the in-flight model and tool calls are dropped and the run fails
+    /// with [`Interrupted`](crate::chat::Interrupted).
+    pub async fn invoke(&mut self, request: Request) -> anyhow::Result<RunOutput> {
+        let result = self.run(request).await;
+        if let Err(err) = &result {
+            self.on_error(err).await;
+        }
+        result
+    }
+
+    async fn run(&self, mut request: Request) -> anyhow::Result<RunOutput> {
+        self.prepare_request(&mut request).await?;
         // Add user memory
         self.add_user_message(&request.prompt).await;
-        // Interact with the LLM to get a response.
-        let mut model = self.model.write().await;
-        let response = model.completion(request.clone()).await?;
 
-        let mut responses = vec![response.content()];
-        self.add_ai_message(&responses[0]).await;
+        let usage = self.usage_context().await;
+        let mut budget = self.budget_tracker();
+        let options = request.options.clone();
+        let mut trace = RunTrace {
+            run_id: usage.run_id.clone(),
+            ..Default::default()
+        };
+        for _ in 0..self.max_steps.max(1) {
+            if let Some(interrupted) = options.interruption() {
+                return Err(interrupted.into());
+            }
+            // Interact with the LLM to get a response.
+            let mut step_request = request.clone();
+            self.before_request(&mut step_request).await;
+            let response = {
+                let mut model = self.model.write().await;
+                match budget
+                    .limit_time(options.guard(model.completion(step_request)))
+                    .await
+                {
+                    Ok(response) => response??,
+                    Err(limit) => return Err(budget_exceeded(limit, trace)),
+                }
+            };
+            let response = ModelResponse {
+                content: response.content(),
+                tool_calls: response.toolcalls(),
+                usage: response.token_usage(),
+            };
+            self.after_response(&response).await;
+            self.record_usage(UsageRecord::completion(&usage, &response.usage));
+            budget.add_usage(&usage, &response.usage);
+            let ModelResponse {
+                content,
+                tool_calls: calls,
+                usage: step_usage,
+            } = response;
+            let mut step = RunStep {
+                content: content.clone(),
+                tool_calls: Vec::with_capacity(calls.len()),
+                usage: step_usage,
+            };
+            if calls.is_empty() {
+                self.add_ai_message(&content).await;
+                trace.steps.push(step);
+                return Ok(RunOutput {
+                    output: content,
+                    trace,
+                });
+            }
+            if let Some(call) = self.find_output_call(&calls) {
+                let output = call.function.arguments.clone();
+                self.add_ai_message(&output).await;
+                step.tool_calls.push(ToolCallTrace {
+                    id: call.id.clone(),
+                    name: call.function.name.clone(),
+                    arguments: output.clone(),
+                    output: output.clone(),
+                    is_error: false,
+                    source: None,
+                    agent_trace: None,
+                });
+                trace.steps.push(step);
+                return Ok(RunOutput { output, trace });
+            }
 
-        // Attempt to parse and execute a tool action.
-        for call in response.toolcalls() {
-            let tool_call = self.execute_tool(call).await?;
-            self.add_ai_message_with_tool_call(&tool_call).await?;
-            responses.push(tool_call);
+            if let Err(limit) = budget.check(calls.len()) {
+                trace.steps.push(step);
+                return Err(budget_exceeded(limit, trace));
+            }
+            step.tool_calls = match budget
+                .limit_time(options.guard(self.run_tool_calls(&mut request, &content, calls)))
+                .await
+            {
+                Ok(tool_calls) => match tool_calls? {
+                    Ok(tool_calls) => tool_calls,
+                    Err(err) => {
+                        trace.steps.push(step);
+                        return Err(tool_calls_error(err, trace));
+                    }
+                },
+                Err(limit) => {
+                    trace.steps.push(step);
+                    return Err(budget_exceeded(limit, trace));
+                }
+            };
+            for call in &step.tool_calls {
+                self.record_usage(UsageRecord::tool_call(&usage, &call.name));
+            }
+            trace.steps.push(step);
         }
 
-        Ok(responses.join("\n"))
+        trace.max_steps_reached = true;
+        Ok(RunOutput {
+            output: trace
+                .steps
+                .last()
+                .map(|step| step.content.clone())
+                .unwrap_or_default(),
+            trace,
+        })
+    }
+
+    /// Enriches the request with the knowledges relevant to its prompt and offers the
+    /// output tool if it has been set.
+    async fn prepare_request(&self, request: &mut Request) -> anyhow::Result<()> {
+        let span = if self.knowledges.is_empty() {
+            Span::none()
+        } else {
+            telemetry::retrieval_span("knowledges")
+        };
+        let knowledges = async {
+            let knowledges = match &self.knowledge_index {
+                Some(index) => index.retrieve(&self.knowledges, &request.prompt).await?,
+                None => {
+                    let mut enriched_knowledges = Vec::new();
+                    for knowledge in self.knowledges.iter() {
+                        let enriched = knowledge.enrich(&request.prompt).await?;
+                        enriched_knowledges.push(enriched);
+                    }
+                    enriched_knowledges
+                }
+            };
+            anyhow::Ok(knowledges)
+        }
+        .instrument(span.clone())
+        .await;
+        request.knowledges = match knowledges {
+            Ok(knowledges) => {
+                span.record("retrieval.results", knowledges.len() as u64);
+                knowledges
+            }
+            Err(err) => {
+                telemetry::record_error(&span, &err);
+                return Err(err);
+            }
+        };
+        if let Some(output_tool) = &self.output_tool {
+            request.tools.push(output_tool.clone());
+        }
+        Ok(())
+    }
+
+    /// Returns the usage attribution of a new run.
+    async fn usage_context(&self) -> UsageContext {
+        UsageContext {
+            run_id: Uuid::new_v4().to_string(),
+            model: self.model.read().await.model_id(),
+            ..self.usage_context.clone()
+        }
+    }
+
+    /// Starts tracking the budget of a new run, priced with the ledger pricing table.
+    fn budget_tracker(&self) -> BudgetTracker {
+        BudgetTracker::new(
+            self.budget.clone(),
+            self.ledger.as_ref().map(|ledger| ledger.pricing().clone()),
+        )
+    }
+
+    /// Records a usage event into the ledger if the ledger has been set.
+    fn record_usage(&self, record: UsageRecord) {
+        if let Some(ledger) = &self.ledger {
+            ledger.record(record);
+        }
+    }
+
+    /// Returns the call of the output tool, if the model made one.
+    fn find_output_call<'a>(&self, calls: &'a [ToolCall]) -> Option<&'a ToolCall> {
+        let output_tool = self.output_tool.as_ref()?;
+        calls
+            .iter()
+            .find(|call| call.function.name == output_tool.name)
+    }
+
+    /// Executes the tool calls of a model step and appends the calls and their results
+    /// to the request history, so that they are sent back to the model in the next step.
+    ///
+    /// The calls are first submitted one by one to the approver, then the approved calls run
+    /// concurrently up to the concurrency limit. A denied or failed call is reported to the
+    /// model as a structured error message instead of aborting the run. The calls are
+    /// recorded in the history and memory once edited by the middlewares and the approver,
+    /// as they are run.
+    async fn run_tool_calls(
+        &self,
+        request: &mut Request,
+        content: &str,
+        mut calls: Vec<ToolCall>,
+    ) -> anyhow::Result<Vec<ToolCallTrace>> {
+        let mut denials = Vec::with_capacity(calls.len());
+        for call in calls.iter_mut() {
+            self.before_tool_call(call).await;
+            denials.push(match self.approve_tool_call(call).await {
+                ApprovalDecision::Approve => None,
+                ApprovalDecision::Deny { reason } => Some(reason),
+                ApprovalDecision::Edit { arguments } => {
+                    call.function.arguments = arguments;
+                    None
+                }
+            });
+        }
+        self.add_ai_message_with_tool_calls(content, &calls).await?;
+        request.move_prompt_into_history();
+        request.history.push(ChatMessage::assistant_with_tool_calls(
+            content,
+            calls.clone(),
+        ));
+        let sources: Vec<Option<ToolSource>> = {
+            let tools = self.tools.read().await;
+            calls
+                .iter()
+                .map(|call| {
+                    tools
+                        .get(&call.function.name)
+                        .map(|tool| tool.source().clone())
+                })
+                .collect()
+        };
+        // The futures are built eagerly, a lazy closure over borrowed calls is not general
+        // enough for the `Send` bounds of the async trait methods driving this run.
+        let futures: Vec<_> = calls
+            .iter()
+            .zip(denials)
+            .zip(&sources)
+            .map(|((call, denial), source)| {
+                let span = telemetry::tool_span(call, source.as_ref());
+                async move {
+                    let result = match denial {
+                        Some(reason) => Err(ToolError::Denied(reason)),
+                        None => self.execute_tool(call).await,
+                    };
+                    if let Err(err) = &result {
+                        telemetry::record_error(&Span::current(), err);
+                    }
+                    result
+                }
+                .instrument(span)
+            })
+            .collect();
+        let results: Vec<Result<(String, Option<RunTrace>), ToolError>> = stream::iter(futures)
+            .buffered(self.max_concurrent_tools.max(1))
+            .collect()
+            .await;
+        // A delegated run which was interrupted or exceeded its budget stops this run, once
+        // the tool results are recorded.
+        let abort = results.iter().find_map(|result| match result {
+            Err(ToolError::Interrupted(interrupted)) => Some(anyhow::Error::from(*interrupted)),
+            Err(ToolError::BudgetExceeded(limit)) => {
+                Some(budget_exceeded(*limit, RunTrace::default()))
+            }
+            _ => None,
+        });
+        let mut traces = Vec::with_capacity(calls.len());
+        for ((call, result), source) in calls.into_iter().zip(results).zip(sources) {
+            let (output, is_error, agent_trace) = match result {
+                Ok((output, agent_trace)) => (output, false, agent_trace),
+                Err(err) => (err.to_message(&call.function.name), true, None),
+            };
+            let mut trace = ToolCallTrace {
+                id: call.id,
+                name: call.function.name,
+                arguments: call.function.arguments,
+                output,
+                is_error,
+                source,
+                agent_trace,
+            };
+            self.after_tool_call(&mut trace).await;
+            self.add_tool_message(&trace.output, &trace.id).await;
+            request
+                .history
+                .push(ChatMessage::tool(&trace.output, &trace.id));
+            traces.push(trace);
+        }
+        match abort {
+            Some(err) => Err(err),
+            None => Ok(traces),
+        }
     }
 
     /// Add a user message into the memory if the memory has been set.
@@ -79,40 +554,626 @@ impl<M: Completion> Executor<M> {
         }
     }
 
-    /// Add an AI message into the memory if the memory has been set.
-    async fn add_ai_message_with_tool_call(&self, tool_call: &str) -> anyhow::Result<()> {
+    /// Add an AI message with its tool calls into the memory if the memory has been set.
+    async fn add_ai_message_with_tool_calls(
+        &self,
+        message: &str,
+        tool_calls: &[ToolCall],
+    ) -> anyhow::Result<()> {
         if let Some(memory) = &self.memory {
             let mut memory = memory.write().await;
-            let tool_call: serde_json::Value = serde_json::from_str(tool_call)?;
-            memory.add_message(Message::new_ai_message("").with_tool_calls(tool_call));
+            let tool_calls = serde_json::to_value(tool_calls)?;
+            memory.add_message(Message::new_ai_message(message).with_tool_calls(tool_calls));
         }
         Ok(())
     }
 
-    /// Executes a tool action and returns the result.
-    async fn execute_tool(&self, call: ToolCall) -> anyhow::Result<String> {
-        let tools = self.tools.read().await;
-        if let Some(tool) = tools
-            .iter()
-            .find(|t| t.name().eq_ignore_ascii_case(&call.function.name))
-        {
-            Ok(tool.run(&call.function.arguments).await?)
+    /// Add a tool result message into the memory if the memory has been set.
+    async fn add_tool_message(&self, message: &str, tool_call_id: &str) {
+        if let Some(memory) = &self.memory {
+            let mut memory = memory.write().await;
+            memory.add_message(Message::new_tool_message(message, tool_call_id));
+        }
+    }
+
+    /// Runs the `before_request` hook of all middlewares.
+    async fn before_request(&self, request: &mut Request) {
+        for middleware in &self.middlewares {
+            middleware.before_request(request).await;
+        }
+    }
+
+    /// Runs the `after_response` hook of all middlewares.
+    async fn after_response(&self, response: &ModelResponse) {
+        for middleware in &self.middlewares {
+            middleware.after_response(response).await;
+        }
+    }
+
+    /// Runs the `before_tool_call` hook of all middlewares.
+    async fn before_tool_call(&self, call: &mut ToolCall) {
+        for middleware in &self.middlewares {
+            middleware.before_tool_call(call).await;
+        }
+    }
+
+    /// Runs the `after_tool_call` hook of all middlewares.
+    async fn after_tool_call(&self, call: &mut ToolCallTrace) {
+        for middleware in &self.middlewares {
+            middleware.after_tool_call(call).await;
+        }
+    }
+
+    /// Runs the `on_error` hook of all middlewares.
+    async fn on_error(&self, error: &anyhow::Error) {
+        for middleware in &self.middlewares {
+            middleware.on_error(error).await;
+        }
+    }
+
+    /// Asks the approver whether the tool call may run, calls of tools which do not require
+    /// approval are always approved.
+    async fn approve_tool_call(&self, call: &ToolCall) -> ApprovalDecision {
+        let Some(approver) = &self.approver else {
+            return ApprovalDecision::Approve;
+        };
+        let requires_approval = {
+            let tools = self.tools.read().await;
+            tools
+                .get(&call.function.name)
+                .is_none_or(|tool| tool.requires_approval())
+        };
+        if requires_approval {
+            approver.approve(call).await
         } else {
-            let mcp_clients = self.mcp_clients.read().await;
-            if !mcp_clients.is_empty() {
-                for mcp_client in mcp_clients.iter() {
-                    if mcp_client.tools.contains_key(&call.function.name) {
-                        let arguments = serde_json::from_str(&call.function.arguments)?;
-                        let response = mcp_client.call_tool(&call.function.name, arguments).await?;
-                        if let Some(text) = response.content[0].as_text() {
-                            return Ok(text.to_string());
-                        } else {
-                            return Ok("".to_string());
+            ApprovalDecision::Approve
+        }
+    }
+
+    /// Executes a tool action and returns the result with the trace of the agent run the
+    /// call was delegated to, if any.
+    ///
+    /// Tools are run with their own timeout, or the executor tool timeout when they do not
+    /// declare one, and retried according to their retry policy.
+    async fn execute_tool(&self, call: &ToolCall) -> Result<(String, Option<RunTrace>), ToolError> {
+        let tools = self.tools.read().await;
+        let tool = tools
+            .get(&call.function.name)
+            .ok_or_else(|| ToolError::NotFound(call.function.name.clone()))?;
+        let timeout = tool.timeout().or(self.tool_timeout);
+        let retry_policy = tool.retry_policy();
+        let mut retries = 0;
+        loop {
+            match with_timeout(timeout, tool.run_with_trace(&call.function.arguments)).await {
+                Err(err) if err.is_retryable() && retries < retry_policy.max_retries => {
+                    retries += 1;
+                    tokio::time::sleep(retry_policy.delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Creates the error of a run aborted by its budget.
+fn budget_exceeded(limit: BudgetLimit, trace: RunTrace) -> anyhow::Error {
+    BudgetExceeded { limit, trace }.into()
+}
+
+/// Returns the error of failed tool calls, a delegated run which exceeded its budget stops
+/// this run with its own trace.
+fn tool_calls_error(err: anyhow::Error, trace: RunTrace) -> anyhow::Error {
+    match err.downcast::<BudgetExceeded>() {
+        Ok(BudgetExceeded { limit, .. }) => budget_exceeded(limit, trace),
+        Err(err) => err,
+    }
+}
+
+/// Awaits a tool run, failing with [`ToolError::Timeout`] when it exceeds the timeout.
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    run: impl Future<Output = Result<T, ToolError>>,
+) -> Result<T, ToolError> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, run)
+            .await
+            .unwrap_or(Err(ToolError::Timeout(timeout))),
+        None => run.await,
+    }
+}
+
+impl<M: StreamingCompletion + Send + Sync + 'static> Executor<M> {
+    /// Executes the task like [`Executor::invoke`], streaming the model deltas of every step.
+    ///
+    /// Tool calls are executed once their step has been fully streamed, the deltas of the
+    /// following step are then yielded on the same stream.
+    pub fn invoke_stream(
+        self,
+        request: Request,
+    ) -> BoxStream<'static, anyhow::Result<CompletionDelta>> {
+        Box::pin(async_stream::stream! {
+            let mut deltas = self.run_stream(request);
+            while let Some(delta) = deltas.next().await {
+                if let Err(err) = &delta {
+                    self.on_error(err).await;
+                }
+                yield delta;
+            }
+        })
+    }
+
+    fn run_stream(&self, mut request: Request) -> BoxStream<'_, anyhow::Result<CompletionDelta>> {
+        Box::pin(async_stream::try_stream! {
+            self.prepare_request(&mut request).await?;
+            // Add user memory
+            self.add_user_message(&request.prompt).await;
+
+            let usage = self.usage_context().await;
+            let mut budget = self.budget_tracker();
+            let options = request.options.clone();
+            let mut trace = RunTrace {
+                run_id: usage.run_id.clone(),
+                ..Default::default()
+            };
+            for _ in 0..self.max_steps.max(1) {
+                if let Some(interrupted) = options.interruption() {
+                    Err::<(), _>(interrupted)?;
+                }
+                let mut step_request = request.clone();
+                self.before_request(&mut step_request).await;
+                let mut stream = {
+                    let mut model = self.model.write().await;
+                    let stream = options.guard(model.completion_stream(step_request));
+                    match budget.limit_time(stream).await {
+                        Ok(stream) => stream??,
+                        Err(limit) => Err(budget_exceeded(limit, trace.clone()))?,
+                    }
+                };
+                let mut accumulator = CompletionDeltaAccumulator::default();
+                loop {
+                    let delta = match budget.limit_time(options.guard(stream.next())).await {
+                        Ok(next) => match next? {
+                            Some(delta) => delta?,
+                            None => break,
+                        },
+                        Err(limit) => Err(budget_exceeded(limit, trace.clone()))?,
+                    };
+                    accumulator.push(&delta);
+                    yield delta;
+                }
+                let response = ModelResponse {
+                    tool_calls: accumulator
+                        .tool_calls()
+                        .into_iter()
+                        .map(|call| ToolCall {
+                            id: call.id,
+                            r#type: call.r#type,
+                            function: CallFunction {
+                                name: call.function.name,
+                                arguments: call.function.arguments,
+                            },
+                        })
+                        .collect(),
+                    usage: accumulator.token_usage.unwrap_or_default(),
+                    content: accumulator.content,
+                };
+                self.after_response(&response).await;
+                self.record_usage(UsageRecord::completion(&usage, &response.usage));
+                budget.add_usage(&usage, &response.usage);
+                if response.tool_calls.is_empty() {
+                    self.add_ai_message(&response.content).await;
+                    break;
+                }
+                if let Some(call) = self.find_output_call(&response.tool_calls) {
+                    self.add_ai_message(&call.function.arguments).await;
+                    break;
+                }
+                let mut step = RunStep {
+                    content: response.content.clone(),
+                    tool_calls: Vec::new(),
+                    usage: response.usage,
+                };
+                if let Err(limit) = budget.check(response.tool_calls.len()) {
+                    trace.steps.push(step.clone());
+                    Err::<(), _>(budget_exceeded(limit, trace.clone()))?;
+                }
+                let calls =
+                    self.run_tool_calls(&mut request, &response.content, response.tool_calls);
+                step.tool_calls = match budget.limit_time(options.guard(calls)).await {
+                    Ok(tool_calls) => match tool_calls? {
+                        Ok(tool_calls) => tool_calls,
+                        Err(err) => {
+                            trace.steps.push(step.clone());
+                            Err(tool_calls_error(err, trace.clone()))?
                         }
+                    },
+                    Err(limit) => {
+                        trace.steps.push(step.clone());
+                        Err(budget_exceeded(limit, trace.clone()))?
                     }
+                };
+                for call in &step.tool_calls {
+                    self.record_usage(UsageRecord::tool_call(&usage, &call.name));
                 }
+                trace.steps.push(step);
             }
-            Err(anyhow::anyhow!("Tool not found: {}", call.function.name))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::make_ref;
+    use crate::memory::WindowBufferMemory;
+    use crate::mock::{MockCompletion, MockResponse};
+    use crate::tool::{RetryPolicy, Tool};
+    use async_trait::async_trait;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// The runs of a [`TestTool`], shared with the test.
+    #[derive(Debug, Default)]
+    struct ToolRuns {
+        runs: AtomicUsize,
+        running: AtomicUsize,
+        max_running: AtomicUsize,
+        inputs: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl ToolRuns {
+        fn inputs(&self) -> Vec<String> {
+            self.inputs.lock().unwrap().clone()
         }
     }
+
+    /// A tool sleeping for a delay, which fails its first runs.
+    struct TestTool {
+        name: &'static str,
+        delay: Duration,
+        failures: usize,
+        timeout: Option<Duration>,
+        retry_policy: RetryPolicy,
+        requires_approval: bool,
+        runs: Arc<ToolRuns>,
+    }
+
+    impl TestTool {
+        fn new(name: &'static str) -> Self {
+            Self {
+                name,
+                delay: Duration::ZERO,
+                failures: 0,
+                timeout: None,
+                retry_policy: RetryPolicy::default(),
+                requires_approval: true,
+                runs: Arc::new(ToolRuns::default()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Tool for TestTool {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn definition(&self) -> ToolDefinition {
+            ToolDefinition {
+                name: self.name.to_string(),
+                description: format!("The {} tool.", self.name),
+                parameters: json!({"type": "object"}),
+            }
+        }
+
+        fn timeout(&self) -> Option<Duration> {
+            self.timeout
+        }
+
+        fn retry_policy(&self) -> RetryPolicy {
+            self.retry_policy
+        }
+
+        fn requires_approval(&self) -> bool {
+            self.requires_approval
+        }
+
+        async fn run(&self, input: &str) -> Result<String, ToolError> {
+            self.runs.inputs.lock().unwrap().push(input.to_string());
+            let run = self.runs.runs.fetch_add(1, Ordering::SeqCst) + 1;
+            let running = self.runs.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.runs.max_running.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            self.runs.running.fetch_sub(1, Ordering::SeqCst);
+            if run <= self.failures {
+                Err(ToolError::Unknown(format!("run {run} failed")))
+            } else {
+                Ok(format!("run {run}"))
+            }
+        }
+    }
+
+    /// A middleware recording its hook calls, which redacts the tool call arguments.
+    #[derive(Default)]
+    struct RecordingMiddleware(std::sync::Mutex<Vec<String>>);
+
+    impl RecordingMiddleware {
+        fn record(&self, event: String) {
+            self.0.lock().unwrap().push(event);
+        }
+
+        fn events(&self) -> Vec<String> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl Middleware for RecordingMiddleware {
+        async fn before_request(&self, _request: &mut Request) {
+            self.record("before_request".to_string());
+        }
+
+        async fn after_response(&self, response: &ModelResponse) {
+            self.record(format!("after_response: {}", response.content));
+        }
+
+        async fn before_tool_call(&self, call: &mut ToolCall) {
+            self.record(format!("before_tool_call: {}", call.function.name));
+            call.function.arguments = json!({"query": "[redacted]"}).to_string();
+        }
+
+        async fn after_tool_call(&self, call: &mut ToolCallTrace) {
+            self.record(format!("after_tool_call: {}", call.name));
+        }
+
+        async fn on_error(&self, error: &anyhow::Error) {
+            self.record(format!("on_error: {error}"));
+        }
+    }
+
+    fn executor(mock: &MockCompletion, tools: Vec<TestTool>) -> Executor<MockCompletion> {
+        let mut registry = ToolRegistry::new();
+        for tool in tools {
+            registry.register(Box::new(tool)).unwrap();
+        }
+        Executor::new(
+            make_ref(mock.clone()),
+            Arc::new(Vec::new()),
+            make_ref(registry),
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_tool_timeout() {
+        let mock = MockCompletion::new()
+            .respond(MockResponse::tool_call("call_1", "slow", json!({})))
+            .respond("Too slow.");
+        let mut tool = TestTool::new("slow");
+        tool.delay = Duration::from_secs(10);
+        tool.timeout = Some(Duration::from_millis(20));
+
+        let run = executor(&mock, vec![tool])
+            .invoke(Request::new("Run it".to_string(), String::new()))
+            .await
+            .unwrap();
+        let call = &run.trace.steps[0].tool_calls[0];
+        assert!(call.is_error);
+        assert_eq!(
+            call.output,
+            ToolError::Timeout(Duration::from_millis(20)).to_message("slow")
+        );
+        mock.assert_history(
+            1,
+            &[
+                ("user", "Run it"),
+                ("assistant", ""),
+                ("tool", call.output.as_str()),
+            ],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tool_retries() {
+        let mock = MockCompletion::new()
+            .respond(
+                MockResponse::tool_call("call_1", "flaky", json!({})).with_tool_call(
+                    "call_2",
+                    "broken",
+                    json!({}),
+                ),
+            )
+            .respond("Done.");
+        let mut flaky = TestTool::new("flaky");
+        flaky.failures = 2;
+        flaky.retry_policy = RetryPolicy::new(2);
+        let flaky_runs = flaky.runs.clone();
+        let mut broken = TestTool::new("broken");
+        broken.failures = usize::MAX;
+        broken.retry_policy = RetryPolicy::new(2);
+        let broken_runs = broken.runs.clone();
+
+        let run = executor(&mock, vec![flaky, broken])
+            .invoke(Request::new("Run them".to_string(), String::new()))
+            .await
+            .unwrap();
+        let calls = &run.trace.steps[0].tool_calls;
+        assert!(!calls[0].is_error);
+        assert_eq!(calls[0].output, "run 3");
+        assert_eq!(flaky_runs.runs.load(Ordering::SeqCst), 3);
+        assert!(calls[1].is_error);
+        assert_eq!(broken_runs.runs.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrent_tools() {
+        let calls = (1..=5).fold(
+            MockResponse::tool_call("call_0", "slow", json!({})),
+            |response, i| response.with_tool_call(format!("call_{i}"), "slow", json!({})),
+        );
+        let mock = MockCompletion::new().respond(calls).respond("Done.");
+        let mut tool = TestTool::new("slow");
+        tool.delay = Duration::from_millis(20);
+        let runs = tool.runs.clone();
+
+        let run = executor(&mock, vec![tool])
+            .max_concurrent_tools(2)
+            .invoke(Request::new("Run them".to_string(), String::new()))
+            .await
+            .unwrap();
+        assert_eq!(runs.runs.load(Ordering::SeqCst), 6);
+        assert_eq!(runs.max_running.load(Ordering::SeqCst), 2);
+        // The results are sent back in the order of the calls.
+        let ids: Vec<&str> = run.trace.steps[0]
+            .tool_calls
+            .iter()
+            .map(|call| call.id.as_str())
+            .collect();
+        assert_eq!(
+            ids,
+            ["call_0", "call_1", "call_2", "call_3", "call_4", "call_5"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_approval_deny() {
+        let mock = MockCompletion::new()
+            .respond(MockResponse::tool_call(
+                "call_1",
+                "delete",
+                json!({"path": "/"}),
+            ))
+            .respond("I was not allowed to.");
+        let tool = TestTool::new("delete");
+        let runs = tool.runs.clone();
+
+        let run = executor(&mock, vec![tool])
+            .approver(Some(Arc::new(|_: &ToolCall| {
+                ApprovalDecision::deny("Deleting is not allowed")
+            })))
+            .invoke(Request::new("Delete everything".to_string(), String::new()))
+            .await
+            .unwrap();
+        let call = &run.trace.steps[0].tool_calls[0];
+        assert!(call.is_error);
+        assert_eq!(
+            call.output,
+            ToolError::Denied("Deleting is not allowed".to_string()).to_message("delete")
+        );
+        assert_eq!(runs.runs.load(Ordering::SeqCst), 0);
+        assert_eq!(mock.request(1).history[2].content, call.output);
+    }
+
+    #[tokio::test]
+    async fn test_approval_edit() {
+        let mock = MockCompletion::new()
+            .respond(MockResponse::tool_call(
+                "call_1",
+                "search",
+                json!({"query": "secret"}),
+            ))
+            .respond("Found it.");
+        let tool = TestTool::new("search");
+        let runs = tool.runs.clone();
+        let mut registry = ToolRegistry::new();
+        registry.register(Box::new(tool)).unwrap();
+        let memory: Ref<dyn Memory> = make_ref(WindowBufferMemory::new(10));
+        let edited = json!({"query": "public"}).to_string();
+
+        let run = Executor::new(
+            make_ref(mock.clone()),
+            Arc::new(Vec::new()),
+            make_ref(registry),
+            Some(memory.clone()),
+        )
+        .approver(Some(Arc::new(|_: &ToolCall| {
+            ApprovalDecision::edit(json!({"query": "public"}))
+        })))
+        .invoke(Request::new("Search".to_string(), String::new()))
+        .await
+        .unwrap();
+        assert_eq!(runs.inputs(), [edited.clone()]);
+        assert_eq!(run.trace.steps[0].tool_calls[0].arguments, edited);
+        // The model and the memory see the arguments which ran.
+        let history = mock.request(1).history;
+        assert_eq!(history[1].tool_calls[0].function.arguments, edited);
+        let messages = memory.read().await.messages();
+        let calls: Vec<ToolCall> =
+            serde_json::from_value(messages[1].tool_calls.clone().unwrap()).unwrap();
+        assert_eq!(calls[0].function.arguments, edited);
+    }
+
+    #[tokio::test]
+    async fn test_approval_not_required() {
+        let mock = MockCompletion::new()
+            .respond(MockResponse::tool_call("call_1", "clock", json!({})))
+            .respond("It is noon.");
+        let mut tool = TestTool::new("clock");
+        tool.requires_approval = false;
+        let runs = tool.runs.clone();
+
+        let run = executor(&mock, vec![tool])
+            .approver(Some(Arc::new(|_: &ToolCall| {
+                ApprovalDecision::deny("Nothing is allowed")
+            })))
+            .invoke(Request::new("What time is it?".to_string(), String::new()))
+            .await
+            .unwrap();
+        assert!(!run.trace.steps[0].tool_calls[0].is_error);
+        assert_eq!(runs.runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_middleware_hooks() {
+        let mock = MockCompletion::new()
+            .respond(MockResponse::tool_call(
+                "call_1",
+                "search",
+                json!({"query": "secret"}),
+            ))
+            .respond("Found it.");
+        let tool = TestTool::new("search");
+        let runs = tool.runs.clone();
+        let middleware = Arc::new(RecordingMiddleware::default());
+
+        executor(&mock, vec![tool])
+            .middlewares(vec![middleware.clone() as Arc<dyn Middleware>])
+            .invoke(Request::new("Search".to_string(), String::new()))
+            .await
+            .unwrap();
+        assert_eq!(
+            middleware.events(),
+            [
+                "before_request",
+                "after_response: ",
+                "before_tool_call: search",
+                "after_tool_call: search",
+                "before_request",
+                "after_response: Found it.",
+            ]
+        );
+        // The redacted call is the one run and sent back to the model.
+        let redacted = json!({"query": "[redacted]"}).to_string();
+        assert_eq!(runs.inputs(), [redacted.clone()]);
+        assert_eq!(
+            mock.request(1).history[1].tool_calls[0].function.arguments,
+            redacted
+        );
+    }
+
+    #[tokio::test]
+    async fn test_middleware_on_error() {
+        let mock = MockCompletion::new().fail("overloaded");
+        let middleware = Arc::new(RecordingMiddleware::default());
+
+        let result = executor(&mock, Vec::new())
+            .middlewares(vec![middleware.clone() as Arc<dyn Middleware>])
+            .invoke(Request::new("Hi".to_string(), String::new()))
+            .await;
+        assert!(result.is_err());
+        let events = middleware.events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0], "before_request");
+        assert!(events[1].starts_with("on_error: ") && events[1].contains("overloaded"));
+    }
 }
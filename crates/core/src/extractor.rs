@@ -1,12 +1,4 @@
-use std::marker::PhantomData;
-
-use crate::{
-    agent::Agent,
-    chat::{Chat, Completion},
-    task::TaskError,
-    tool::{StructureTool, ToolError},
-};
-use async_trait::async_trait;
+use crate::{agent::Agent, chat::Completion, task::TaskError};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -23,20 +15,10 @@ where
 {
     /// Constructor for Extractor that initializes the agent with the given model.
     #[inline]
-    pub async fn new<T>(model: M) -> Self
-    where
-        T: Serialize + for<'a> Deserialize<'a> + JsonSchema + Send + Sync + 'static,
-    {
+    pub async fn new(model: M) -> Self {
         Self {
             agent: Agent::new("extract-agent", model)
-                .preamble(
-                    r#"Extract the data structure from the input string.
-Note you MUST use the tool named `extractor` to extract the input string to the
-data structure.
-"#,
-                )
-                .tool(ExtractTool::<T> { _data: PhantomData })
-                .await,
+                .preamble("Extract the data structure from the input string."),
         }
     }
 
@@ -46,7 +28,7 @@ data structure.
     where
         T: Serialize + for<'a> Deserialize<'a> + JsonSchema + Send + Sync + 'static,
     {
-        Ok(serde_json::from_str(&self.agent.prompt(input).await?)?)
+        Ok(self.agent.prompt_typed(input).await?)
     }
 }
 
@@ -55,34 +37,37 @@ data structure.
 pub enum ExtractionError {
     #[error("TaskError: {0}")]
     TaskError(#[from] TaskError),
-    #[error("JsonError: {0}")]
-    JsonError(#[from] serde_json::Error),
-}
-
-struct ExtractTool<T>
-where
-    T: Serialize + for<'a> Deserialize<'a> + JsonSchema + Send + Sync,
-{
-    _data: PhantomData<T>,
 }
 
-#[async_trait]
-impl<T> StructureTool for ExtractTool<T>
-where
-    T: Serialize + for<'a> Deserialize<'a> + JsonSchema + Send + Sync,
-{
-    type Input = T;
-    type Output = T;
-
-    fn name(&self) -> &str {
-        "extractor"
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockCompletion, MockResponse};
+    use serde_json::json;
 
-    fn description(&self) -> &str {
-        "Extract the data structure from the input string."
+    #[derive(Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+    struct Person {
+        name: String,
+        age: u8,
     }
 
-    async fn run_with_args(&self, input: Self::Input) -> Result<Self::Output, ToolError> {
-        Ok(input)
+    #[tokio::test]
+    async fn test_extractor() {
+        let mock = MockCompletion::new().respond(MockResponse::tool_call(
+            "call_1",
+            "final_output",
+            json!({"name": "Alice", "age": 30}),
+        ));
+        let extractor = Extractor::new(mock.clone()).await;
+        let person: Person = extractor.extract("Alice is 30 years old.").await.unwrap();
+        assert_eq!(
+            person,
+            Person {
+                name: "Alice".to_string(),
+                age: 30
+            }
+        );
+        mock.assert_tools(0, &["final_output"]);
+        mock.assert_prompt_contains(0, "Alice is 30 years old.");
     }
 }
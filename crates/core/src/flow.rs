@@ -3,3 +3,361 @@ pub use dagrs::{
     Action, Content, DefaultNode, EmptyAction, EnvVar, Graph, InChannels, Node, NodeId, NodeName,
     NodeTable, OutChannels, Output, RecvErr, SendErr, auto_node, dependencies,
 };
+
+use crate::agent::Agent;
+use crate::chat::{Chat, Completion};
+use async_trait::async_trait;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// The placeholder of a node template replaced by the upstream outputs not bound to a name.
+pub const INPUT_PLACEHOLDER: &str = "{input}";
+
+/// The content an [`AgentNode`] sends to each of its downstream nodes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeMessage {
+    /// The output of the upstream agent.
+    Output(String),
+    /// The edge was not taken, or the upstream node was skipped or failed.
+    Skipped,
+}
+
+type EdgeCondition = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// A graph node which prompts an agent with the outputs of its upstream nodes.
+///
+/// The upstream outputs are rendered into the prompt template: outputs bound to a name
+/// with [`AgentNode::input`] replace their `{name}` placeholder and the others are joined
+/// into `{input}`. The agent output is sent downstream as a [`NodeMessage`] and is the
+/// output of the node. A node whose upstream nodes were all skipped is skipped as well,
+/// without prompting its agent.
+///
+/// The edges of the node are declared with [`AgentNode::upstream`] and
+/// [`AgentNode::downstream`], and must also be added to the graph with
+/// [`Graph::add_edge`].
+pub struct AgentNode<M: Completion> {
+    id: NodeId,
+    name: NodeName,
+    agent: Arc<Agent<M>>,
+    template: String,
+    /// The upstream nodes, in the order their outputs are joined into `{input}`.
+    upstream: Vec<NodeId>,
+    /// The downstream nodes.
+    downstream: Vec<NodeId>,
+    /// The placeholder names of the upstream outputs bound to a name.
+    inputs: HashMap<NodeId, String>,
+    /// The conditions of the outgoing edges, edges without one are always taken.
+    conditions: HashMap<NodeId, EdgeCondition>,
+    collector: Option<ResultCollector>,
+    in_channels: InChannels,
+    out_channels: OutChannels,
+}
+
+impl<M: Completion> AgentNode<M> {
+    /// Creates a new node prompting the agent with the rendered template.
+    pub fn new(
+        name: impl ToString,
+        agent: impl Into<Arc<Agent<M>>>,
+        template: impl ToString,
+        node_table: &mut NodeTable,
+    ) -> Self {
+        let name = name.to_string();
+        Self {
+            id: node_table.alloc_id_for(&name),
+            name,
+            agent: agent.into(),
+            template: template.to_string(),
+            upstream: Vec::new(),
+            downstream: Vec::new(),
+            inputs: HashMap::new(),
+            conditions: HashMap::new(),
+            collector: None,
+            in_channels: InChannels::default(),
+            out_channels: OutChannels::default(),
+        }
+    }
+
+    /// Receives the output of an upstream node.
+    pub fn upstream(mut self, from: NodeId) -> Self {
+        if !self.upstream.contains(&from) {
+            self.upstream.push(from);
+        }
+        self
+    }
+
+    /// Sends the output to a downstream node.
+    pub fn downstream(mut self, to: NodeId) -> Self {
+        if !self.downstream.contains(&to) {
+            self.downstream.push(to);
+        }
+        self
+    }
+
+    /// Receives the output of an upstream node into the `{name}` placeholder of the
+    /// template.
+    pub fn input(mut self, name: impl ToString, from: NodeId) -> Self {
+        self.inputs.insert(from, name.to_string());
+        self.upstream(from)
+    }
+
+    /// Sends the output to a downstream node only when the condition holds on it, the
+    /// downstream node receives [`NodeMessage::Skipped`] otherwise.
+    pub fn when(
+        mut self,
+        to: NodeId,
+        condition: impl Fn(&str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.conditions.insert(to, Arc::new(condition));
+        self.downstream(to)
+    }
+
+    /// Records the output of the node into the collector.
+    pub fn collector(mut self, collector: ResultCollector) -> Self {
+        self.collector = Some(collector);
+        self
+    }
+
+    /// Receives the upstream outputs and renders the prompt, `None` when all upstream
+    /// nodes were skipped.
+    async fn receive(&mut self) -> Option<String> {
+        let mut input = Vec::new();
+        let mut named = HashMap::new();
+        let mut received = self.upstream.is_empty();
+        for &id in &self.upstream {
+            let message = match self.in_channels.recv_from(&id).await {
+                Ok(content) => content.get::<NodeMessage>().cloned().or_else(|| {
+                    content
+                        .get::<String>()
+                        .map(|output| NodeMessage::Output(output.clone()))
+                }),
+                Err(_) => None,
+            };
+            let Some(NodeMessage::Output(output)) = message else {
+                continue;
+            };
+            received = true;
+            match self.inputs.get(&id) {
+                Some(name) => {
+                    named.insert(name.clone(), output);
+                }
+                None => input.push(output),
+            }
+        }
+        received.then(|| render_template(&self.template, &input, &named))
+    }
+
+    /// Sends the output, or [`NodeMessage::Skipped`] when there is none, to the downstream
+    /// nodes whose edge condition holds.
+    async fn send(&self, output: Option<&str>) {
+        for id in &self.downstream {
+            let message = match output {
+                Some(output)
+                    if self
+                        .conditions
+                        .get(id)
+                        .is_none_or(|condition| condition(output)) =>
+                {
+                    NodeMessage::Output(output.to_string())
+                }
+                _ => NodeMessage::Skipped,
+            };
+            let _ = self.out_channels.send_to(id, Content::new(message)).await;
+        }
+    }
+}
+
+#[async_trait]
+impl<M: Completion + Send + Sync + 'static> Node for AgentNode<M> {
+    fn id(&self) -> NodeId {
+        self.id
+    }
+
+    fn name(&self) -> NodeName {
+        self.name.clone()
+    }
+
+    fn input_channels(&mut self) -> &mut InChannels {
+        &mut self.in_channels
+    }
+
+    fn output_channels(&mut self) -> &mut OutChannels {
+        &mut self.out_channels
+    }
+
+    async fn run(&mut self, _env: Arc<EnvVar>) -> Output {
+        let Some(prompt) = self.receive().await else {
+            self.send(None).await;
+            return Output::empty();
+        };
+        match self.agent.prompt(&prompt).await {
+            Ok(output) => {
+                if let Some(collector) = &self.collector {
+                    collector.insert(&self.name, &output);
+                }
+                self.send(Some(&output)).await;
+                Output::new(output)
+            }
+            Err(err) => {
+                self.send(None).await;
+                Output::error(err.to_string())
+            }
+        }
+    }
+}
+
+/// Collects the outputs of the agent nodes of a graph, keyed by node name.
+#[derive(Debug, Clone, Default)]
+pub struct ResultCollector {
+    results: Arc<Mutex<BTreeMap<NodeName, String>>>,
+}
+
+impl ResultCollector {
+    /// Creates an empty collector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the output of a node, `None` if it was skipped, failed or did not run yet.
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.results.lock().unwrap().get(name).cloned()
+    }
+
+    /// Returns the outputs of all nodes which produced one.
+    pub fn results(&self) -> BTreeMap<NodeName, String> {
+        self.results.lock().unwrap().clone()
+    }
+
+    fn insert(&self, name: &str, output: &str) {
+        self.results
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), output.to_string());
+    }
+}
+
+/// Replaces the `{name}` placeholders of the template with the named outputs and
+/// `{input}` with the other outputs, separated by blank lines.
+///
+/// The template is rendered in a single pass, placeholders in the outputs are kept as is.
+fn render_template(template: &str, input: &[String], named: &HashMap<String, String>) -> String {
+    let mut prompt = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        prompt.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest[1..].find(['{', '}']).map(|end| end + 1) else {
+            break;
+        };
+        let placeholder = &rest[..=end];
+        let output = if placeholder == INPUT_PLACEHOLDER {
+            Some(input.join("\n\n"))
+        } else if placeholder.ends_with('}') {
+            named.get(&placeholder[1..end]).cloned()
+        } else {
+            None
+        };
+        match output {
+            Some(output) => {
+                prompt.push_str(&output);
+                rest = &rest[end + 1..];
+            }
+            // Not a placeholder, the text is kept up to the next brace.
+            None => {
+                prompt.push_str(&rest[..end]);
+                rest = &rest[end..];
+            }
+        }
+    }
+    prompt.push_str(rest);
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockCompletion;
+
+    #[test]
+    fn test_render_template() {
+        let named = HashMap::from([("draft".to_string(), "Hello".to_string())]);
+        let input = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(
+            render_template("Review {draft} with:\n{input}", &input, &named),
+            "Review Hello with:\na\n\nb"
+        );
+        // The outputs are not rendered again, and unknown placeholders are kept.
+        let named = HashMap::from([("draft".to_string(), "{input} {draft}".to_string())]);
+        assert_eq!(
+            render_template("{{draft}} {unknown} {input}", &input, &named),
+            "{{input} {draft}} {unknown} a\n\nb"
+        );
+    }
+
+    #[test]
+    fn test_agent_nodes_graph() {
+        let writer_model = MockCompletion::new().respond("A draft");
+        let reviewer_model = MockCompletion::new();
+        let editor_model = MockCompletion::new();
+        let summarizer_model = MockCompletion::new().respond("A summary");
+        let collector = ResultCollector::new();
+        let mut node_table = NodeTable::new();
+        let writer = AgentNode::new(
+            "writer",
+            Agent::new("writer", writer_model.clone()),
+            "Write a story",
+            &mut node_table,
+        );
+        let reviewer = AgentNode::new(
+            "reviewer",
+            Agent::new("reviewer", reviewer_model.clone()),
+            "Review {draft}",
+            &mut node_table,
+        );
+        let editor = AgentNode::new(
+            "editor",
+            Agent::new("editor", editor_model.clone()),
+            "Edit {input}",
+            &mut node_table,
+        );
+        let summarizer = AgentNode::new(
+            "summarizer",
+            Agent::new("summarizer", summarizer_model.clone()),
+            "Summarize {input}",
+            &mut node_table,
+        );
+        let (writer_id, reviewer_id, editor_id, summarizer_id) =
+            (writer.id(), reviewer.id(), editor.id(), summarizer.id());
+        let writer = writer
+            .when(reviewer_id, |output| output.contains("final"))
+            .downstream(summarizer_id)
+            .collector(collector.clone());
+        let reviewer = reviewer
+            .input("draft", writer_id)
+            .downstream(editor_id)
+            .collector(collector.clone());
+        let editor = editor.upstream(reviewer_id).collector(collector.clone());
+        let summarizer = summarizer.upstream(writer_id).collector(collector.clone());
+
+        let mut graph = Graph::new();
+        graph.add_node(writer);
+        graph.add_node(reviewer);
+        graph.add_node(editor);
+        graph.add_node(summarizer);
+        graph.add_edge(writer_id, vec![reviewer_id, summarizer_id]);
+        graph.add_edge(reviewer_id, vec![editor_id]);
+        graph.start().unwrap();
+
+        // The reviewer edge is not taken, the editor downstream of it is skipped as well.
+        reviewer_model.assert_calls(0);
+        editor_model.assert_calls(0);
+        summarizer_model.assert_prompt_contains(0, "Summarize A draft");
+        assert_eq!(
+            collector.results(),
+            BTreeMap::from([
+                ("summarizer".to_string(), "A summary".to_string()),
+                ("writer".to_string(), "A draft".to_string()),
+            ])
+        );
+        assert_eq!(collector.get("reviewer"), None);
+    }
+}
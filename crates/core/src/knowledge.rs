@@ -1,15 +1,118 @@
-use crate::chunking::Chunker;
+use crate::chunking::{ChunkError, Chunker};
+use crate::store::{Storage, VectorStoreError};
+use async_trait::async_trait;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::OnceCell;
 
+/// The default number of knowledge chunks injected into a prompt.
+pub const DEFAULT_KNOWLEDGE_TOP_K: usize = 4;
+
+/// The default minimum relevance score of the knowledge chunks injected into a prompt.
+pub const DEFAULT_KNOWLEDGE_THRESHOLD: f32 = 0.5;
+
+#[async_trait]
 pub trait Knowledge: Chunker {
     /// Load the content into the memory.
-    fn load(&self) -> Result<String, KnowledgeError>;
+    ///
+    /// Implementations cache the loaded content, so that it is only read once.
+    async fn load(&self) -> Result<String, KnowledgeError>;
     /// Enrich the knowledge with the input string.
-    fn enrich(&self, input: &str) -> Result<String, KnowledgeError>;
+    async fn enrich(&self, input: &str) -> Result<String, KnowledgeError>;
+    /// The name of the knowledge source, e.g. a file path or an URL, used to attribute
+    /// the retrieved chunks.
+    fn source(&self) -> String {
+        "knowledge".to_string()
+    }
 }
 
+#[async_trait]
 pub trait FileKnowledge: Knowledge {
-    fn load_with_path(&self) -> Result<(PathBuf, String), KnowledgeError>;
+    async fn load_with_path(&self) -> Result<(PathBuf, String), KnowledgeError>;
+}
+
+/// Retrieves the knowledge chunks relevant to a prompt instead of injecting whole sources.
+///
+/// The chunks of all knowledge sources are embedded into the store once, on the first
+/// retrieval, and each retrieved chunk is attributed to its source.
+pub struct KnowledgeIndex {
+    store: Arc<dyn Storage>,
+    top_k: usize,
+    threshold: f32,
+    /// The source of each indexed chunk.
+    sources: OnceCell<HashMap<String, String>>,
+}
+
+impl KnowledgeIndex {
+    /// Creates a new `KnowledgeIndex` embedding the knowledge chunks into an empty store.
+    pub fn new(store: impl Storage + 'static) -> Self {
+        Self {
+            store: Arc::new(store),
+            top_k: DEFAULT_KNOWLEDGE_TOP_K,
+            threshold: DEFAULT_KNOWLEDGE_THRESHOLD,
+            sources: OnceCell::new(),
+        }
+    }
+
+    /// Sets the maximum number of chunks injected into a prompt.
+    pub fn top_k(mut self, top_k: usize) -> Self {
+        self.top_k = top_k;
+        self
+    }
+
+    /// Sets the minimum relevance score of the injected chunks.
+    pub fn threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Embeds the chunks of the knowledge sources into the store, if not done yet.
+    pub async fn index(&self, knowledges: &[Box<dyn Knowledge>]) -> Result<(), KnowledgeError> {
+        self.sources(knowledges).await.map(|_| ())
+    }
+
+    /// Indexes the knowledge sources if needed and returns the source of each chunk.
+    async fn sources(
+        &self,
+        knowledges: &[Box<dyn Knowledge>],
+    ) -> Result<&HashMap<String, String>, KnowledgeError> {
+        self.sources
+            .get_or_try_init(|| async {
+                let mut sources = HashMap::new();
+                for knowledge in knowledges {
+                    // Fill the content cache before chunking it.
+                    knowledge.load().await?;
+                    let source = knowledge.source();
+                    for chunk in knowledge.chunk()? {
+                        if sources.insert(chunk.clone(), source.clone()).is_none() {
+                            self.store.save(chunk).await?;
+                        }
+                    }
+                }
+                Ok::<_, KnowledgeError>(sources)
+            })
+            .await
+    }
+
+    /// Returns the chunks relevant to the prompt, each wrapped with its source.
+    pub async fn retrieve(
+        &self,
+        knowledges: &[Box<dyn Knowledge>],
+        prompt: &str,
+    ) -> Result<Vec<String>, KnowledgeError> {
+        let sources = self.sources(knowledges).await?;
+        Ok(self
+            .store
+            .search(prompt, self.top_k, self.threshold)
+            .await?
+            .into_iter()
+            .map(|(_, chunk, _)| {
+                let source = sources.get(&chunk).map_or("knowledge", String::as_str);
+                format!("<knowledge source=\"{source}\">\n{chunk}\n</knowledge>")
+            })
+            .collect())
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -21,4 +124,105 @@ pub enum KnowledgeError {
     Unknown(String),
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
+    #[error("Chunk error: {0}")]
+    ChunkError(#[from] ChunkError),
+    #[error("Vector store error: {0}")]
+    StoreError(#[from] VectorStoreError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{DocumentId, TopNResults};
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A store which matches the saved values containing the query.
+    #[derive(Clone, Default)]
+    struct KeywordStorage(Arc<Mutex<Vec<String>>>);
+
+    #[async_trait]
+    impl Storage for KeywordStorage {
+        async fn save(&self, value: String) -> Result<(), VectorStoreError> {
+            self.0.lock().unwrap().push(value);
+            Ok(())
+        }
+
+        async fn search(&self, query: &str, limit: usize, _threshold: f32) -> TopNResults {
+            Ok(self
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .enumerate()
+                .filter(|(_, value)| value.contains(query))
+                .take(limit)
+                .map(|(i, value)| (DocumentId(i.to_string()), value.clone(), 1.0))
+                .collect())
+        }
+
+        async fn reset(&self) -> Result<(), VectorStoreError> {
+            self.0.lock().unwrap().clear();
+            Ok(())
+        }
+    }
+
+    /// A knowledge source made of fixed chunks, which counts its loads.
+    struct ChunksKnowledge {
+        source: &'static str,
+        chunks: Vec<&'static str>,
+        loads: AtomicUsize,
+    }
+
+    impl Chunker for ChunksKnowledge {
+        fn chunk(&self) -> Result<Vec<String>, ChunkError> {
+            Ok(self.chunks.iter().map(|chunk| chunk.to_string()).collect())
+        }
+    }
+
+    #[async_trait]
+    impl Knowledge for ChunksKnowledge {
+        async fn load(&self) -> Result<String, KnowledgeError> {
+            self.loads.fetch_add(1, Ordering::SeqCst);
+            Ok(self.chunks.join("\n"))
+        }
+
+        async fn enrich(&self, _input: &str) -> Result<String, KnowledgeError> {
+            self.load().await
+        }
+
+        fn source(&self) -> String {
+            self.source.to_string()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_knowledge_index_retrieve() {
+        let storage = KeywordStorage::default();
+        let index = KnowledgeIndex::new(storage.clone()).top_k(2);
+        let knowledges: Vec<Box<dyn Knowledge>> = vec![
+            Box::new(ChunksKnowledge {
+                source: "seoul.md",
+                chunks: vec!["Seoul is the capital of Korea.", "Seoul has palaces."],
+                loads: AtomicUsize::new(0),
+            }),
+            Box::new(ChunksKnowledge {
+                source: "paris.md",
+                chunks: vec!["Paris is the capital of France.", "Seoul has palaces."],
+                loads: AtomicUsize::new(0),
+            }),
+        ];
+
+        let chunks = index.retrieve(&knowledges, "capital").await.unwrap();
+        assert_eq!(
+            chunks,
+            [
+                "<knowledge source=\"seoul.md\">\nSeoul is the capital of Korea.\n</knowledge>",
+                "<knowledge source=\"paris.md\">\nParis is the capital of France.\n</knowledge>",
+            ]
+        );
+        // The chunks are indexed once, duplicated chunks included.
+        index.retrieve(&knowledges, "palaces").await.unwrap();
+        assert_eq!(storage.0.lock().unwrap().len(), 3);
+    }
 }
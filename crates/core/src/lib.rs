@@ -1,8 +1,12 @@
 pub mod agent;
+pub mod approval;
+pub mod budget;
+pub mod cassette;
 pub mod chat;
 pub mod chunking;
 pub mod cleaner;
 pub mod concatenator;
+pub mod delegation;
 pub mod embeddings;
 pub mod executor;
 pub mod extractor;
@@ -12,11 +16,18 @@ pub mod knowledge;
 pub mod llm;
 pub mod mcp;
 pub mod memory;
+pub mod middleware;
+pub mod mock;
 pub mod parser;
+pub mod react;
+pub mod retriever;
+pub mod spec;
 pub mod splitting;
 pub mod store;
 pub mod task;
+pub mod telemetry;
 pub mod tool;
+pub mod usage;
 
 pub use alith_client as client;
 pub use alith_interface as interface;
@@ -31,3 +42,23 @@ pub type Ref<T> = Arc<RwLock<T>>;
 pub fn make_ref<T>(t: T) -> Ref<T> {
     Arc::new(RwLock::new(t))
 }
+
+/// Items used by the code generated by the `alith-macros` crate.
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::tool::{StructureTool, ToolError};
+    pub use async_trait;
+    pub use schemars;
+    pub use serde;
+
+    /// Converts the error of a tool function into a tool error, tool errors are kept as is.
+    pub fn tool_error<E>(err: E) -> ToolError
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        match err.into().downcast::<ToolError>() {
+            Ok(err) => *err,
+            Err(err) => ToolError::NormalError(err),
+        }
+    }
+}
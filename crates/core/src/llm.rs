@@ -1,8 +1,9 @@
 pub mod client;
 
-use crate::chat::{Completion, CompletionError};
+use crate::chat::{Completion, CompletionError, CompletionStream, StreamingCompletion};
 use crate::embeddings::{Embeddings, EmbeddingsData, EmbeddingsError};
 pub use crate::llm::client::ClientConfig;
+use crate::spec::ModelSpec;
 use anyhow::Result;
 use async_trait::async_trait;
 use client::{Client, CompletionResponse};
@@ -67,6 +68,8 @@ pub struct LLM {
     /// The name or identifier of the model to use
     /// Examples: "gpt-4", "gpt-3.5-turbo", etc.
     pub model: String,
+    /// The base URL of the OpenAI compatible endpoint, if any
+    base_url: Option<String>,
     /// The LLM client used to communicate with model backends
     client: Client,
 }
@@ -80,6 +83,7 @@ impl LLM {
     pub fn from_model_name_and_config(model: &str, config: ClientConfig) -> Result<Self> {
         Ok(Self {
             model: model.to_string(),
+            base_url: None,
             client: Client::from_model_name(model, config)?,
         })
     }
@@ -97,6 +101,7 @@ impl LLM {
     ) -> Result<Self> {
         Ok(Self {
             model: model.to_string(),
+            base_url: Some(base_url.to_string()),
             client: Client::openai_compatible_client(api_key, base_url, model, config)?,
         })
     }
@@ -112,6 +117,18 @@ impl LLM {
     pub fn client(&self) -> &Client {
         &self.client
     }
+
+    /// Returns the declarative spec of the model, the API key is never included.
+    pub fn spec(&self) -> ModelSpec {
+        match &self.base_url {
+            Some(base_url) => ModelSpec::Endpoint {
+                model: self.model.clone(),
+                base_url: base_url.clone(),
+                api_key_env: None,
+            },
+            None => ModelSpec::Name(self.model.clone()),
+        }
+    }
 }
 
 impl Completion for LLM {
@@ -123,6 +140,23 @@ impl Completion for LLM {
     ) -> Result<Self::Response, CompletionError> {
         self.client.completion(request).await
     }
+
+    fn supports_response_format(&self) -> bool {
+        self.client.supports_response_format()
+    }
+
+    fn model_id(&self) -> String {
+        self.model.clone()
+    }
+}
+
+impl StreamingCompletion for LLM {
+    async fn completion_stream(
+        &mut self,
+        request: crate::chat::Request,
+    ) -> Result<CompletionStream, CompletionError> {
+        self.client.completion_stream(request).await
+    }
 }
 
 #[derive(Clone)]
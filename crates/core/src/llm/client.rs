@@ -7,15 +7,19 @@ use std::sync::Arc;
 use crate::chat::CallFunction;
 use crate::chat::Completion;
 use crate::chat::CompletionError;
+use crate::chat::CompletionStream;
 use crate::chat::Request;
 use crate::chat::ResponseContent;
 use crate::chat::ResponseTokenUsage;
 use crate::chat::ResponseToolCalls;
+use crate::chat::StreamingCompletion;
 use crate::chat::ToolCall;
 use crate::embeddings::EmbeddingsData;
 use crate::embeddings::EmbeddingsError;
+use alith_interface::cassette::Cassette;
 use alith_interface::requests::completion::TokenUsage;
 use anyhow::Result;
+use futures::{StreamExt, TryStreamExt};
 
 pub use alith_client as client;
 pub use alith_client::LLMClient;
@@ -37,6 +41,7 @@ macro_rules! build_llm_client {
                     .extra_headers
                     .insert(HeaderName::from_str(k.as_str())?, v.parse()?);
             }
+            builder.config.api_config.cassette = $config.cassette.clone();
             let client = builder.init()?;
             return Ok(Client { client });
         }
@@ -121,6 +126,7 @@ impl Client {
                 .extra_headers
                 .insert(HeaderName::from_str(k.as_str())?, v.parse()?);
         }
+        builder.config.api_config.cassette = config.cassette;
         let client = builder.init()?;
         Ok(Client { client })
     }
@@ -129,6 +135,8 @@ impl Client {
 #[derive(Debug, Default, bon::Builder)]
 pub struct ClientConfig {
     pub extra_headers: HashMap<String, String>,
+    /// The cassette recording or replaying the API calls of the client.
+    pub cassette: Option<Arc<Cassette>>,
 }
 
 impl ResponseContent for CompletionResponse {
@@ -167,10 +175,12 @@ impl Drop for Client {
     }
 }
 
-impl Completion for Client {
-    type Response = CompletionResponse;
-
-    async fn completion(&mut self, request: Request) -> Result<Self::Response, CompletionError> {
+impl Client {
+    /// Builds the backend completion request for a chat request.
+    pub(crate) fn build_completion(
+        &self,
+        request: &Request,
+    ) -> Result<ChatCompletion, CompletionError> {
         // New the complation request
         let mut completion = self.client.chat_completion();
         if let Some(temperature) = request.temperature {
@@ -184,37 +194,100 @@ impl Completion for Client {
         }
         // Construct the prompt
         let prompt = completion.prompt();
-        // Add preamble if provided
-        if !request.preamble.trim().is_empty() {
+        // Add preamble if provided, followed by the system messages of the history, e.g. a
+        // conversation summary, since the system message must be the first message.
+        let system = std::iter::once(request.preamble.as_str())
+            .chain(
+                request
+                    .history
+                    .iter()
+                    .filter(|msg| msg.role == "system")
+                    .map(|msg| msg.content.as_str()),
+            )
+            .filter(|content| !content.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        if !system.is_empty() {
             prompt
                 .add_system_message()
                 .map_err(|err| CompletionError::Normal(err.to_string()))?
-                .set_content(&request.preamble);
+                .set_content(&system);
         }
         // Add conversation history
-        for msg in &request.history {
-            let result = match msg.role.as_str() {
-                "system" => prompt.add_system_message(),
+        for msg in request.history.iter().filter(|msg| msg.role != "system") {
+            let message = match msg.role.as_str() {
                 "user" => prompt.add_user_message(),
                 "assistant" => prompt.add_assistant_message(),
+                "tool" => prompt.add_tool_message(),
                 _ => continue, // Just skip unknown roles
-            };
-            result
+            }
+            .map_err(|err| CompletionError::Normal(err.to_string()))?;
+            message.set_content(&msg.content);
+            if !msg.tool_calls.is_empty() {
+                message.set_tool_calls(
+                    msg.tool_calls
+                        .iter()
+                        .map(|call| PromptToolCall {
+                            id: call.id.clone(),
+                            name: call.function.name.clone(),
+                            arguments: call.function.arguments.clone(),
+                        })
+                        .collect(),
+                );
+            }
+            if let Some(tool_call_id) = &msg.tool_call_id {
+                message.set_tool_call_id(tool_call_id);
+            }
+        }
+        if !request.prompt.is_empty() {
+            prompt
+                .add_user_message()
                 .map_err(|err| CompletionError::Normal(err.to_string()))?
-                .set_content(&msg.content);
+                .set_content(request.effective_prompt().as_str());
         }
-        prompt
-            .add_user_message()
-            .map_err(|err| CompletionError::Normal(err.to_string()))?
-            .set_content(request.effective_prompt().as_str());
         // Add custom tools
         completion.base_req.tools.append(&mut request.tools.clone());
+        completion.base_req.response_format = request.response_format.clone();
+        completion.base_req.cancellation = Some(request.options.cancellation.clone());
+        completion.base_req.deadline = request.options.deadline;
+        Ok(completion)
+    }
+}
+
+impl Completion for Client {
+    type Response = CompletionResponse;
+
+    async fn completion(&mut self, request: Request) -> Result<Self::Response, CompletionError> {
         // Execute the completion request
-        completion
+        self.build_completion(&request)?
             .run()
             .await
             .map_err(|err| CompletionError::Normal(err.to_string()))
     }
+
+    fn supports_response_format(&self) -> bool {
+        self.client.backend.supports_json_schema()
+    }
+
+    fn model_id(&self) -> String {
+        self.client.backend.model_id().to_string()
+    }
+}
+
+impl StreamingCompletion for Client {
+    async fn completion_stream(
+        &mut self,
+        request: Request,
+    ) -> Result<CompletionStream, CompletionError> {
+        let stream = self
+            .build_completion(&request)?
+            .stream()
+            .await
+            .map_err(|err| CompletionError::Normal(err.to_string()))?;
+        Ok(stream
+            .map_err(|err| CompletionError::Normal(err.to_string()))
+            .boxed())
+    }
 }
 
 impl Client {
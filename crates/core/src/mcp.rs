@@ -1,10 +1,10 @@
-use crate::tool::ToolDefinition;
+use crate::tool::{ToolDefinition, ToolRegistryError};
 pub use mcp_client::Error;
 pub use mcp_client::McpService;
 pub use mcp_client::client::{ClientCapabilities, ClientInfo};
 use mcp_client::client::{McpClient, McpClientTrait};
 pub use mcp_client::transport::{SseTransport, StdioTransport, Transport};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::ops::Deref;
 use std::path::Path;
@@ -12,16 +12,16 @@ use std::time::Duration;
 use thiserror::Error;
 use tracing_subscriber::EnvFilter;
 
-#[derive(Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MCPServerConfig {
     pub command: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub args: Vec<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub env: HashMap<String, String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MCPConfig {
     #[serde(rename = "mcpServers")]
     pub mcp_servers: HashMap<String, MCPServerConfig>,
@@ -59,16 +59,31 @@ pub fn init_logging() {
 pub async fn setup_mcp_clients<P: AsRef<Path>>(
     path: P,
 ) -> Result<HashMap<String, MCPClient>, MCPError> {
-    let path = path.as_ref();
-    let config_str = tokio::fs::read_to_string(&path).await?;
-    let config: MCPConfig = serde_json::from_str(&config_str)?;
+    let config = read_mcp_config(path).await?;
+    start_mcp_clients(&config.mcp_servers).await
+}
+
+/// Read the MCP servers config from the path.
+pub async fn read_mcp_config<P: AsRef<Path>>(path: P) -> Result<MCPConfig, MCPError> {
+    let config_str = tokio::fs::read_to_string(path.as_ref()).await?;
+    Ok(serde_json::from_str(&config_str)?)
+}
 
+/// Spawn an MCP client for each server config, keyed by the server name.
+pub async fn start_mcp_clients(
+    servers: &HashMap<String, MCPServerConfig>,
+) -> Result<HashMap<String, MCPClient>, MCPError> {
     let mut mcp_clients_map = HashMap::new();
 
     // For each server in the config, spawn an MCP client
-    for (server_name, server_conf) in config.mcp_servers {
-        let client = stdio_client(server_conf.command, server_conf.args, server_conf.env).await?;
-        mcp_clients_map.insert(server_name, client);
+    for (server_name, server_conf) in servers {
+        let client = stdio_client(
+            &server_conf.command,
+            server_conf.args.iter().collect(),
+            server_conf.env.clone(),
+        )
+        .await?;
+        mcp_clients_map.insert(server_name.clone(), client);
     }
 
     Ok(mcp_clients_map)
@@ -82,6 +97,8 @@ pub enum MCPError {
     ConfigParseError(#[from] serde_json::Error),
     #[error("MCP error {0}")]
     MCPError(#[from] Error),
+    #[error("Tool registry error: {0}")]
+    ToolRegistryError(#[from] ToolRegistryError),
 }
 
 /// Create a sse mcp client.
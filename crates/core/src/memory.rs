@@ -1,5 +1,14 @@
+use crate::chat::{Completion, Message as ChatMessage, Request, ResponseContent};
+use crate::spec::MemorySpec;
+use crate::store::{Storage, VectorStoreError};
+use crate::{Ref, make_ref};
+use alith_models::Tokenizer;
+use futures::future::BoxFuture;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -36,7 +45,7 @@ impl MessageType {
 }
 
 /// Represents a message with content, type, optional ID, and optional tool calls.
-#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
 pub struct Message {
     pub content: String,
     pub message_type: MessageType,
@@ -106,6 +115,25 @@ impl Message {
     }
 }
 
+/// Converts a memory message into a chat message, restoring its tool calls and tool call ID.
+impl From<Message> for ChatMessage {
+    fn from(message: Message) -> Self {
+        ChatMessage {
+            role: message.message_type.type_string(),
+            content: message.content,
+            tool_calls: message
+                .tool_calls
+                .and_then(|tool_calls| serde_json::from_value(tool_calls).ok())
+                .unwrap_or_default(),
+            tool_call_id: if message.message_type == MessageType::Tool {
+                message.id
+            } else {
+                None
+            },
+        }
+    }
+}
+
 /// A trait representing a memory storage for messages.
 pub trait Memory: Send + Sync {
     /// Returns all messages stored in memory.
@@ -135,6 +163,18 @@ pub trait Memory: Send + Sync {
             .collect::<Vec<String>>()
             .join("\n")
     }
+
+    /// Returns the declarative spec of the memory, `None` when it can not be described by one.
+    fn spec(&self) -> Option<MemorySpec> {
+        None
+    }
+
+    /// Returns the messages to send as history with a new prompt, all messages by default.
+    ///
+    /// Memories which retrieve past messages by their relevance override this method.
+    fn relevant_messages<'a>(&'a self, _prompt: &'a str) -> BoxFuture<'a, Vec<Message>> {
+        Box::pin(async move { self.messages() })
+    }
 }
 
 /// Converts a type implementing `Memory` into a boxed trait object.
@@ -196,16 +236,691 @@ impl Memory for WindowBufferMemory {
         self.messages.clone()
     }
 
-    /// Adds a message to the buffer, removing the oldest message if the buffer is full.
+    /// Adds a message to the buffer, removing the oldest turns if the buffer is full.
+    ///
+    /// Whole turns are removed, so that the buffer starts with a user message and tool
+    /// results are never separated from their tool calls. The latest turn is always kept,
+    /// even if it is longer than the window.
     fn add_message(&mut self, message: Message) {
-        if self.messages.len() >= self.window_size {
-            self.messages.remove(0);
+        self.messages.push(message);
+        let tokens = vec![1; self.messages.len()];
+        let start = oldest_turns_over_budget(&tokens, &self.messages, self.window_size);
+        self.messages.drain(..start);
+    }
+
+    /// Clears all messages from the buffer.
+    fn clear(&mut self) {
+        self.messages.clear();
+    }
+
+    fn spec(&self) -> Option<MemorySpec> {
+        Some(MemorySpec::WindowBuffer {
+            window_size: self.window_size,
+        })
+    }
+}
+
+/// The number of tokens added to the content of each message for its role and delimiters.
+const TOKENS_PER_MESSAGE: usize = 4;
+
+/// Counts the tokens of a message, including its tool calls.
+fn message_tokens(tokenizer: &Tokenizer, message: &Message) -> usize {
+    let tool_calls = message
+        .tool_calls
+        .as_ref()
+        .map(|tool_calls| tokenizer.count_tokens(&tool_calls.to_string()) as usize)
+        .unwrap_or_default();
+    tokenizer.count_tokens(&message.content) as usize + tool_calls + TOKENS_PER_MESSAGE
+}
+
+/// Returns the number of oldest messages to drop so that the remaining messages fit in the
+/// token budget.
+///
+/// Whole turns are dropped, so that the remaining messages start with a user message and
+/// tool results are never separated from their tool calls. The latest turn is always kept.
+fn oldest_turns_over_budget(tokens: &[usize], messages: &[Message], budget: usize) -> usize {
+    let mut total: usize = tokens.iter().sum();
+    let mut start = 0;
+    while total > budget {
+        let end = messages
+            .iter()
+            .skip(start + 1)
+            .position(|message| message.message_type == MessageType::Human)
+            .map_or(messages.len(), |i| start + 1 + i);
+        if end >= messages.len() {
+            break;
         }
+        total -= tokens[start..end].iter().sum::<usize>();
+        start = end;
+    }
+    start
+}
+
+/// A memory which keeps the latest messages fitting in a token budget.
+///
+/// Unlike [`WindowBufferMemory`], messages are dropped by their token count, oldest turns
+/// first, so that the history neither wastes nor overflows the model context.
+pub struct TokenBufferMemory {
+    tokenizer: Arc<Tokenizer>,
+    max_tokens: usize,
+    messages: Vec<Message>,
+    tokens: Vec<usize>,
+}
+
+impl TokenBufferMemory {
+    /// Creates a new `TokenBufferMemory` keeping at most `max_tokens` tokens of history.
+    pub fn new(tokenizer: Arc<Tokenizer>, max_tokens: usize) -> Self {
+        Self {
+            tokenizer,
+            max_tokens,
+            messages: Vec::new(),
+            tokens: Vec::new(),
+        }
+    }
+
+    /// Get the token budget.
+    #[inline]
+    pub fn max_tokens(&self) -> usize {
+        self.max_tokens
+    }
+
+    /// Get the number of tokens of the stored messages.
+    #[inline]
+    pub fn total_tokens(&self) -> usize {
+        self.tokens.iter().sum()
+    }
+}
+
+impl Memory for TokenBufferMemory {
+    /// Returns all messages in the buffer.
+    fn messages(&self) -> Vec<Message> {
+        self.messages.clone()
+    }
+
+    /// Adds a message to the buffer, removing the oldest turns if the budget is exceeded.
+    fn add_message(&mut self, message: Message) {
+        self.tokens.push(message_tokens(&self.tokenizer, &message));
         self.messages.push(message);
+        let start = oldest_turns_over_budget(&self.tokens, &self.messages, self.max_tokens);
+        self.messages.drain(..start);
+        self.tokens.drain(..start);
     }
 
     /// Clears all messages from the buffer.
     fn clear(&mut self) {
         self.messages.clear();
+        self.tokens.clear();
+    }
+}
+
+/// The instructions used to fold conversation turns into the running summary.
+const SUMMARY_PREAMBLE: &str = "Progressively summarize the lines of conversation provided, \
+adding onto the previous summary and returning a new summary. Keep the facts, names, \
+decisions and open questions, and answer with the summary only.";
+
+/// A memory which folds the oldest turns into a running summary once a token budget is
+/// exceeded.
+///
+/// The summary is returned as a system message before the remaining messages. Folding is
+/// done by the model in a background task, so the budget may be exceeded until the summary
+/// is ready. A failed summary is retried on the next message. Outside of a Tokio runtime,
+/// the oldest turns are dropped without being summarized.
+pub struct SummaryBufferMemory<M: Completion> {
+    model: Ref<M>,
+    tokenizer: Arc<Tokenizer>,
+    max_tokens: usize,
+    state: Arc<std::sync::Mutex<SummaryState>>,
+}
+
+#[derive(Default)]
+struct SummaryState {
+    summary: Option<String>,
+    messages: Vec<Message>,
+    tokens: Vec<usize>,
+    /// The number of oldest messages being folded into the summary.
+    folding: usize,
+    /// Incremented when the memory is cleared, so that pending summaries are discarded.
+    generation: usize,
+}
+
+impl<M: Completion + Send + Sync + 'static> SummaryBufferMemory<M> {
+    /// Creates a new `SummaryBufferMemory` keeping about `max_tokens` tokens of history,
+    /// the summary included.
+    pub fn new(model: M, tokenizer: Arc<Tokenizer>, max_tokens: usize) -> Self {
+        Self::new_with_ref(make_ref(model), tokenizer, max_tokens)
+    }
+
+    /// Creates a new `SummaryBufferMemory` using a model shared with e.g. an agent.
+    pub fn new_with_ref(model: Ref<M>, tokenizer: Arc<Tokenizer>, max_tokens: usize) -> Self {
+        Self {
+            model,
+            tokenizer,
+            max_tokens,
+            state: Default::default(),
+        }
+    }
+
+    /// Get the running summary of the folded turns, if any.
+    pub fn summary(&self) -> Option<String> {
+        self.state().summary.clone()
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, SummaryState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| panic!("SummaryBufferMemory Error - state not available: {e:?}"))
+    }
+
+    /// Starts folding the oldest turns into the summary if the budget is exceeded and no
+    /// summary is pending.
+    fn fold_over_budget(&self) {
+        let mut state = self.state();
+        if state.folding > 0 {
+            return;
+        }
+        let summary_tokens = state
+            .summary
+            .as_ref()
+            .map(|summary| self.tokenizer.count_tokens(summary) as usize + TOKENS_PER_MESSAGE)
+            .unwrap_or_default();
+        let folding = oldest_turns_over_budget(
+            &state.tokens,
+            &state.messages,
+            self.max_tokens.saturating_sub(summary_tokens),
+        );
+        if folding == 0 {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            tracing::warn!(
+                "SummaryBufferMemory needs a Tokio runtime to summarize, dropping the oldest turns"
+            );
+            state.messages.drain(..folding);
+            state.tokens.drain(..folding);
+            return;
+        };
+        state.folding = folding;
+        let request = Request::new(
+            format!(
+                "Current summary:\n{}\n\nNew lines of conversation:\n{}\n\nNew summary:",
+                state.summary.as_deref().unwrap_or_default(),
+                state.messages[..folding]
+                    .iter()
+                    .map(|msg| format!("{}: {}", msg.message_type.type_string(), msg.content))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            SUMMARY_PREAMBLE.to_string(),
+        );
+        let generation = state.generation;
+        let model = self.model.clone();
+        let shared = self.state.clone();
+        runtime.spawn(async move {
+            let response = model.write().await.completion(request).await;
+            let mut state = shared.lock().unwrap_or_else(|e| e.into_inner());
+            if state.generation != generation {
+                return;
+            }
+            if let Ok(response) = response {
+                state.summary = Some(response.content());
+                state.messages.drain(..folding);
+                state.tokens.drain(..folding);
+            }
+            state.folding = 0;
+        });
+    }
+}
+
+impl<M: Completion + Send + Sync + 'static> Memory for SummaryBufferMemory<M> {
+    /// Returns the summary as a system message followed by the messages not folded yet.
+    fn messages(&self) -> Vec<Message> {
+        let state = self.state();
+        state
+            .summary
+            .iter()
+            .map(|summary| {
+                Message::new_system_message(format!(
+                    "Summary of the earlier conversation:\n{summary}"
+                ))
+            })
+            .chain(state.messages.iter().cloned())
+            .collect()
+    }
+
+    /// Adds a message, folding the oldest turns into the summary if the budget is exceeded.
+    fn add_message(&mut self, message: Message) {
+        {
+            let mut state = self.state();
+            state.tokens.push(message_tokens(&self.tokenizer, &message));
+            state.messages.push(message);
+        }
+        self.fold_over_budget();
+    }
+
+    /// Clears the summary and all messages, discarding a pending summary.
+    fn clear(&mut self) {
+        let mut state = self.state();
+        let generation = state.generation + 1;
+        *state = SummaryState {
+            generation,
+            ..Default::default()
+        };
+    }
+}
+
+/// A long-term memory which writes each conversation turn into a vector store.
+///
+/// With each new prompt, the `top_k` past turns most relevant to the prompt are retrieved
+/// from the store and returned as a system message, followed by the most recent messages.
+/// Turns are written in the background once the assistant answers, use
+/// [`VectorStoreMemory::flush`] to wait for the pending writes and get their errors.
+pub struct VectorStoreMemory {
+    store: Arc<dyn Storage>,
+    top_k: usize,
+    threshold: f32,
+    window_size: usize,
+    messages: Vec<Message>,
+    /// The user message and the answer of the current turn.
+    turn: Vec<String>,
+    writes: Vec<tokio::task::JoinHandle<Result<(), VectorStoreError>>>,
+}
+
+impl VectorStoreMemory {
+    /// Creates a new `VectorStoreMemory` retrieving the `top_k` most relevant past turns and
+    /// keeping the last `window_size` messages.
+    pub fn new(store: impl Storage + 'static, top_k: usize, window_size: usize) -> Self {
+        Self {
+            store: Arc::new(store),
+            top_k,
+            threshold: 0.5,
+            window_size,
+            messages: Vec::new(),
+            turn: Vec::new(),
+            writes: Vec::new(),
+        }
+    }
+
+    /// Sets the minimum relevance score of the retrieved turns.
+    pub fn threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Waits until all pending writes are done and returns the first error which occurred,
+    /// if any.
+    pub async fn flush(&mut self) -> Result<(), VectorStoreError> {
+        let mut result = Ok(());
+        for write in self.writes.drain(..) {
+            let write = write
+                .await
+                .map_err(|err| VectorStoreError::DatastoreError(Box::new(err)))
+                .and_then(|write| write);
+            if result.is_ok() {
+                result = write;
+            }
+        }
+        result
+    }
+
+    /// Writes the current turn into the store in the background.
+    fn write_turn(&mut self) {
+        let turn = std::mem::take(&mut self.turn).join("\n");
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            tracing::warn!("VectorStoreMemory needs a Tokio runtime to write, the turn is lost");
+            return;
+        };
+        let store = self.store.clone();
+        self.writes.retain(|write| !write.is_finished());
+        self.writes
+            .push(runtime.spawn(async move { store.save(turn).await }));
+    }
+}
+
+impl Memory for VectorStoreMemory {
+    /// Returns the most recent messages.
+    fn messages(&self) -> Vec<Message> {
+        self.messages.clone()
+    }
+
+    /// Adds a message to the recent messages, the turn is written into the store once the
+    /// assistant answers without calling tools.
+    fn add_message(&mut self, message: Message) {
+        match message.message_type {
+            MessageType::Human => {
+                self.turn = vec![format!("user: {}", message.content)];
+            }
+            MessageType::AI if message.tool_calls.is_none() && !self.turn.is_empty() => {
+                self.turn.push(format!("assistant: {}", message.content));
+                self.write_turn();
+            }
+            _ => {}
+        }
+        self.messages.push(message);
+        // Keep whole turns, so that the recent messages start with a user message.
+        let tokens = vec![1; self.messages.len()];
+        let start = oldest_turns_over_budget(&tokens, &self.messages, self.window_size);
+        self.messages.drain(..start);
+    }
+
+    /// Clears the recent messages, the turns written into the store are kept.
+    fn clear(&mut self) {
+        self.messages.clear();
+        self.turn.clear();
+    }
+
+    /// Returns the past turns relevant to the prompt as a system message, followed by the
+    /// most recent messages.
+    fn relevant_messages<'a>(&'a self, prompt: &'a str) -> BoxFuture<'a, Vec<Message>> {
+        Box::pin(async move {
+            let mut messages = Vec::with_capacity(self.messages.len() + 1);
+            let turns = match self.store.search(prompt, self.top_k, self.threshold).await {
+                Ok(turns) => turns,
+                Err(err) => {
+                    tracing::warn!("VectorStoreMemory failed to search the past turns: {err}");
+                    Vec::new()
+                }
+            };
+            let turns = turns
+                .into_iter()
+                .map(|(_, turn, _)| turn)
+                // Skip the turns which are still in the recent messages.
+                .filter(|turn| {
+                    !self.messages.iter().any(|message| {
+                        message.message_type == MessageType::Human
+                            && turn.starts_with(&format!("user: {}\n", message.content))
+                    })
+                })
+                .collect::<Vec<_>>();
+            if !turns.is_empty() {
+                messages.push(Message::new_system_message(format!(
+                    "Relevant earlier conversation:\n\n{}",
+                    turns.join("\n\n")
+                )));
+            }
+            messages.extend(self.messages.iter().cloned());
+            messages
+        })
+    }
+}
+
+/// A memory which persists the messages of a session into a JSON Lines file.
+///
+/// The messages of the session `session_id` are stored in `dir/{session_id}.jsonl`, one
+/// serialized [`Message`] per line, and loaded back when the memory is opened again. Since
+/// [`Memory::add_message`] can not fail, a failed write is kept and reported by
+/// [`JsonlMemory::flush`].
+pub struct JsonlMemory {
+    dir: PathBuf,
+    session_id: String,
+    messages: Vec<Message>,
+    error: Option<std::io::Error>,
+}
+
+impl JsonlMemory {
+    /// Opens the memory of a session, loading the messages stored by previous runs.
+    ///
+    /// The session ID may only contain ASCII alphanumeric characters, `-` and `_`.
+    pub fn open(dir: impl AsRef<Path>, session_id: impl ToString) -> std::io::Result<Self> {
+        let session_id = session_id.to_string();
+        if session_id.is_empty()
+            || !session_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid session ID: {session_id:?}"),
+            ));
+        }
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+        let mut memory = Self {
+            dir,
+            session_id,
+            messages: Vec::new(),
+            error: None,
+        };
+        match File::open(memory.path()) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    let line = line?;
+                    if !line.trim().is_empty() {
+                        memory.messages.push(serde_json::from_str(&line)?);
+                    }
+                }
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        Ok(memory)
+    }
+
+    /// Get the session ID.
+    #[inline]
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Get the path of the session file.
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(format!("{}.jsonl", self.session_id))
+    }
+
+    /// Returns the first error which occurred while writing the session file since the last
+    /// call, if any.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    fn append(&self, message: &Message) -> std::io::Result<()> {
+        let mut line = serde_json::to_string(message)?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path())?
+            .write_all(line.as_bytes())
+    }
+
+    fn keep_error(&mut self, result: std::io::Result<()>) {
+        if let Err(err) = result {
+            self.error.get_or_insert(err);
+        }
+    }
+}
+
+impl Memory for JsonlMemory {
+    /// Returns all messages of the session.
+    fn messages(&self) -> Vec<Message> {
+        self.messages.clone()
+    }
+
+    /// Adds a message to the session and appends it to the session file.
+    fn add_message(&mut self, message: Message) {
+        let result = self.append(&message);
+        self.keep_error(result);
+        self.messages.push(message);
+    }
+
+    /// Clears all messages of the session and truncates the session file.
+    fn clear(&mut self) {
+        self.messages.clear();
+        let result = File::create(self.path()).map(|_| ());
+        self.keep_error(result);
+    }
+
+    fn spec(&self) -> Option<MemorySpec> {
+        Some(MemorySpec::Jsonl {
+            dir: self.dir.clone(),
+            session_id: self.session_id.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_oldest_turns_over_budget() {
+        let messages = vec![
+            Message::new_human_message("a"),
+            Message::new_ai_message("b").with_tool_calls(json!([])),
+            Message::new_tool_message("c", "call_1"),
+            Message::new_ai_message("d"),
+            Message::new_human_message("e"),
+            Message::new_ai_message("f"),
+        ];
+        let tokens = [10, 10, 10, 10, 10, 10];
+        assert_eq!(oldest_turns_over_budget(&tokens, &messages, 60), 0);
+        // The first turn is dropped as a whole, tool results included.
+        assert_eq!(oldest_turns_over_budget(&tokens, &messages, 50), 4);
+        // The latest turn is kept even if it exceeds the budget.
+        assert_eq!(oldest_turns_over_budget(&tokens, &messages, 5), 4);
+    }
+
+    #[test]
+    fn test_window_buffer_memory_keeps_whole_turns() {
+        let mut memory = WindowBufferMemory::new(2);
+        memory.add_user_message("What is the weather?");
+        memory.add_message(Message::new_ai_message("").with_tool_calls(json!([{"id": "call_1"}])));
+        memory.add_message(Message::new_tool_message("Sunny", "call_1"));
+        memory.add_ai_message("It is sunny.");
+        // The window is smaller than the tool turn, which is kept whole.
+        let messages = memory.messages();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0].message_type, MessageType::Human);
+
+        memory.add_user_message("Thanks!");
+        memory.add_ai_message("You are welcome.");
+        let messages = memory.messages();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].content, "Thanks!");
+    }
+
+    #[test]
+    fn test_summary_buffer_memory_without_runtime() {
+        let mock = crate::mock::MockCompletion::new();
+        let tokenizer = Arc::new(Tokenizer::new_tiktoken("gpt-4").unwrap());
+        let mut memory = SummaryBufferMemory::new(mock.clone(), tokenizer, 1);
+        memory.add_user_message("Hi");
+        memory.add_ai_message("Hello!");
+        memory.add_user_message("Bye");
+
+        // The oldest turn is dropped since it cannot be summarized.
+        let messages = memory.messages();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].content, "Bye");
+        assert!(memory.summary().is_none());
+        mock.assert_calls(0);
+    }
+
+    #[test]
+    fn test_jsonl_memory_resume() {
+        let dir = std::env::temp_dir().join(format!("alith-memory-{}", uuid::Uuid::new_v4()));
+        let mut memory = JsonlMemory::open(&dir, "session-1").unwrap();
+        memory.add_user_message("What is the weather?");
+        memory.add_message(Message::new_ai_message("").with_tool_calls(json!([{"id": "call_1"}])));
+        memory.add_message(Message::new_tool_message("Sunny", "call_1"));
+        memory.flush().unwrap();
+
+        let messages = JsonlMemory::open(&dir, "session-1").unwrap().messages();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1].tool_calls, Some(json!([{"id": "call_1"}])));
+        assert_eq!(messages[2].id.as_deref(), Some("call_1"));
+        assert!(
+            JsonlMemory::open(&dir, "other")
+                .unwrap()
+                .messages()
+                .is_empty()
+        );
+        assert!(JsonlMemory::open(&dir, "../escape").is_err());
+
+        memory.clear();
+        assert!(
+            JsonlMemory::open(&dir, "session-1")
+                .unwrap()
+                .messages()
+                .is_empty()
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// A store which matches the saved values containing the query.
+    #[derive(Default)]
+    struct KeywordStorage(std::sync::Mutex<Vec<String>>);
+
+    #[async_trait::async_trait]
+    impl Storage for KeywordStorage {
+        async fn save(&self, value: String) -> Result<(), VectorStoreError> {
+            self.0.lock().unwrap().push(value);
+            Ok(())
+        }
+
+        async fn search(
+            &self,
+            query: &str,
+            limit: usize,
+            _threshold: f32,
+        ) -> crate::store::TopNResults {
+            Ok(self
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .enumerate()
+                .filter(|(_, value)| value.contains(query))
+                .take(limit)
+                .map(|(i, value)| (crate::store::DocumentId(i.to_string()), value.clone(), 1.0))
+                .collect())
+        }
+
+        async fn reset(&self) -> Result<(), VectorStoreError> {
+            self.0.lock().unwrap().clear();
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_vector_store_memory_retrieval() {
+        let mut memory = VectorStoreMemory::new(KeywordStorage::default(), 2, 2);
+        memory.add_user_message("rust");
+        memory.add_ai_message("A language");
+        memory.add_user_message("tea");
+        memory.add_ai_message("A drink");
+        memory.flush().await.unwrap();
+
+        let messages = memory.relevant_messages("rust").await;
+        assert_eq!(messages.len(), 3);
+        assert_eq!(
+            messages[0].content,
+            "Relevant earlier conversation:\n\nuser: rust\nassistant: A language"
+        );
+        assert_eq!(messages[1].content, "tea");
+        // Turns which are still recent are not repeated.
+        assert_eq!(memory.relevant_messages("tea").await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_vector_store_memory_keeps_whole_turns() {
+        let mut memory = VectorStoreMemory::new(KeywordStorage::default(), 2, 2);
+        memory.add_user_message("What is the weather?");
+        memory.add_message(Message::new_ai_message("").with_tool_calls(json!([{"id": "call_1"}])));
+        memory.add_message(Message::new_tool_message("Sunny", "call_1"));
+        memory.add_ai_message("It is sunny.");
+        // The window is smaller than the tool turn, which is kept whole.
+        let messages = memory.messages();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0].message_type, MessageType::Human);
+
+        memory.add_user_message("Thanks!");
+        let messages = memory.messages();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].content, "Thanks!");
+        memory.flush().await.unwrap();
     }
 }
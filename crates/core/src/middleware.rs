@@ -0,0 +1,42 @@
+use crate::chat::{Request, TokenUsage, ToolCall};
+use crate::executor::ToolCallTrace;
+use async_trait::async_trait;
+
+/// A model response as seen by middlewares.
+#[derive(Debug, Clone, Default)]
+pub struct ModelResponse {
+    /// The text content returned by the model.
+    pub content: String,
+    /// The tool calls requested by the model.
+    pub tool_calls: Vec<ToolCall>,
+    /// The token usage of the model call.
+    pub usage: TokenUsage,
+}
+
+/// A trait to hook into the lifecycle of an executor run, e.g. for logging, redaction
+/// or metrics.
+///
+/// Middlewares registered on an agent are run in registration order, all hooks do
+/// nothing by default.
+#[async_trait]
+pub trait Middleware: Send + Sync {
+    /// Called before each model call with the request about to be sent.
+    ///
+    /// Changes only apply to this model call, the next step starts from the original request.
+    async fn before_request(&self, _request: &mut Request) {}
+
+    /// Called after each model call with its response.
+    async fn after_response(&self, _response: &ModelResponse) {}
+
+    /// Called before a tool call is submitted for approval and run, the call may be edited.
+    ///
+    /// The edited call is the one recorded in the history and memory.
+    async fn before_tool_call(&self, _call: &mut ToolCall) {}
+
+    /// Called after a tool call ran, failed or was denied, the output sent back to the model
+    /// may be edited.
+    async fn after_tool_call(&self, _call: &mut ToolCallTrace) {}
+
+    /// Called when the run fails.
+    async fn on_error(&self, _error: &anyhow::Error) {}
+}
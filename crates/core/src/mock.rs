@@ -0,0 +1,375 @@
+pub use alith_interface::requests::completion::CompletionFinishReason;
+
+use crate::chat::{
+    CallFunction, Completion, CompletionDelta, CompletionError, CompletionStream, Request,
+    ResponseContent, ResponseTokenUsage, ResponseToolCalls, StreamingCompletion, TokenUsage,
+    ToolCall, ToolCallDelta,
+};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// A completion engine answering with scripted responses, to test agents offline.
+///
+/// Each model call consumes the next scripted response in order and fails once the script
+/// is exhausted. The received requests are recorded and can be checked with the assertion
+/// helpers. Cloning the mock returns a handle to the same script and requests, so that a
+/// test keeps a handle on the mock given to an agent.
+#[derive(Debug, Clone)]
+pub struct MockCompletion {
+    model_id: String,
+    supports_response_format: bool,
+    state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    script: VecDeque<Result<MockResponse, String>>,
+    requests: Vec<Request>,
+}
+
+/// A scripted response of a [`MockCompletion`].
+#[derive(Debug, Clone, Default)]
+pub struct MockResponse {
+    pub content: String,
+    pub tool_calls: Vec<ToolCall>,
+    pub finish_reason: Option<CompletionFinishReason>,
+    pub usage: TokenUsage,
+}
+
+impl MockResponse {
+    /// Creates a text response finished by the end of sequence token.
+    pub fn text(content: impl ToString) -> Self {
+        Self {
+            content: content.to_string(),
+            finish_reason: Some(CompletionFinishReason::Eos),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a response calling a tool with the JSON arguments.
+    pub fn tool_call(id: impl ToString, name: impl ToString, arguments: Value) -> Self {
+        Self {
+            finish_reason: Some(CompletionFinishReason::ToolsCall),
+            ..Default::default()
+        }
+        .with_tool_call(id, name, arguments)
+    }
+
+    /// Adds a call of a tool with the JSON arguments.
+    pub fn with_tool_call(
+        mut self,
+        id: impl ToString,
+        name: impl ToString,
+        arguments: Value,
+    ) -> Self {
+        self.tool_calls.push(ToolCall {
+            id: id.to_string(),
+            r#type: "function".to_string(),
+            function: CallFunction {
+                name: name.to_string(),
+                arguments: arguments.to_string(),
+            },
+        });
+        self
+    }
+
+    /// Sets the reason the completion finished.
+    pub fn finish_reason(mut self, finish_reason: CompletionFinishReason) -> Self {
+        self.finish_reason = Some(finish_reason);
+        self
+    }
+
+    /// Sets the prompt and completion token usage.
+    pub fn usage(mut self, prompt_tokens: u32, completion_tokens: u32) -> Self {
+        self.usage = TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+            ..Default::default()
+        };
+        self
+    }
+}
+
+impl From<&str> for MockResponse {
+    fn from(content: &str) -> Self {
+        Self::text(content)
+    }
+}
+
+impl From<String> for MockResponse {
+    fn from(content: String) -> Self {
+        Self::text(content)
+    }
+}
+
+impl Default for MockCompletion {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockCompletion {
+    /// Creates a mock with an empty script.
+    pub fn new() -> Self {
+        Self {
+            model_id: "mock".to_string(),
+            supports_response_format: false,
+            state: Arc::new(Mutex::new(MockState::default())),
+        }
+    }
+
+    /// Creates a mock with an empty script whose usage is attributed to `model_id`.
+    pub fn with_model_id(model_id: impl ToString) -> Self {
+        Self {
+            model_id: model_id.to_string(),
+            ..Self::new()
+        }
+    }
+
+    /// Appends a response to the script.
+    pub fn respond(self, response: impl Into<MockResponse>) -> Self {
+        self.push(Ok(response.into()));
+        self
+    }
+
+    /// Appends a failed model call to the script.
+    pub fn fail(self, message: impl ToString) -> Self {
+        self.push(Err(message.to_string()));
+        self
+    }
+
+    /// Sets whether the mock claims to honor [`Request::response_format`].
+    pub fn response_format(mut self, supports_response_format: bool) -> Self {
+        self.supports_response_format = supports_response_format;
+        self
+    }
+
+    /// Returns the requests received so far.
+    pub fn requests(&self) -> Vec<Request> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Returns the request of the model call `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the model was called fewer than `index + 1` times.
+    #[track_caller]
+    pub fn request(&self, index: usize) -> Request {
+        let requests = self.requests();
+        match requests.get(index) {
+            Some(request) => request.clone(),
+            None => panic!(
+                "MockCompletion received {} requests, no request {index}",
+                requests.len()
+            ),
+        }
+    }
+
+    /// Returns the number of model calls.
+    pub fn calls(&self) -> usize {
+        self.state.lock().unwrap().requests.len()
+    }
+
+    /// Returns the number of scripted responses not consumed yet.
+    pub fn remaining(&self) -> usize {
+        self.state.lock().unwrap().script.len()
+    }
+
+    /// Asserts the number of model calls.
+    #[track_caller]
+    pub fn assert_calls(&self, calls: usize) {
+        assert_eq!(self.calls(), calls, "unexpected number of model calls");
+    }
+
+    /// Asserts that the whole script was consumed.
+    #[track_caller]
+    pub fn assert_done(&self) {
+        assert_eq!(self.remaining(), 0, "scripted responses left");
+    }
+
+    /// Asserts the preamble of the request of the model call `index`.
+    #[track_caller]
+    pub fn assert_preamble(&self, index: usize, preamble: &str) {
+        assert_eq!(self.request(index).preamble, preamble);
+    }
+
+    /// Asserts that the prompt of the request of the model call `index` contains `text`.
+    #[track_caller]
+    pub fn assert_prompt_contains(&self, index: usize, text: &str) {
+        let prompt = self.request(index).effective_prompt();
+        assert!(
+            prompt.contains(text),
+            "{prompt:?} does not contain {text:?}"
+        );
+    }
+
+    /// Asserts the roles and contents of the history of the request of the model call
+    /// `index`.
+    #[track_caller]
+    pub fn assert_history(&self, index: usize, history: &[(&str, &str)]) {
+        let request = self.request(index);
+        let actual: Vec<(&str, &str)> = request
+            .history
+            .iter()
+            .map(|message| (message.role.as_str(), message.content.as_str()))
+            .collect();
+        assert_eq!(actual, history);
+    }
+
+    /// Asserts the names of the tools offered in the request of the model call `index`.
+    #[track_caller]
+    pub fn assert_tools(&self, index: usize, names: &[&str]) {
+        let request = self.request(index);
+        let actual: Vec<&str> = request
+            .tools
+            .iter()
+            .map(|tool| tool.name.as_str())
+            .collect();
+        assert_eq!(actual, names);
+    }
+
+    /// Asserts the IDs of the documents attached to the request of the model call `index`.
+    #[track_caller]
+    pub fn assert_documents(&self, index: usize, ids: &[&str]) {
+        let request = self.request(index);
+        let actual: Vec<&str> = request
+            .documents
+            .iter()
+            .map(|doc| doc.id.0.as_str())
+            .collect();
+        assert_eq!(actual, ids);
+    }
+
+    fn push(&self, response: Result<MockResponse, String>) {
+        self.state.lock().unwrap().script.push_back(response);
+    }
+
+    /// Records the request and returns the next scripted response.
+    fn next(&self, request: Request) -> Result<MockResponse, CompletionError> {
+        let mut state = self.state.lock().unwrap();
+        state.requests.push(request);
+        match state.script.pop_front() {
+            Some(response) => response.map_err(CompletionError::Normal),
+            None => Err(CompletionError::Normal(format!(
+                "MockCompletion has no scripted response left for call {}",
+                state.requests.len()
+            ))),
+        }
+    }
+}
+
+impl Completion for MockCompletion {
+    type Response = MockResponse;
+
+    async fn completion(&mut self, request: Request) -> Result<Self::Response, CompletionError> {
+        self.next(request)
+    }
+
+    fn supports_response_format(&self) -> bool {
+        self.supports_response_format
+    }
+
+    fn model_id(&self) -> String {
+        self.model_id.clone()
+    }
+}
+
+impl StreamingCompletion for MockCompletion {
+    /// Streams the scripted response as a content delta, one delta per tool call, and a
+    /// last delta with the finish reason and the token usage.
+    async fn completion_stream(
+        &mut self,
+        request: Request,
+    ) -> Result<CompletionStream, CompletionError> {
+        let response = self.next(request)?;
+        let mut deltas = Vec::new();
+        if !response.content.is_empty() {
+            deltas.push(CompletionDelta {
+                content: Some(response.content),
+                ..Default::default()
+            });
+        }
+        for (index, call) in response.tool_calls.into_iter().enumerate() {
+            deltas.push(CompletionDelta {
+                tool_calls: vec![ToolCallDelta {
+                    index,
+                    id: Some(call.id),
+                    name: Some(call.function.name),
+                    arguments: call.function.arguments,
+                }],
+                ..Default::default()
+            });
+        }
+        deltas.push(CompletionDelta {
+            finish_reason: response.finish_reason,
+            token_usage: Some(response.usage),
+            ..Default::default()
+        });
+        Ok(Box::pin(futures::stream::iter(deltas.into_iter().map(Ok))))
+    }
+}
+
+impl ResponseContent for MockResponse {
+    fn content(&self) -> String {
+        self.content.clone()
+    }
+}
+
+impl ResponseToolCalls for MockResponse {
+    fn toolcalls(&self) -> Vec<ToolCall> {
+        self.tool_calls.clone()
+    }
+}
+
+impl ResponseTokenUsage for MockResponse {
+    fn token_usage(&self) -> TokenUsage {
+        self.usage.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_mock_completion_script() {
+        let mut mock = MockCompletion::new()
+            .respond("Hello")
+            .fail("overloaded")
+            .respond(MockResponse::tool_call(
+                "call_1",
+                "search",
+                serde_json::json!({"q": "rust"}),
+            ));
+        let handle = mock.clone();
+
+        let response = mock
+            .completion(Request::new("Hi".to_string(), "Be brief.".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(response.content(), "Hello");
+        assert!(mock.completion(Request::default()).await.is_err());
+
+        let deltas: Vec<_> = mock
+            .completion_stream(Request::default())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(deltas.len(), 2);
+        assert_eq!(
+            deltas[0].as_ref().unwrap().tool_calls[0].name.as_deref(),
+            Some("search")
+        );
+        assert!(mock.completion(Request::default()).await.is_err());
+
+        handle.assert_calls(4);
+        handle.assert_done();
+        handle.assert_preamble(0, "Be brief.");
+        handle.assert_prompt_contains(0, "Hi");
+    }
+}
@@ -0,0 +1,391 @@
+use crate::chat::{
+    CallFunction, Completion, CompletionError, Message, Request, ResponseContent,
+    ResponseTokenUsage, ResponseToolCalls, TokenUsage, ToolCall, ToolDefinition,
+};
+use crate::json::parse_json_markdown;
+use serde_json::Value;
+use uuid::Uuid;
+
+const ACTION: &str = "Action:";
+const ACTION_INPUT: &str = "Action Input:";
+const OBSERVATION: &str = "Observation:";
+const FINAL_ANSWER: &str = "Final Answer:";
+
+/// A completion engine calling tools through its text output, for models without native
+/// function calling.
+///
+/// The tools of the request are described in the preamble and the model is asked to answer
+/// in the ReAct format, with `Action` and `Action Input` lines or a JSON tool block. The
+/// tool calls are parsed out of the output, and the tool calls and results of the history
+/// are rendered back as text, so that the same agent code works on local models.
+pub struct ReActCompletion<M: Completion> {
+    model: M,
+}
+
+/// The response of a [`ReActCompletion`], with the tool calls parsed out of the output.
+#[derive(Debug, Clone, Default)]
+pub struct ReActResponse {
+    pub content: String,
+    pub tool_calls: Vec<ToolCall>,
+    pub usage: TokenUsage,
+}
+
+impl<M: Completion> ReActCompletion<M> {
+    /// Wraps a model without native function calling.
+    pub fn new(model: M) -> Self {
+        Self { model }
+    }
+
+    /// The wrapped model.
+    #[inline]
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+}
+
+impl<M> Completion for ReActCompletion<M>
+where
+    M: Completion + Send + Sync,
+    M::Response: Send,
+{
+    type Response = ReActResponse;
+
+    async fn completion(
+        &mut self,
+        mut request: Request,
+    ) -> Result<Self::Response, CompletionError> {
+        let tools = std::mem::take(&mut request.tools);
+        if !tools.is_empty() {
+            request.preamble = format!("{}\n\n{}", request.preamble, tools_prompt(&tools))
+                .trim_start()
+                .to_string();
+        }
+        request.history = render_history(std::mem::take(&mut request.history));
+        let response = self.model.completion(request).await?;
+        let usage = response.token_usage();
+        let mut content = response.content();
+        let mut tool_calls = response.toolcalls();
+        if tool_calls.is_empty() && !tools.is_empty() {
+            (content, tool_calls) = parse_tool_calls(&content, &tools);
+        }
+        Ok(ReActResponse {
+            content,
+            tool_calls,
+            usage,
+        })
+    }
+
+    fn supports_response_format(&self) -> bool {
+        self.model.supports_response_format()
+    }
+
+    fn model_id(&self) -> String {
+        self.model.model_id()
+    }
+}
+
+/// Describes the tools and the answer format in the preamble.
+fn tools_prompt(tools: &[ToolDefinition]) -> String {
+    let mut prompt = String::from("You have access to the following tools:\n\n");
+    for tool in tools {
+        prompt.push_str(&format!(
+            "- {}: {}\n  Arguments JSON schema: {}\n",
+            tool.name, tool.description, tool.parameters
+        ));
+    }
+    let names: Vec<&str> = tools.iter().map(|tool| tool.name.as_str()).collect();
+    prompt.push_str(&format!(
+        "\nTo use a tool, answer with the following format and stop:\n\
+         Thought: what you need to do next\n\
+         {ACTION} the tool to use, one of [{}]\n\
+         {ACTION_INPUT} the JSON arguments of the tool\n\n\
+         The result of the tool is then given to you as `{OBSERVATION} ...`. When you can \
+         answer without any tool, answer with:\n\
+         {FINAL_ANSWER} your answer",
+        names.join(", ")
+    ));
+    prompt
+}
+
+/// Renders the tool calls and results of the history as text messages.
+///
+/// The results of the calls of one step are merged into one user message, since user
+/// messages cannot follow each other.
+fn render_history(history: Vec<Message>) -> Vec<Message> {
+    let mut messages: Vec<Message> = Vec::with_capacity(history.len());
+    let mut observing = false;
+    for message in history {
+        let is_observation = message.tool_call_id.is_some() || message.role == "tool";
+        if !message.tool_calls.is_empty() {
+            let mut content = message.content;
+            for call in &message.tool_calls {
+                content.push_str(&format!(
+                    "\n{ACTION} {}\n{ACTION_INPUT} {}",
+                    call.function.name, call.function.arguments
+                ));
+            }
+            messages.push(Message::assistant(content.trim_start()));
+        } else if is_observation && observing {
+            if let Some(last) = messages.last_mut() {
+                last.content
+                    .push_str(&format!("\n{OBSERVATION} {}", message.content));
+            }
+        } else if is_observation {
+            messages.push(Message::user(format!("{OBSERVATION} {}", message.content)));
+        } else {
+            messages.push(message);
+        }
+        observing = is_observation;
+    }
+    messages
+}
+
+/// Parses the tool calls out of a model output, and returns the remaining content.
+///
+/// `Action`/`Action Input` pairs are parsed first, then a JSON tool block naming one of the
+/// tools. A `Final Answer` before any action is returned as the content.
+fn parse_tool_calls(output: &str, tools: &[ToolDefinition]) -> (String, Vec<ToolCall>) {
+    // The model may go on with an observation of its own, which is discarded.
+    let output = match output.find(OBSERVATION) {
+        Some(end) => &output[..end],
+        None => output,
+    };
+    let action = output.find(ACTION);
+    if let Some(start) = output.find(FINAL_ANSWER) {
+        if action.is_none_or(|action| start < action) {
+            let answer = output[start + FINAL_ANSWER.len()..].trim();
+            return (answer.to_string(), Vec::new());
+        }
+    }
+    if let Some(action) = action {
+        let calls: Vec<ToolCall> = output[action..]
+            .split(ACTION)
+            .filter_map(parse_action)
+            .collect();
+        if !calls.is_empty() {
+            return (output[..action].trim().to_string(), calls);
+        }
+    }
+    if let Ok(block) = parse_json_markdown(output) {
+        if let Some(call) = parse_tool_block(&block, tools) {
+            return (String::new(), vec![call]);
+        }
+    }
+    (output.trim().to_string(), Vec::new())
+}
+
+/// Parses the tool name and the input following an `Action:` marker.
+fn parse_action(action: &str) -> Option<ToolCall> {
+    let (name, input) = match action.find(ACTION_INPUT) {
+        Some(input) => (&action[..input], &action[input + ACTION_INPUT.len()..]),
+        None => (action, ""),
+    };
+    let name = name.trim().trim_matches(['`', '[', ']', '"']).trim();
+    if name.is_empty() {
+        return None;
+    }
+    let input = input.trim();
+    let arguments = if input.is_empty() {
+        "{}".to_string()
+    } else {
+        // Invalid inputs are sent as is, the tool error is then reported to the model.
+        parse_json_markdown(input)
+            .map(|value| value.to_string())
+            .unwrap_or_else(|_| input.to_string())
+    };
+    Some(tool_call(name, arguments))
+}
+
+/// Parses a JSON tool block such as `{"name": "search", "arguments": {"query": "rust"}}`.
+fn parse_tool_block(block: &Value, tools: &[ToolDefinition]) -> Option<ToolCall> {
+    let name = ["name", "tool", "action"]
+        .iter()
+        .find_map(|key| block.get(key)?.as_str())?;
+    if !tools.iter().any(|tool| tool.name == name) {
+        return None;
+    }
+    let arguments = ["arguments", "parameters", "action_input", "input"]
+        .iter()
+        .find_map(|key| block.get(key))
+        .map(|arguments| match arguments {
+            // Some models encode the arguments as a JSON string.
+            Value::String(arguments) => arguments.clone(),
+            arguments => arguments.to_string(),
+        })
+        .unwrap_or_else(|| "{}".to_string());
+    Some(tool_call(name, arguments))
+}
+
+fn tool_call(name: &str, arguments: String) -> ToolCall {
+    ToolCall {
+        id: format!("call_{}", Uuid::new_v4().simple()),
+        r#type: "function".to_string(),
+        function: CallFunction {
+            name: name.to_string(),
+            arguments,
+        },
+    }
+}
+
+impl ResponseContent for ReActResponse {
+    fn content(&self) -> String {
+        self.content.clone()
+    }
+}
+
+impl ResponseToolCalls for ReActResponse {
+    fn toolcalls(&self) -> Vec<ToolCall> {
+        self.tool_calls.clone()
+    }
+}
+
+impl ResponseTokenUsage for ReActResponse {
+    fn token_usage(&self) -> TokenUsage {
+        self.usage.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::Agent;
+    use crate::llm::client::{Client, ClientConfig};
+    use crate::mock::MockCompletion;
+    use crate::tool::{StructureTool, ToolError};
+    use async_trait::async_trait;
+    use schemars::JsonSchema;
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    fn search_tool() -> ToolDefinition {
+        ToolDefinition {
+            name: "search".to_string(),
+            description: "Searches the web.".to_string(),
+            parameters: json!({"type": "object", "properties": {"query": {"type": "string"}}}),
+        }
+    }
+
+    #[test]
+    fn test_parse_tool_calls() {
+        let tools = [search_tool()];
+        let (content, calls) = parse_tool_calls(
+            "Thought: I need to search.\nAction: search\nAction Input: {\"query\": \"rust\"}\nObservation: made up",
+            &tools,
+        );
+        assert_eq!(content, "Thought: I need to search.");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].function.name, "search");
+        assert_eq!(calls[0].function.arguments, r#"{"query":"rust"}"#);
+
+        let block = "```json\n{\"name\": \"search\", \"arguments\": {\"query\": \"rust\"}}\n```";
+        let (_, calls) = parse_tool_calls(block, &tools);
+        assert_eq!(calls[0].function.arguments, r#"{"query":"rust"}"#);
+
+        let (content, calls) = parse_tool_calls("Thought: done.\nFinal Answer: 42", &tools);
+        assert_eq!(content, "42");
+        assert!(calls.is_empty());
+
+        let (content, calls) = parse_tool_calls(r#"{"query": "not a call"}"#, &tools);
+        assert_eq!(content, r#"{"query": "not a call"}"#);
+        assert!(calls.is_empty());
+    }
+
+    struct Search;
+
+    #[derive(JsonSchema, Serialize, Deserialize)]
+    struct SearchInput {
+        query: String,
+    }
+
+    #[async_trait]
+    impl StructureTool for Search {
+        type Input = SearchInput;
+        type Output = String;
+
+        fn name(&self) -> &str {
+            "search"
+        }
+
+        async fn run_with_args(&self, input: Self::Input) -> Result<Self::Output, ToolError> {
+            Ok(format!("Results for {}", input.query))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_react_agent_two_actions() {
+        let mock = MockCompletion::new()
+            .respond(
+                "Action: search\nAction Input: {\"query\": \"rust\"}\n\
+                 Action: search\nAction Input: {\"query\": \"go\"}",
+            )
+            .respond("Final Answer: Both are languages.");
+        let agent = Agent::new("react", ReActCompletion::new(mock.clone()))
+            .tool(Search)
+            .await;
+
+        let output = agent
+            .prompt_with_trace("What are Rust and Go?")
+            .await
+            .unwrap();
+        assert_eq!(output.output, "Both are languages.");
+        mock.assert_history(
+            1,
+            &[
+                ("user", "What are Rust and Go?"),
+                (
+                    "assistant",
+                    "Action: search\nAction Input: {\"query\":\"rust\"}\n\
+                     Action: search\nAction Input: {\"query\":\"go\"}",
+                ),
+                (
+                    "user",
+                    "Observation: \"Results for rust\"\nObservation: \"Results for go\"",
+                ),
+            ],
+        );
+
+        // The rendered history is a valid prompt for the API clients.
+        let client = Client::openai_compatible_client(
+            "sk-test",
+            "http://localhost:8080/v1",
+            "gpt-4",
+            ClientConfig::default(),
+        )
+        .unwrap();
+        let mut completion = client.build_completion(&mock.request(1)).unwrap();
+        let roles: Vec<String> = completion
+            .prompt()
+            .get_built_prompt_messages()
+            .unwrap()
+            .into_iter()
+            .map(|message| message["role"].clone())
+            .collect();
+        assert_eq!(roles, ["system", "user", "assistant", "user"]);
+    }
+
+    #[tokio::test]
+    async fn test_react_agent() {
+        let mock = MockCompletion::new()
+            .respond("Action: search\nAction Input: {\"query\": \"rust\"}")
+            .respond("Final Answer: Rust is a language.");
+        let agent = Agent::new("react", ReActCompletion::new(mock.clone()))
+            .tool(Search)
+            .await;
+
+        let output = agent.prompt_with_trace("What is Rust?").await.unwrap();
+        assert_eq!(output.output, "Rust is a language.");
+        mock.assert_tools(0, &[]);
+        assert!(mock.request(0).preamble.contains("- search: "));
+        mock.assert_history(
+            1,
+            &[
+                ("user", "What is Rust?"),
+                (
+                    "assistant",
+                    "Action: search\nAction Input: {\"query\":\"rust\"}",
+                ),
+                ("user", "Observation: \"Results for rust\""),
+            ],
+        );
+    }
+}
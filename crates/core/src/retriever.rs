@@ -0,0 +1,383 @@
+use crate::chat::Document;
+use crate::embeddings::{Embeddings, EmbeddingsData, EmbeddingsError};
+use crate::store::{Storage, VectorStoreError};
+use async_trait::async_trait;
+use futures::future::try_join_all;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// The default number of documents retrieved from a store index for each prompt.
+pub const DEFAULT_RETRIEVER_TOP_K: usize = 4;
+
+/// The default minimum similarity score of the retrieved documents.
+pub const DEFAULT_RETRIEVER_THRESHOLD: f32 = 0.5;
+
+/// A retrieved document with its similarity score.
+#[derive(Debug, Clone)]
+pub struct ScoredDocument {
+    pub document: Document,
+    pub score: f32,
+}
+
+/// Retrieves the documents relevant to a query from one store index.
+pub struct Retriever {
+    name: Option<String>,
+    store: Box<dyn Storage>,
+    top_k: usize,
+    threshold: f32,
+}
+
+impl Retriever {
+    /// Creates a new `Retriever` over a store index.
+    pub fn new(store: impl Storage + 'static) -> Self {
+        Self {
+            name: None,
+            store: Box::new(store),
+            top_k: DEFAULT_RETRIEVER_TOP_K,
+            threshold: DEFAULT_RETRIEVER_THRESHOLD,
+        }
+    }
+
+    /// Sets the name of the index, added to the metadata of the retrieved documents.
+    pub fn name(mut self, name: impl ToString) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Sets the maximum number of documents retrieved for each query.
+    pub fn top_k(mut self, top_k: usize) -> Self {
+        self.top_k = top_k;
+        self
+    }
+
+    /// Sets the minimum similarity score of the retrieved documents.
+    pub fn threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Retrieves the documents relevant to the query, with the index name and the score
+    /// in their metadata.
+    pub async fn retrieve(&self, query: &str) -> Result<Vec<ScoredDocument>, VectorStoreError> {
+        Ok(self
+            .store
+            .search(query, self.top_k, self.threshold)
+            .await?
+            .into_iter()
+            .map(|(id, text, score)| {
+                let mut additional_props =
+                    HashMap::from([("score".to_string(), score.to_string())]);
+                if let Some(name) = &self.name {
+                    additional_props.insert("index".to_string(), name.clone());
+                }
+                ScoredDocument {
+                    document: Document {
+                        id,
+                        text,
+                        additional_props,
+                    },
+                    score,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Reorders the retrieved documents, e.g. with a cross-encoder model.
+#[async_trait]
+pub trait Reranker: Send + Sync {
+    /// Returns the documents ordered from the most to the least relevant to the query.
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: Vec<ScoredDocument>,
+    ) -> Result<Vec<ScoredDocument>, VectorStoreError>;
+}
+
+/// Retrieves documents from several store indices and merges them by score.
+///
+/// The merged documents are deduplicated by text, then optionally diversified with maximal
+/// marginal relevance (MMR) and reranked, before keeping the `top_k` best ones.
+#[derive(Default)]
+pub struct RetrievalPipeline {
+    retrievers: Vec<Retriever>,
+    top_k: Option<usize>,
+    mmr: Option<Mmr>,
+    reranker: Option<Arc<dyn Reranker>>,
+}
+
+struct Mmr {
+    embeddings: Box<dyn DynEmbeddings>,
+    lambda: f32,
+}
+
+impl RetrievalPipeline {
+    /// Creates an empty pipeline.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a retriever over a store index.
+    pub fn retriever(mut self, retriever: Retriever) -> Self {
+        self.retrievers.push(retriever);
+        self
+    }
+
+    /// Sets the maximum number of documents kept after merging, all are kept by default.
+    pub fn top_k(mut self, top_k: usize) -> Self {
+        self.top_k = Some(top_k);
+        self
+    }
+
+    /// Diversifies the merged documents with maximal marginal relevance, `lambda` trades
+    /// relevance (1.0) against diversity (0.0).
+    pub fn mmr(mut self, embeddings: impl Embeddings + 'static, lambda: f32) -> Self {
+        self.mmr = Some(Mmr {
+            embeddings: Box::new(embeddings),
+            lambda,
+        });
+        self
+    }
+
+    /// Reranks the merged documents before keeping the `top_k` best ones.
+    pub fn reranker(mut self, reranker: impl Reranker + 'static) -> Self {
+        self.reranker = Some(Arc::new(reranker));
+        self
+    }
+
+    /// Returns `true` if the pipeline has no retriever.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.retrievers.is_empty()
+    }
+
+    /// Retrieves the documents relevant to the query from all indices.
+    pub async fn retrieve(&self, query: &str) -> Result<Vec<Document>, VectorStoreError> {
+        if self.retrievers.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut documents: Vec<ScoredDocument> = try_join_all(
+            self.retrievers
+                .iter()
+                .map(|retriever| retriever.retrieve(query)),
+        )
+        .await?
+        .into_iter()
+        .flatten()
+        .collect();
+        documents.sort_by(|a, b| b.score.total_cmp(&a.score));
+        let mut texts = HashSet::new();
+        documents.retain(|document| texts.insert(document.document.text.clone()));
+        if let Some(mmr) = &self.mmr {
+            documents = mmr.diversify(query, documents, self.top_k).await?;
+        }
+        if let Some(reranker) = &self.reranker {
+            documents = reranker.rerank(query, documents).await?;
+        }
+        if let Some(top_k) = self.top_k {
+            documents.truncate(top_k);
+        }
+        Ok(documents
+            .into_iter()
+            .map(|document| document.document)
+            .collect())
+    }
+}
+
+impl Mmr {
+    /// Greedily selects the documents maximizing
+    /// `lambda * sim(query, doc) - (1 - lambda) * max(sim(doc, selected))`.
+    async fn diversify(
+        &self,
+        query: &str,
+        documents: Vec<ScoredDocument>,
+        top_k: Option<usize>,
+    ) -> Result<Vec<ScoredDocument>, VectorStoreError> {
+        let mut input = vec![query.to_string()];
+        input.extend(
+            documents
+                .iter()
+                .map(|document| document.document.text.clone()),
+        );
+        let embeddings = self.embeddings.embed(input).await?;
+        let Some((query, candidates)) = embeddings.split_first() else {
+            return Ok(documents);
+        };
+        let relevance: Vec<f64> = candidates
+            .iter()
+            .map(|candidate| cosine_similarity(&query.vec, &candidate.vec))
+            .collect();
+        let mut remaining: Vec<usize> = (0..documents.len().min(candidates.len())).collect();
+        let mut selected: Vec<usize> = Vec::new();
+        let k = top_k.unwrap_or(remaining.len());
+        while selected.len() < k && !remaining.is_empty() {
+            let (position, _) = remaining
+                .iter()
+                .map(|&i| {
+                    let redundancy = selected
+                        .iter()
+                        .map(|&j| cosine_similarity(&candidates[i].vec, &candidates[j].vec))
+                        .fold(0.0, f64::max);
+                    self.lambda as f64 * relevance[i] - (1.0 - self.lambda as f64) * redundancy
+                })
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .expect("remaining candidates are not empty");
+            selected.push(remaining.remove(position));
+        }
+        let mut documents: Vec<Option<ScoredDocument>> = documents.into_iter().map(Some).collect();
+        Ok(selected
+            .into_iter()
+            .filter_map(|i| documents[i].take())
+            .collect())
+    }
+}
+
+fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let norm =
+        a.iter().map(|a| a * a).sum::<f64>().sqrt() * b.iter().map(|b| b * b).sum::<f64>().sqrt();
+    if norm == 0.0 { 0.0 } else { dot / norm }
+}
+
+/// An object safe view of [`Embeddings`].
+#[async_trait]
+trait DynEmbeddings: Send + Sync {
+    async fn embed(&self, input: Vec<String>) -> Result<Vec<EmbeddingsData>, EmbeddingsError>;
+}
+
+#[async_trait]
+impl<E: Embeddings> DynEmbeddings for E {
+    async fn embed(&self, input: Vec<String>) -> Result<Vec<EmbeddingsData>, EmbeddingsError> {
+        self.embed_texts(input).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{DocumentId, TopNResults};
+
+    /// A store returning fixed documents, scored by their position.
+    struct FixedStorage(Vec<&'static str>);
+
+    #[async_trait]
+    impl Storage for FixedStorage {
+        async fn save(&self, _value: String) -> Result<(), VectorStoreError> {
+            Ok(())
+        }
+
+        async fn search(&self, _query: &str, limit: usize, threshold: f32) -> TopNResults {
+            Ok(self
+                .0
+                .iter()
+                .enumerate()
+                .map(|(i, text)| {
+                    (
+                        DocumentId(i.to_string()),
+                        text.to_string(),
+                        0.9 - i as f32 * 0.2,
+                    )
+                })
+                .filter(|(_, _, score)| *score >= threshold)
+                .take(limit)
+                .collect())
+        }
+
+        async fn reset(&self) -> Result<(), VectorStoreError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retrieval_pipeline_merges_by_score() {
+        let pipeline = RetrievalPipeline::new()
+            .retriever(Retriever::new(FixedStorage(vec!["a", "b", "c"])).name("first"))
+            .retriever(
+                Retriever::new(FixedStorage(vec!["b", "d"]))
+                    .name("second")
+                    .threshold(0.0),
+            )
+            .top_k(3);
+        let documents = pipeline.retrieve("query").await.unwrap();
+        let texts: Vec<&str> = documents.iter().map(|d| d.text.as_str()).collect();
+        // `b` is kept once, from the index where it scored best.
+        assert_eq!(texts, vec!["a", "b", "d"]);
+        assert_eq!(documents[1].additional_props["index"], "second");
+    }
+
+    /// Embeds the query and the fruits as fixed two dimensional vectors.
+    #[derive(Clone)]
+    struct FruitEmbeddings;
+
+    #[async_trait]
+    impl Embeddings for FruitEmbeddings {
+        async fn embed_texts(
+            &self,
+            input: Vec<String>,
+        ) -> Result<Vec<EmbeddingsData>, EmbeddingsError> {
+            Ok(input
+                .into_iter()
+                .map(|document| {
+                    let vec = match document.as_str() {
+                        "apricot" => vec![0.9, 0.1],
+                        "banana" => vec![0.6, 0.8],
+                        _ => vec![1.0, 0.0],
+                    };
+                    EmbeddingsData { document, vec }
+                })
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retrieval_pipeline_mmr() {
+        let storage = || FixedStorage(vec!["apple", "apricot", "banana"]);
+        let pipeline = RetrievalPipeline::new()
+            .retriever(Retriever::new(storage()).threshold(0.0))
+            .top_k(2);
+        let documents = pipeline.retrieve("query").await.unwrap();
+        let texts: Vec<&str> = documents.iter().map(|d| d.text.as_str()).collect();
+        assert_eq!(texts, vec!["apple", "apricot"]);
+
+        // `apricot` is almost a duplicate of `apple`, the less relevant `banana` is kept.
+        let pipeline = RetrievalPipeline::new()
+            .retriever(Retriever::new(storage()).threshold(0.0))
+            .mmr(FruitEmbeddings, 0.3)
+            .top_k(2);
+        let documents = pipeline.retrieve("query").await.unwrap();
+        let texts: Vec<&str> = documents.iter().map(|d| d.text.as_str()).collect();
+        assert_eq!(texts, vec!["apple", "banana"]);
+    }
+
+    /// Reverses the documents, recording the query.
+    #[derive(Default)]
+    struct ReverseReranker(std::sync::Mutex<Vec<String>>);
+
+    #[async_trait]
+    impl Reranker for Arc<ReverseReranker> {
+        async fn rerank(
+            &self,
+            query: &str,
+            mut documents: Vec<ScoredDocument>,
+        ) -> Result<Vec<ScoredDocument>, VectorStoreError> {
+            self.0.lock().unwrap().push(query.to_string());
+            documents.reverse();
+            Ok(documents)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retrieval_pipeline_reranker() {
+        let reranker = Arc::new(ReverseReranker::default());
+        let pipeline = RetrievalPipeline::new()
+            .retriever(Retriever::new(FixedStorage(vec!["a", "b", "c"])).threshold(0.0))
+            .reranker(reranker.clone())
+            .top_k(2);
+        let documents = pipeline.retrieve("query").await.unwrap();
+        let texts: Vec<&str> = documents.iter().map(|d| d.text.as_str()).collect();
+        // The documents are reranked before keeping the `top_k` best ones.
+        assert_eq!(texts, vec!["c", "b"]);
+        assert_eq!(*reranker.0.lock().unwrap(), vec!["query".to_string()]);
+    }
+}
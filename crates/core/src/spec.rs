@@ -0,0 +1,451 @@
+use crate::agent::Agent;
+use crate::chat::Completion;
+use crate::executor::{DEFAULT_MAX_STEPS, RunTrace};
+use crate::llm::LLM;
+use crate::mcp::{MCPError, MCPServerConfig};
+use crate::memory::{JsonlMemory, Memory, WindowBufferMemory};
+use crate::retriever::Retriever;
+use crate::store::{Storage, TopNResults, VectorStoreError};
+use crate::tool::{RetryPolicy, Tool, ToolDefinition, ToolError, ToolRegistryError, ToolSource};
+use crate::{Ref, make_ref};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A declarative agent definition which can be loaded from TOML or JSON files.
+///
+/// Tools and store indices are referenced by name and resolved through a [`SpecRegistry`]
+/// when the spec is built into an [`Agent`].
+///
+/// ```toml
+/// name = "assistant"
+/// model = "gpt-4"
+/// preamble = "You are a helpful assistant."
+/// temperature = 0.7
+/// tools = ["search"]
+///
+/// [memory]
+/// type = "window_buffer"
+/// window_size = 10
+///
+/// [mcp_servers.filesystem]
+/// command = "npx"
+/// args = ["-y", "@modelcontextprotocol/server-filesystem", "."]
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentSpec {
+    /// The name of the agent.
+    pub name: String,
+    /// The model to use.
+    pub model: ModelSpec,
+    /// System prompt for the agent.
+    #[serde(default)]
+    pub preamble: String,
+    /// Temperature of the model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Maximum number of tokens for the completion.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<usize>,
+    /// Maximum number of model calls in one prompt.
+    #[serde(default = "default_max_steps")]
+    pub max_steps: usize,
+    /// The registry names of the tools to use.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<String>,
+    /// The qualified names of the local or MCP tools which are not offered to the model.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disabled_tools: Vec<String>,
+    /// The MCP servers to start, keyed by the server name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub mcp_servers: BTreeMap<String, MCPServerConfig>,
+    /// Agent memory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<MemorySpec>,
+    /// Indexed storages for the agent.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub store_indices: Vec<StoreIndexSpec>,
+}
+
+fn default_max_steps() -> usize {
+    DEFAULT_MAX_STEPS
+}
+
+/// The model of an agent spec, either a model name or an OpenAI compatible endpoint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ModelSpec {
+    /// A model name such as `gpt-4` or `claude-3-5-sonnet`, the API key is read from the
+    /// environment variable of the provider.
+    Name(String),
+    /// A model served by an OpenAI compatible endpoint.
+    Endpoint {
+        model: String,
+        base_url: String,
+        /// The environment variable holding the API key, no key is sent when it is not set.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        api_key_env: Option<String>,
+    },
+}
+
+impl ModelSpec {
+    /// Create the model described by the spec.
+    pub fn build(&self) -> Result<LLM, SpecError> {
+        match self {
+            ModelSpec::Name(model) => {
+                LLM::from_model_name(model).map_err(|err| SpecError::ModelError(err.to_string()))
+            }
+            ModelSpec::Endpoint {
+                model,
+                base_url,
+                api_key_env,
+            } => {
+                let api_key = match api_key_env {
+                    Some(env) => {
+                        std::env::var(env).map_err(|_| SpecError::MissingApiKey(env.to_string()))?
+                    }
+                    None => String::new(),
+                };
+                LLM::openai_compatible_model(&api_key, base_url, model)
+                    .map_err(|err| SpecError::ModelError(err.to_string()))
+            }
+        }
+    }
+}
+
+/// The memory of an agent spec.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MemorySpec {
+    /// A [`WindowBufferMemory`] keeping the last `window_size` messages.
+    WindowBuffer { window_size: usize },
+    /// A [`JsonlMemory`] persisting the session `session_id` into the directory `dir`.
+    Jsonl { dir: PathBuf, session_id: String },
+}
+
+impl MemorySpec {
+    /// Create the memory described by the spec.
+    pub fn build(&self) -> Result<Ref<dyn Memory>, SpecError> {
+        let memory: Ref<dyn Memory> = match self {
+            MemorySpec::WindowBuffer { window_size } => {
+                make_ref(WindowBufferMemory::new(*window_size))
+            }
+            MemorySpec::Jsonl { dir, session_id } => make_ref(JsonlMemory::open(dir, session_id)?),
+        };
+        Ok(memory)
+    }
+}
+
+/// A storage index of an agent spec.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoreIndexSpec {
+    /// The registry name of the storage.
+    pub store: String,
+    /// The number of documents retrieved from the storage for each prompt.
+    pub sample: usize,
+    /// The minimum similarity score of the retrieved documents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f32>,
+}
+
+/// Resolves the tool and storage names of agent specs.
+#[derive(Default, Clone)]
+pub struct SpecRegistry {
+    tools: HashMap<String, Arc<dyn Tool>>,
+    stores: HashMap<String, Arc<dyn Storage>>,
+}
+
+impl SpecRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a tool under its name.
+    pub fn tool(mut self, tool: impl Tool + 'static) -> Self {
+        self.tools.insert(tool.name().to_string(), Arc::new(tool));
+        self
+    }
+
+    /// Registers a storage under the given name.
+    pub fn store(mut self, name: impl ToString, store: impl Storage + 'static) -> Self {
+        self.stores.insert(name.to_string(), Arc::new(store));
+        self
+    }
+
+    fn get_tool(&self, name: &str) -> Result<Box<dyn Tool>, SpecError> {
+        self.tools
+            .get(name)
+            .map(|tool| Box::new(SharedTool(tool.clone())) as Box<dyn Tool>)
+            .ok_or_else(|| SpecError::UnknownTool(name.to_string()))
+    }
+
+    fn get_store(&self, name: &str) -> Result<SharedStorage, SpecError> {
+        self.stores
+            .get(name)
+            .map(|store| SharedStorage(store.clone()))
+            .ok_or_else(|| SpecError::UnknownStore(name.to_string()))
+    }
+}
+
+impl AgentSpec {
+    /// Parses a spec from a TOML string.
+    pub fn from_toml(s: &str) -> Result<Self, SpecError> {
+        Ok(toml::from_str(s)?)
+    }
+
+    /// Parses a spec from a JSON string.
+    pub fn from_json(s: &str) -> Result<Self, SpecError> {
+        Ok(serde_json::from_str(s)?)
+    }
+
+    /// Serializes the spec into a TOML string.
+    pub fn to_toml(&self) -> Result<String, SpecError> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    /// Serializes the spec into a JSON string.
+    pub fn to_json(&self) -> Result<String, SpecError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Loads a spec from a file, the format is detected from the `.toml` or `.json` extension.
+    pub async fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, SpecError> {
+        let path = path.as_ref();
+        let content = tokio::fs::read_to_string(path).await?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(&content),
+            Some("json") => Self::from_json(&content),
+            _ => Err(SpecError::UnsupportedFormat(path.display().to_string())),
+        }
+    }
+
+    /// Builds an agent using the model described by the spec.
+    pub async fn build(&self, registry: &SpecRegistry) -> Result<Agent<LLM>, SpecError> {
+        self.build_with_model(self.model.build()?, registry).await
+    }
+
+    /// Builds an agent using the given model in place of the model of the spec.
+    pub async fn build_with_model<M: Completion>(
+        &self,
+        model: M,
+        registry: &SpecRegistry,
+    ) -> Result<Agent<M>, SpecError> {
+        let mut agent = Agent::new(&self.name, model)
+            .preamble(&self.preamble)
+            .max_steps(self.max_steps);
+        let mut tools = agent.tools.write().await;
+        for name in &self.tools {
+            tools.register(registry.get_tool(name)?)?;
+        }
+        drop(tools);
+        agent.temperature = self.temperature;
+        agent.max_tokens = self.max_tokens;
+        agent.memory = self.memory.as_ref().map(MemorySpec::build).transpose()?;
+        for index in &self.store_indices {
+            let mut retriever = Retriever::new(registry.get_store(&index.store)?)
+                .name(&index.store)
+                .top_k(index.sample);
+            if let Some(threshold) = index.threshold {
+                retriever = retriever.threshold(threshold);
+            }
+            agent = agent.retriever(retriever);
+        }
+        if !self.mcp_servers.is_empty() {
+            agent = agent
+                .mcp_servers(self.mcp_servers.clone().into_iter().collect())
+                .await?;
+        }
+        let mut tools = agent.tools.write().await;
+        for name in &self.disabled_tools {
+            tools.disable(name)?;
+        }
+        drop(tools);
+        Ok(agent)
+    }
+}
+
+impl Agent<LLM> {
+    /// Returns the declarative spec of the agent.
+    ///
+    /// Store indices are not included since storages are not named once added to an agent.
+    pub async fn spec(&self) -> AgentSpec {
+        let memory = match &self.memory {
+            Some(memory) => memory.read().await.spec(),
+            None => None,
+        };
+        let tools = self.tools.read().await;
+        AgentSpec {
+            name: self.name.clone(),
+            model: self.model.read().await.spec(),
+            preamble: self.preamble.clone(),
+            temperature: self.temperature,
+            max_tokens: self.max_tokens,
+            max_steps: self.max_steps,
+            tools: tools
+                .tools()
+                .filter(|tool| *tool.source() == ToolSource::Local)
+                .map(|tool| tool.name().to_string())
+                .collect(),
+            disabled_tools: tools
+                .tools()
+                .filter(|tool| !tool.is_enabled())
+                .map(|tool| tool.name().to_string())
+                .collect(),
+            mcp_servers: self
+                .mcp_server_configs()
+                .iter()
+                .map(|(name, config)| (name.clone(), config.clone()))
+                .collect(),
+            memory,
+            store_indices: vec![],
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SpecError {
+    #[error("Failed to read spec file: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Unsupported spec file format: {0}")]
+    UnsupportedFormat(String),
+    #[error("Failed to parse TOML spec: {0}")]
+    TomlParseError(#[from] toml::de::Error),
+    #[error("Failed to serialize TOML spec: {0}")]
+    TomlSerializeError(#[from] toml::ser::Error),
+    #[error("JSON spec error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Tool registry error: {0}")]
+    ToolRegistryError(#[from] ToolRegistryError),
+    #[error("Unknown tool: {0}")]
+    UnknownTool(String),
+    #[error("Unknown store: {0}")]
+    UnknownStore(String),
+    #[error("Missing API key environment variable: {0}")]
+    MissingApiKey(String),
+    #[error("Model error: {0}")]
+    ModelError(String),
+    #[error("MCP error: {0}")]
+    MCPError(#[from] MCPError),
+}
+
+/// A registry tool shared by the agents built from specs.
+struct SharedTool(Arc<dyn Tool>);
+
+#[async_trait]
+impl Tool for SharedTool {
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn version(&self) -> &str {
+        self.0.version()
+    }
+
+    fn description(&self) -> &str {
+        self.0.description()
+    }
+
+    fn author(&self) -> &str {
+        self.0.author()
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        self.0.definition()
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.0.timeout()
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        self.0.retry_policy()
+    }
+
+    fn requires_approval(&self) -> bool {
+        self.0.requires_approval()
+    }
+
+    fn validate_input(&self, input: &str) -> Result<(), ToolError> {
+        self.0.validate_input(input)
+    }
+
+    async fn run(&self, input: &str) -> Result<String, ToolError> {
+        self.0.run(input).await
+    }
+
+    async fn run_with_trace(&self, input: &str) -> Result<(String, Option<RunTrace>), ToolError> {
+        self.0.run_with_trace(input).await
+    }
+}
+
+/// A registry storage shared by the agents built from specs.
+struct SharedStorage(Arc<dyn Storage>);
+
+#[async_trait]
+impl Storage for SharedStorage {
+    async fn save(&self, value: String) -> Result<(), VectorStoreError> {
+        self.0.save(value).await
+    }
+
+    async fn search(&self, query: &str, limit: usize, threshold: f32) -> TopNResults {
+        self.0.search(query, limit, threshold).await
+    }
+
+    async fn reset(&self) -> Result<(), VectorStoreError> {
+        self.0.reset().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agent_spec_toml_roundtrip() {
+        let spec = AgentSpec::from_toml(
+            r#"
+name = "assistant"
+preamble = "You are a helpful assistant."
+tools = ["search"]
+
+[model]
+model = "deepseek-chat"
+base_url = "https://api.deepseek.com"
+api_key_env = "DEEPSEEK_API_KEY"
+
+[memory]
+type = "window_buffer"
+window_size = 10
+
+[mcp_servers.filesystem]
+command = "npx"
+args = ["-y", "@modelcontextprotocol/server-filesystem"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(spec.max_steps, DEFAULT_MAX_STEPS);
+        assert_eq!(
+            spec.memory,
+            Some(MemorySpec::WindowBuffer { window_size: 10 })
+        );
+        assert_eq!(spec.mcp_servers["filesystem"].command, "npx");
+        assert_eq!(
+            AgentSpec::from_toml(&spec.to_toml().unwrap()).unwrap(),
+            spec
+        );
+        assert_eq!(
+            AgentSpec::from_json(&spec.to_json().unwrap()).unwrap(),
+            spec
+        );
+        assert_eq!(
+            AgentSpec::from_json(r#"{"name": "a", "model": "gpt-4"}"#)
+                .unwrap()
+                .model,
+            ModelSpec::Name("gpt-4".to_string())
+        );
+    }
+}
@@ -85,11 +85,13 @@ impl<E: Embeddings> Storage for InMemoryStorage<E> {
             .embed_texts(vec![value])
             .await
             .map_err(VectorStoreError::EmbeddingError)?;
+        // The HNSW point IDs are the indices of the documents in `data`.
+        let offset = data.len();
         data.append(&mut embeddings.clone());
         let list: Vec<_> = embeddings
             .iter()
             .enumerate()
-            .map(|(k, data)| (&data.vec, k))
+            .map(|(k, data)| (&data.vec, offset + k))
             .collect();
         self.hnsw.write().await.parallel_insert(&list);
         Ok(())
@@ -122,6 +124,7 @@ impl<E: Embeddings> Storage for InMemoryStorage<E> {
     async fn reset(&self) -> Result<(), VectorStoreError> {
         let mut data = self.data.write().await;
         data.clear();
+        *self.hnsw.write().await = Self::build_hnsw(&data);
         Ok(())
     }
 }
@@ -167,3 +170,49 @@ impl<E: Embeddings> InMemoryStorage<E> {
         hnsw
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Embeds the texts as one-hot vectors of their first letter.
+    #[derive(Clone)]
+    struct LetterEmbeddings;
+
+    #[async_trait]
+    impl Embeddings for LetterEmbeddings {
+        async fn embed_texts(
+            &self,
+            input: Vec<String>,
+        ) -> Result<Vec<EmbeddingsData>, EmbeddingsError> {
+            Ok(input
+                .into_iter()
+                .map(|document| {
+                    let mut vec = vec![0.0; 26];
+                    let letter = document.bytes().next().unwrap_or(b'a');
+                    vec[(letter.to_ascii_lowercase().wrapping_sub(b'a') % 26) as usize] = 1.0;
+                    EmbeddingsData { document, vec }
+                })
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_storage_save_and_reset() {
+        let documents = LetterEmbeddings
+            .embed_texts(vec!["apple".to_string()])
+            .await
+            .unwrap();
+        let storage = InMemoryStorage::from_documents(LetterEmbeddings, documents);
+        storage.save("banana".to_string()).await.unwrap();
+        storage.save("cherry".to_string()).await.unwrap();
+        let results = storage.search("cucumber", 1, 0.5).await.unwrap();
+        assert_eq!(results[0].1, "cherry");
+
+        storage.reset().await.unwrap();
+        storage.save("date".to_string()).await.unwrap();
+        assert!(storage.search("avocado", 1, 0.5).await.unwrap().is_empty());
+        let results = storage.search("dragonfruit", 1, 0.5).await.unwrap();
+        assert_eq!(results[0].1, "date");
+    }
+}
@@ -1,13 +1,19 @@
+use crate::budget::BudgetLimit;
+use crate::executor::RunTrace;
 use crate::mcp::MCPError;
 use crate::{
     agent::Agent,
-    chat::{Chat, Completion},
+    chat::{Chat, Completion, Interrupted},
 };
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
+pub mod queue;
+
+pub use queue::{TaskQueue, TaskRecord, TaskStatus};
+
 #[derive(Clone)]
 pub struct Task<M: Completion> {
     pub id: Uuid,
@@ -28,7 +34,7 @@ impl<M: Completion + Send + Sync> Task<M> {
     /// Creates a new task.
     pub fn new(agent: Arc<RwLock<Agent<M>>>, prompt: String) -> Self {
         Self {
-            id: Uuid::default(),
+            id: Uuid::new_v4(),
             prompt,
             output: None,
             agent,
@@ -47,10 +53,7 @@ impl<M: Completion + Send + Sync> Task<M> {
         // Get a write lock for mutable access to the Agent
         let agent = self.agent.read().await;
         // Call `execute_task` on the Agent
-        let result = agent
-            .prompt(&self.prompt.clone())
-            .await
-            .map_err(|err| TaskError::ExecutionError(err.to_string()))?;
+        let result = agent.prompt(&self.prompt.clone()).await?;
 
         // Set the output of the task
         self.output = Some(result);
@@ -84,4 +87,21 @@ pub enum TaskError {
     Unknown(String),
     #[error("MCP error: {0}")]
     MCPError(#[from] MCPError),
+    #[error("Invalid output: {0}")]
+    InvalidOutput(String),
+    #[error("Budget exceeded: {limit}")]
+    BudgetExceeded { limit: BudgetLimit, trace: RunTrace },
+    #[error("The task was cancelled")]
+    Cancelled,
+    #[error("The task deadline has passed")]
+    DeadlineExceeded,
+}
+
+impl From<Interrupted> for TaskError {
+    fn from(interrupted: Interrupted) -> Self {
+        match interrupted {
+            Interrupted::Cancelled => TaskError::Cancelled,
+            Interrupted::DeadlineExceeded => TaskError::DeadlineExceeded,
+        }
+    }
 }
@@ -0,0 +1,501 @@
+use super::{Task, TaskError};
+use crate::chat::Completion;
+use futures::FutureExt;
+use std::any::Any;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::panic::AssertUnwindSafe;
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
+use tokio::task::AbortHandle;
+use uuid::Uuid;
+
+/// The default maximum number of tasks of a queue running at the same time.
+pub const DEFAULT_MAX_CONCURRENT_TASKS: usize = 4;
+
+/// The status of a task submitted to a [`TaskQueue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TaskStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl TaskStatus {
+    /// Returns `true` if the task completed, failed or was cancelled.
+    #[inline]
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            TaskStatus::Completed | TaskStatus::Failed | TaskStatus::Cancelled
+        )
+    }
+}
+
+/// The state of a task submitted to a [`TaskQueue`].
+#[derive(Debug, Clone)]
+pub struct TaskRecord {
+    pub id: Uuid,
+    pub prompt: String,
+    pub priority: usize,
+    pub tags: Vec<String>,
+    pub status: TaskStatus,
+    /// The output of the task once completed.
+    pub output: Option<String>,
+    /// The error message of the task once failed.
+    pub error: Option<String>,
+    /// The submission, start and end times, as seconds since the UNIX epoch.
+    pub submitted_at: u64,
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+}
+
+/// Schedules tasks by priority, running a bounded number of them at the same time.
+///
+/// Tasks with a higher [`TaskMetadata::priority`](super::TaskMetadata) run first and tasks
+/// of the same priority run in submission order. The queue keeps the record of every
+/// submitted task, which can be polled with [`TaskQueue::status`] or awaited with
+/// [`TaskQueue::wait`]. Cloning the queue returns a handle to the same queue.
+pub struct TaskQueue<M: Completion> {
+    inner: Arc<Inner<M>>,
+}
+
+struct Inner<M: Completion> {
+    max_concurrency: usize,
+    state: Mutex<State<M>>,
+    /// Notified each time a task finishes.
+    finished: Notify,
+}
+
+struct State<M: Completion> {
+    pending: BinaryHeap<Ticket>,
+    tasks: HashMap<Uuid, Task<M>>,
+    running: HashMap<Uuid, AbortHandle>,
+    records: HashMap<Uuid, TaskRecord>,
+    /// The IDs of the submitted tasks, in submission order.
+    order: Vec<Uuid>,
+}
+
+/// The scheduling order of a pending task, the greatest ticket runs first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Ticket {
+    priority: usize,
+    seq: Reverse<u64>,
+    id: Uuid,
+}
+
+impl<M: Completion> Clone for TaskQueue<M> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<M: Completion + Send + Sync + 'static> Default for TaskQueue<M> {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CONCURRENT_TASKS)
+    }
+}
+
+impl<M: Completion + Send + Sync + 'static> TaskQueue<M> {
+    /// Creates a queue running at most `max_concurrency` tasks at the same time, across
+    /// all agents.
+    pub fn new(max_concurrency: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                max_concurrency: max_concurrency.max(1),
+                state: Mutex::new(State {
+                    pending: BinaryHeap::new(),
+                    tasks: HashMap::new(),
+                    running: HashMap::new(),
+                    records: HashMap::new(),
+                    order: Vec::new(),
+                }),
+                finished: Notify::new(),
+            }),
+        }
+    }
+
+    /// Submits a task and returns its ID, a new ID is assigned to tasks with the nil ID
+    /// or an ID already submitted.
+    ///
+    /// The task starts right away if the concurrency limit allows it, so this must be
+    /// called within a Tokio runtime.
+    pub fn submit(&self, mut task: Task<M>) -> Uuid {
+        let mut state = self.inner.state.lock().unwrap();
+        if task.id.is_nil() || state.records.contains_key(&task.id) {
+            task.id = Uuid::new_v4();
+        }
+        let id = task.id;
+        let (priority, tags) = task
+            .metadata
+            .as_ref()
+            .map(|metadata| (metadata.priority, metadata.tags.clone()))
+            .unwrap_or_default();
+        state.records.insert(
+            id,
+            TaskRecord {
+                id,
+                prompt: task.prompt.clone(),
+                priority,
+                tags,
+                status: TaskStatus::Pending,
+                output: None,
+                error: None,
+                submitted_at: now(),
+                started_at: None,
+                finished_at: None,
+            },
+        );
+        let seq = state.order.len() as u64;
+        state.order.push(id);
+        state.pending.push(Ticket {
+            priority,
+            seq: Reverse(seq),
+            id,
+        });
+        state.tasks.insert(id, task);
+        self.inner.dispatch(&mut state);
+        id
+    }
+
+    /// Returns the status of a task, `None` if it was never submitted.
+    pub fn status(&self, id: &Uuid) -> Option<TaskStatus> {
+        let state = self.inner.state.lock().unwrap();
+        state.records.get(id).map(|record| record.status)
+    }
+
+    /// Returns the record of a task, `None` if it was never submitted.
+    pub fn record(&self, id: &Uuid) -> Option<TaskRecord> {
+        let state = self.inner.state.lock().unwrap();
+        state.records.get(id).cloned()
+    }
+
+    /// Returns the records of all submitted tasks, in submission order.
+    pub fn records(&self) -> Vec<TaskRecord> {
+        let state = self.inner.state.lock().unwrap();
+        state
+            .order
+            .iter()
+            .filter_map(|id| state.records.get(id).cloned())
+            .collect()
+    }
+
+    /// Returns the records of the tasks with the tag, in submission order.
+    pub fn records_with_tag(&self, tag: &str) -> Vec<TaskRecord> {
+        let mut records = self.records();
+        records.retain(|record| record.tags.iter().any(|t| t == tag));
+        records
+    }
+
+    /// Returns the number of pending and running tasks.
+    pub fn len(&self) -> usize {
+        let state = self.inner.state.lock().unwrap();
+        state.tasks.len() + state.running.len()
+    }
+
+    /// Returns `true` if no task is pending or running.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Cancels a pending or running task, returns `false` if it is already finished or
+    /// was never submitted.
+    pub fn cancel(&self, id: &Uuid) -> bool {
+        let mut state = self.inner.state.lock().unwrap();
+        let cancelled = if state.tasks.remove(id).is_some() {
+            // The ticket is discarded when it is popped.
+            true
+        } else if let Some(handle) = state.running.remove(id) {
+            handle.abort();
+            true
+        } else {
+            false
+        };
+        if cancelled {
+            if let Some(record) = state.records.get_mut(id) {
+                record.status = TaskStatus::Cancelled;
+                record.finished_at = Some(now());
+            }
+            self.inner.dispatch(&mut state);
+            self.inner.finished.notify_waiters();
+        }
+        cancelled
+    }
+
+    /// Waits until a task is finished and returns its record, `None` if it was never
+    /// submitted.
+    pub async fn wait(&self, id: &Uuid) -> Option<TaskRecord> {
+        loop {
+            let mut finished = pin!(self.inner.finished.notified());
+            finished.as_mut().enable();
+            match self.record(id) {
+                Some(record) if !record.status.is_finished() => finished.await,
+                record => return record,
+            }
+        }
+    }
+
+    /// Waits until no task is pending or running.
+    pub async fn wait_all(&self) {
+        loop {
+            let mut finished = pin!(self.inner.finished.notified());
+            finished.as_mut().enable();
+            if self.is_empty() {
+                return;
+            }
+            finished.await;
+        }
+    }
+}
+
+impl<M: Completion + Send + Sync + 'static> Inner<M> {
+    /// Starts the pending tasks with the highest priority while the concurrency limit
+    /// allows it.
+    fn dispatch(self: &Arc<Self>, state: &mut State<M>) {
+        while state.running.len() < self.max_concurrency {
+            let Some(ticket) = state.pending.pop() else {
+                break;
+            };
+            // Cancelled tasks have no task left.
+            let Some(mut task) = state.tasks.remove(&ticket.id) else {
+                continue;
+            };
+            if let Some(record) = state.records.get_mut(&ticket.id) {
+                record.status = TaskStatus::Running;
+                record.started_at = Some(now());
+            }
+            let inner = self.clone();
+            let handle = tokio::spawn(async move {
+                // A panicking task fails, its slot is released.
+                let result = AssertUnwindSafe(task.execute())
+                    .catch_unwind()
+                    .await
+                    .unwrap_or_else(|panic| Err(TaskError::ExecutionError(panic_message(panic))));
+                inner.finish(&task.id, result);
+            });
+            state.running.insert(ticket.id, handle.abort_handle());
+        }
+    }
+
+    /// Records the result of a task and starts the next pending tasks.
+    fn finish(self: &Arc<Self>, id: &Uuid, result: Result<String, TaskError>) {
+        let mut state = self.state.lock().unwrap();
+        // A cancelled task is no longer running.
+        if state.running.remove(id).is_none() {
+            return;
+        }
+        if let Some(record) = state.records.get_mut(id) {
+            match result {
+                Ok(output) => {
+                    record.status = TaskStatus::Completed;
+                    record.output = Some(output);
+                }
+                Err(err) => {
+                    record.status = TaskStatus::Failed;
+                    record.error = Some(err.to_string());
+                }
+            }
+            record.finished_at = Some(now());
+        }
+        self.dispatch(&mut state);
+        drop(state);
+        self.finished.notify_waiters();
+    }
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    let message = match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => panic
+            .downcast_ref::<&str>()
+            .map_or("unknown panic", |message| *message)
+            .to_string(),
+    };
+    format!("the task panicked: {message}")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::Agent;
+    use crate::chat::{CompletionError, Request};
+    use crate::mock::MockResponse;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::{RwLock, Semaphore};
+
+    /// A model answering with the prompt once its gate is open, which fails the prompts
+    /// starting with "fail" and panics on the prompts starting with "panic".
+    #[derive(Clone)]
+    struct GatedCompletion {
+        gate: Arc<Semaphore>,
+        running: Arc<AtomicUsize>,
+        max_running: Arc<AtomicUsize>,
+    }
+
+    impl GatedCompletion {
+        fn new(open: bool) -> Self {
+            Self {
+                gate: Arc::new(Semaphore::new(open as usize)),
+                running: Default::default(),
+                max_running: Default::default(),
+            }
+        }
+
+        fn open(&self) {
+            self.gate.add_permits(1);
+        }
+
+        /// Yields until `running` tasks are waiting for the gate.
+        async fn wait_running(&self, running: usize) {
+            while self.running.load(Ordering::SeqCst) < running {
+                tokio::task::yield_now().await;
+            }
+        }
+
+        fn task(&self, prompt: &str) -> Task<Self> {
+            let agent = Agent::new("agent", self.clone());
+            Task::new(Arc::new(RwLock::new(agent)), prompt.to_string())
+        }
+    }
+
+    impl Completion for GatedCompletion {
+        type Response = MockResponse;
+
+        async fn completion(&mut self, request: Request) -> Result<MockResponse, CompletionError> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+            let _permit = self.gate.acquire().await.unwrap();
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            if request.prompt.starts_with("panic") {
+                panic!("the model crashed");
+            }
+            if request.prompt.starts_with("fail") {
+                Err(CompletionError::Normal("overloaded".to_string()))
+            } else {
+                Ok(MockResponse::text(request.prompt))
+            }
+        }
+    }
+
+    fn statuses(queue: &TaskQueue<GatedCompletion>) -> Vec<TaskStatus> {
+        queue.records().iter().map(|record| record.status).collect()
+    }
+
+    #[tokio::test]
+    async fn test_queue_concurrency_bound() {
+        use TaskStatus::*;
+        let model = GatedCompletion::new(false);
+        let queue = TaskQueue::new(2);
+        for prompt in ["a", "b", "c", "d", "e"] {
+            queue.submit(model.task(prompt));
+        }
+        model.wait_running(2).await;
+        assert_eq!(
+            statuses(&queue),
+            [Running, Running, Pending, Pending, Pending]
+        );
+        assert_eq!(queue.len(), 5);
+
+        model.open();
+        queue.wait_all().await;
+        assert_eq!(model.max_running.load(Ordering::SeqCst), 2);
+        assert!(queue.is_empty());
+        let outputs: Vec<_> = queue
+            .records()
+            .into_iter()
+            .map(|record| (record.status, record.output.unwrap()))
+            .collect();
+        assert_eq!(
+            outputs,
+            ["a", "b", "c", "d", "e"].map(|prompt| (Completed, prompt.to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_queue_cancel() {
+        let model = GatedCompletion::new(false);
+        let queue = TaskQueue::new(1);
+        let running = queue.submit(model.task("a"));
+        let pending = queue.submit(model.task("b"));
+        model.wait_running(1).await;
+
+        assert!(queue.cancel(&pending));
+        assert_eq!(queue.status(&pending), Some(TaskStatus::Cancelled));
+        assert!(queue.cancel(&running));
+        assert_eq!(queue.status(&running), Some(TaskStatus::Cancelled));
+        assert!(queue.is_empty());
+        assert!(!queue.cancel(&running));
+        assert!(!queue.cancel(&Uuid::new_v4()));
+
+        // The cancelled tasks free their slot.
+        model.open();
+        let next = queue.submit(model.task("c"));
+        let record = queue.wait(&next).await.unwrap();
+        assert_eq!(record.status, TaskStatus::Completed);
+        assert_eq!(queue.record(&pending).unwrap().started_at, None);
+    }
+
+    #[tokio::test]
+    async fn test_queue_wait_failed() {
+        let model = GatedCompletion::new(true);
+        let queue = TaskQueue::new(2);
+        let completed = queue.submit(model.task("ok"));
+        let failed = queue.submit(model.task("fail"));
+
+        let record = queue.wait(&failed).await.unwrap();
+        assert_eq!(record.status, TaskStatus::Failed);
+        assert!(record.error.unwrap().contains("overloaded"));
+        assert!(record.output.is_none());
+        assert!(record.finished_at.is_some());
+        queue.wait_all().await;
+        let record = queue.wait(&completed).await.unwrap();
+        assert_eq!(record.status, TaskStatus::Completed);
+        assert_eq!(record.output.as_deref(), Some("ok"));
+        assert!(queue.wait(&Uuid::new_v4()).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_queue_task_panic() {
+        let model = GatedCompletion::new(true);
+        let queue = TaskQueue::new(1);
+        let panicked = queue.submit(model.task("panic"));
+        let next = queue.submit(model.task("ok"));
+
+        let record = queue.wait(&panicked).await.unwrap();
+        assert_eq!(record.status, TaskStatus::Failed);
+        assert!(record.error.unwrap().contains("the model crashed"));
+        // The slot of the panicked task is released.
+        let record = queue.wait(&next).await.unwrap();
+        assert_eq!(record.status, TaskStatus::Completed);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_ticket_order() {
+        let ticket = |priority, seq| Ticket {
+            priority,
+            seq: Reverse(seq),
+            id: Uuid::new_v4(),
+        };
+        let mut pending =
+            BinaryHeap::from([ticket(0, 0), ticket(2, 1), ticket(0, 2), ticket(2, 3)]);
+        let order: Vec<u64> =
+            std::iter::from_fn(|| pending.pop().map(|ticket| ticket.seq.0)).collect();
+        // Higher priorities first, then submission order.
+        assert_eq!(order, vec![1, 3, 0, 2]);
+    }
+}
@@ -0,0 +1,380 @@
+//! Tracing spans of the agent runs and their export to OpenTelemetry.
+//!
+//! Each agent run is recorded as an `agent.chat` span, with child spans for the retrievals,
+//! the tool and MCP calls and each model call attempt. The span fields follow the
+//! OpenTelemetry semantic conventions for generative AI where one exists. With the
+//! `telemetry` feature, [`init_telemetry`] exports the spans over OTLP or as JSON lines on
+//! stdout, any other `tracing` subscriber may be installed instead.
+use crate::chat::ToolCall;
+use crate::executor::RunTrace;
+use crate::tool::ToolSource;
+use std::fmt::Display;
+use tracing::Span;
+use tracing::field::{Empty, display};
+
+/// Creates the span of an agent run.
+pub(crate) fn agent_span(
+    agent: &str,
+    agent_id: &str,
+    model: &str,
+    session_id: Option<&str>,
+) -> Span {
+    tracing::info_span!(
+        "agent.chat",
+        gen_ai.operation.name = "chat",
+        gen_ai.agent.name = agent,
+        gen_ai.agent.id = agent_id,
+        gen_ai.request.model = model,
+        session.id = session_id,
+        run.id = Empty,
+        gen_ai.usage.input_tokens = Empty,
+        gen_ai.usage.output_tokens = Empty,
+        agent.steps = Empty,
+        agent.tool_calls = Empty,
+        error.message = Empty,
+        otel.status_code = Empty,
+    )
+}
+
+/// Creates the span of a retrieval, `kind` is `documents` or `knowledges`.
+pub(crate) fn retrieval_span(kind: &str) -> Span {
+    tracing::info_span!(
+        "agent.retrieval",
+        retrieval.kind = kind,
+        retrieval.results = Empty,
+        error.message = Empty,
+        otel.status_code = Empty,
+    )
+}
+
+/// Creates the span of a tool call.
+pub(crate) fn tool_span(call: &ToolCall, source: Option<&ToolSource>) -> Span {
+    tracing::info_span!(
+        "agent.tool_call",
+        gen_ai.operation.name = "execute_tool",
+        gen_ai.tool.name = call.function.name.as_str(),
+        gen_ai.tool.call.id = call.id.as_str(),
+        tool.source = source.map(display),
+        error.message = Empty,
+        otel.status_code = Empty,
+    )
+}
+
+/// Records the run ID, the token usage and the number of steps and tool calls of a run.
+pub(crate) fn record_trace(span: &Span, trace: &RunTrace) {
+    let usage = trace.usage();
+    span.record("run.id", trace.run_id.as_str());
+    span.record("gen_ai.usage.input_tokens", usage.prompt_tokens);
+    span.record("gen_ai.usage.output_tokens", usage.completion_tokens);
+    span.record("agent.steps", trace.steps.len() as u64);
+    span.record("agent.tool_calls", trace.tool_calls().count() as u64);
+}
+
+/// Marks the span as failed with the error.
+pub(crate) fn record_error(span: &Span, err: &impl Display) {
+    span.record("otel.status_code", "ERROR");
+    span.record("error.message", display(err));
+}
+
+#[cfg(feature = "telemetry")]
+pub use exporter::{
+    DEFAULT_SERVICE_NAME, TelemetryConfig, TelemetryError, TelemetryExporter, TelemetryGuard,
+    init_telemetry,
+};
+
+#[cfg(feature = "telemetry")]
+mod exporter {
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::{Resource, trace::SdkTracerProvider};
+    use tracing_subscriber::fmt::format::FmtSpan;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+    use tracing_subscriber::{EnvFilter, Layer};
+
+    /// The default service name of the exported spans.
+    pub const DEFAULT_SERVICE_NAME: &str = "alith";
+
+    /// Where the spans are exported.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum TelemetryExporter {
+        /// Exports the spans to an OpenTelemetry collector over OTLP/HTTP.
+        ///
+        /// The endpoint is the full URL of the traces, e.g. `http://localhost:4318/v1/traces`,
+        /// and defaults to the `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT` and
+        /// `OTEL_EXPORTER_OTLP_ENDPOINT` environment variables.
+        Otlp { endpoint: Option<String> },
+        /// Writes each closed span as a JSON line on stdout.
+        Stdout,
+    }
+
+    /// The configuration of [`init_telemetry`].
+    #[derive(Debug, Clone)]
+    pub struct TelemetryConfig {
+        pub service_name: String,
+        pub exporter: TelemetryExporter,
+        /// The filter directives of the exported spans, e.g. `alith_core=info`, defaults to
+        /// the `RUST_LOG` environment variable or `info`.
+        pub filter: Option<String>,
+    }
+
+    impl Default for TelemetryConfig {
+        fn default() -> Self {
+            Self {
+                service_name: DEFAULT_SERVICE_NAME.to_string(),
+                exporter: TelemetryExporter::Otlp { endpoint: None },
+                filter: None,
+            }
+        }
+    }
+
+    impl TelemetryConfig {
+        /// Creates a configuration exporting the spans of the service over OTLP.
+        pub fn new(service_name: impl ToString) -> Self {
+            Self {
+                service_name: service_name.to_string(),
+                ..Default::default()
+            }
+        }
+
+        /// Exports the spans over OTLP/HTTP to the traces endpoint.
+        pub fn otlp(mut self, endpoint: impl ToString) -> Self {
+            self.exporter = TelemetryExporter::Otlp {
+                endpoint: Some(endpoint.to_string()),
+            };
+            self
+        }
+
+        /// Writes the spans as JSON lines on stdout.
+        pub fn stdout(mut self) -> Self {
+            self.exporter = TelemetryExporter::Stdout;
+            self
+        }
+
+        /// Sets the filter directives of the exported spans.
+        pub fn filter(mut self, filter: impl ToString) -> Self {
+            self.filter = Some(filter.to_string());
+            self
+        }
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    pub enum TelemetryError {
+        #[error("OTLP exporter error: {0}")]
+        Exporter(#[from] opentelemetry_otlp::ExporterBuildError),
+        #[error("Invalid filter directives: {0}")]
+        Filter(#[from] tracing_subscriber::filter::ParseError),
+        #[error("Failed to install the tracing subscriber: {0}")]
+        Init(#[from] tracing_subscriber::util::TryInitError),
+        #[error("Failed to shut the exporter down: {0}")]
+        Shutdown(#[from] opentelemetry_sdk::error::OTelSdkError),
+    }
+
+    /// Flushes the pending spans and shuts the exporter down when dropped, or explicitly
+    /// with [`TelemetryGuard::shutdown`] to get the error.
+    #[must_use = "the exporter is shut down when the guard is dropped"]
+    pub struct TelemetryGuard {
+        provider: Option<SdkTracerProvider>,
+    }
+
+    impl TelemetryGuard {
+        /// Flushes the pending spans and shuts the exporter down.
+        pub fn shutdown(mut self) -> Result<(), TelemetryError> {
+            match self.provider.take() {
+                Some(provider) => Ok(provider.shutdown()?),
+                None => Ok(()),
+            }
+        }
+    }
+
+    impl Drop for TelemetryGuard {
+        fn drop(&mut self) {
+            if let Some(provider) = self.provider.take() {
+                if let Err(err) = provider.shutdown() {
+                    tracing::warn!("Failed to shut the telemetry exporter down: {err}");
+                }
+            }
+        }
+    }
+
+    /// Installs a global `tracing` subscriber exporting the spans, and returns the guard
+    /// which must be kept alive until the program exits.
+    pub fn init_telemetry(config: TelemetryConfig) -> Result<TelemetryGuard, TelemetryError> {
+        let filter = match &config.filter {
+            Some(filter) => EnvFilter::try_new(filter)?,
+            None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        };
+        match config.exporter {
+            TelemetryExporter::Otlp { endpoint } => {
+                let mut builder = opentelemetry_otlp::SpanExporter::builder().with_http();
+                if let Some(endpoint) = endpoint {
+                    builder = builder.with_endpoint(endpoint);
+                }
+                let provider = SdkTracerProvider::builder()
+                    .with_batch_exporter(builder.build()?)
+                    .with_resource(
+                        Resource::builder()
+                            .with_service_name(config.service_name)
+                            .build(),
+                    )
+                    .build();
+                let layer = tracing_opentelemetry::layer()
+                    .with_tracer(provider.tracer(DEFAULT_SERVICE_NAME))
+                    .with_filter(filter);
+                tracing_subscriber::registry().with(layer).try_init()?;
+                Ok(TelemetryGuard {
+                    provider: Some(provider),
+                })
+            }
+            TelemetryExporter::Stdout => {
+                let layer = tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_span_events(FmtSpan::CLOSE)
+                    .with_current_span(true)
+                    .with_span_list(true)
+                    .with_filter(filter);
+                tracing_subscriber::registry().with(layer).try_init()?;
+                Ok(TelemetryGuard { provider: None })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::agent::Agent;
+    use crate::chat::Chat;
+    use crate::mock::{MockCompletion, MockResponse};
+    use crate::tool::{StructureTool, ToolError};
+    use async_trait::async_trait;
+    use schemars::JsonSchema;
+    use serde::Deserialize;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+    use tracing::Subscriber;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing_subscriber::Layer;
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::registry::LookupSpan;
+
+    #[derive(Debug)]
+    struct RecordedSpan {
+        name: &'static str,
+        parent: Option<&'static str>,
+        fields: HashMap<String, String>,
+    }
+
+    /// A layer recording the spans with their fields.
+    #[derive(Clone, Default)]
+    struct SpanRecorder(Arc<Mutex<Vec<RecordedSpan>>>);
+
+    impl SpanRecorder {
+        fn spans(&self, name: &str) -> Vec<(Option<&'static str>, HashMap<String, String>)> {
+            self.0
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|span| span.name == name)
+                .map(|span| (span.parent, span.fields.clone()))
+                .collect()
+        }
+    }
+
+    struct FieldVisitor<'a>(&'a mut HashMap<String, String>);
+
+    impl Visit for FieldVisitor<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{value:?}"));
+        }
+    }
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for SpanRecorder {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let span = ctx.span(id).unwrap();
+            let mut fields = HashMap::new();
+            attrs.record(&mut FieldVisitor(&mut fields));
+            let mut spans = self.0.lock().unwrap();
+            span.extensions_mut().insert(spans.len());
+            spans.push(RecordedSpan {
+                name: attrs.metadata().name(),
+                parent: span.parent().map(|parent| parent.name()),
+                fields,
+            });
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+            let span = ctx.span(id).unwrap();
+            let index = *span.extensions().get::<usize>().unwrap();
+            values.record(&mut FieldVisitor(&mut self.0.lock().unwrap()[index].fields));
+        }
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    struct LookupInput {
+        key: String,
+    }
+
+    struct Lookup;
+
+    #[async_trait]
+    impl StructureTool for Lookup {
+        type Input = LookupInput;
+        type Output = String;
+
+        fn name(&self) -> &str {
+            "lookup"
+        }
+
+        async fn run_with_args(&self, input: Self::Input) -> Result<Self::Output, ToolError> {
+            match input.key.as_str() {
+                "a" => Ok("1".to_string()),
+                key => Err(ToolError::NotFound(key.to_string())),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_agent_spans() {
+        let recorder = SpanRecorder::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+        let mock =
+            MockCompletion::new()
+                .respond(
+                    MockResponse::tool_call("call_1", "lookup", json!({"key": "a"}))
+                        .with_tool_call("call_2", "lookup", json!({"key": "b"})),
+                )
+                .respond("Done.");
+        let agent = Agent::new("librarian", mock).tool(Lookup).await;
+        agent.prompt("Look a and b up").await.unwrap();
+
+        let chats = recorder.spans("agent.chat");
+        assert_eq!(chats.len(), 1);
+        let (_, chat) = &chats[0];
+        assert_eq!(chat["gen_ai.agent.name"], "librarian");
+        assert_eq!(chat["agent.steps"], "2");
+        assert_eq!(chat["agent.tool_calls"], "2");
+        assert!(chat.contains_key("run.id"));
+        assert!(!chat.contains_key("error.message"));
+
+        let calls = recorder.spans("agent.tool_call");
+        assert_eq!(calls.len(), 2);
+        for (parent, call) in &calls {
+            assert_eq!(*parent, Some("agent.chat"));
+            assert_eq!(call["gen_ai.tool.name"], "lookup");
+        }
+        assert_eq!(calls[0].1["gen_ai.tool.call.id"], "call_1");
+        assert!(!calls[0].1.contains_key("otel.status_code"));
+        assert_eq!(calls[1].1["gen_ai.tool.call.id"], "call_2");
+        assert_eq!(calls[1].1["otel.status_code"], "ERROR");
+        assert!(calls[1].1["error.message"].contains("b"));
+    }
+}
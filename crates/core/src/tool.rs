@@ -1,9 +1,16 @@
+use crate::budget::BudgetLimit;
+use crate::chat::Interrupted;
+use crate::executor::RunTrace;
 use async_trait::async_trait;
 use schemars::{JsonSchema, schema::RootSchema, schema_for};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::time::Duration;
+
+pub mod registry;
 
 pub use alith_interface::requests::completion::{ToolChoice, ToolDefinition};
+pub use registry::{RegisteredTool, ToolRegistry, ToolRegistryError, ToolSource};
 
 #[async_trait]
 pub trait Tool: Send + Sync {
@@ -25,6 +32,22 @@ pub trait Tool: Send + Sync {
 
     fn definition(&self) -> ToolDefinition;
 
+    /// The maximum duration of a single tool run, `None` means no limit.
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// The retry policy applied when a tool run fails with a retryable error.
+    fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::default()
+    }
+
+    /// Whether the configured tool approver must approve the tool calls, read-only
+    /// tools may return false to skip the approval.
+    fn requires_approval(&self) -> bool {
+        true
+    }
+
     fn validate_input(&self, input: &str) -> Result<(), ToolError> {
         if input.trim().is_empty() {
             Err(ToolError::InvalidInput)
@@ -34,6 +57,12 @@ pub trait Tool: Send + Sync {
     }
 
     async fn run(&self, input: &str) -> Result<String, ToolError>;
+
+    /// Runs the tool and returns its output with the trace of the agent run the call was
+    /// delegated to, if any, which is nested into the trace of the calling run.
+    async fn run_with_trace(&self, input: &str) -> Result<(String, Option<RunTrace>), ToolError> {
+        Ok((self.run(input).await?, None))
+    }
 }
 
 #[async_trait]
@@ -57,6 +86,22 @@ pub trait StructureTool: Send + Sync {
         "Anonymous"
     }
 
+    /// The maximum duration of a single tool run, `None` means no limit.
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// The retry policy applied when a tool run fails with a retryable error.
+    fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::default()
+    }
+
+    /// Whether the configured tool approver must approve the tool calls, read-only
+    /// tools may return false to skip the approval.
+    fn requires_approval(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> RootSchema {
         schema_for!(Self::Input)
     }
@@ -104,6 +149,18 @@ impl<T: StructureTool> Tool for T {
         self.definition()
     }
 
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout()
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy()
+    }
+
+    fn requires_approval(&self) -> bool {
+        self.requires_approval()
+    }
+
     async fn run(&self, input: &str) -> Result<String, ToolError> {
         match serde_json::from_str(input) {
             Ok(input) => {
@@ -115,6 +172,33 @@ impl<T: StructureTool> Tool for T {
     }
 }
 
+/// The retry policy of a tool.
+///
+/// The default policy never retries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of retries after the first attempt.
+    pub max_retries: usize,
+    /// The delay before each retry.
+    pub delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Creates a retry policy retrying immediately up to `max_retries` times.
+    pub fn new(max_retries: usize) -> Self {
+        Self {
+            max_retries,
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// Sets the delay before each retry.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 #[error("Tool error")]
 pub enum ToolError {
@@ -130,6 +214,58 @@ pub enum ToolError {
     Unknown(String),
     #[error("JsonError: {0}")]
     JsonError(#[from] serde_json::Error),
+    #[error("The tool timed out after {0:?}")]
+    Timeout(Duration),
+    #[error("Tool not found: {0}")]
+    NotFound(String),
+    #[error("The tool call was denied: {0}")]
+    Denied(String),
+    /// A delegated agent run was cancelled or passed its deadline, which stops the
+    /// calling run as well.
+    #[error("The tool run was interrupted: {0}")]
+    Interrupted(Interrupted),
+    /// A delegated agent run exceeded its budget, which stops the calling run as well.
+    #[error("The tool run exceeded its budget: {0}")]
+    BudgetExceeded(BudgetLimit),
+}
+
+impl ToolError {
+    /// Returns a short machine readable name of the error kind.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ToolError::NormalError(_) => "error",
+            ToolError::InvalidInput => "invalid_input",
+            ToolError::InvalidOutput => "invalid_output",
+            ToolError::InvalidTool => "invalid_tool",
+            ToolError::Unknown(_) => "unknown",
+            ToolError::JsonError(_) => "json_error",
+            ToolError::Timeout(_) => "timeout",
+            ToolError::NotFound(_) => "not_found",
+            ToolError::Denied(_) => "denied",
+            ToolError::Interrupted(_) => "interrupted",
+            ToolError::BudgetExceeded(_) => "budget_exceeded",
+        }
+    }
+
+    /// Whether a failed tool run may succeed when it is retried.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ToolError::NormalError(_) | ToolError::Unknown(_) | ToolError::Timeout(_)
+        )
+    }
+
+    /// Formats the error as the structured tool result sent back to the model.
+    pub fn to_message(&self, tool: &str) -> String {
+        json!({
+            "error": {
+                "tool": tool,
+                "type": self.kind(),
+                "message": self.to_string(),
+            }
+        })
+        .to_string()
+    }
 }
 
 #[cfg(test)]
@@ -139,6 +275,7 @@ mod tests {
     use schemars::JsonSchema;
     use serde::{Deserialize, Serialize};
     use serde_json::json;
+    use std::time::Duration;
 
     pub struct DummyTool;
 
@@ -179,4 +316,21 @@ mod tests {
         assert_eq!(tool.name(), "dummy");
         assert_eq!(output, "\"x: 1, y: 2\"");
     }
+
+    #[test]
+    fn test_tool_error_message() {
+        let err = ToolError::Timeout(Duration::from_secs(2));
+        assert!(err.is_retryable());
+        let message: serde_json::Value = serde_json::from_str(&err.to_message("dummy")).unwrap();
+        assert_eq!(
+            message,
+            json!({
+                "error": {
+                    "tool": "dummy",
+                    "type": "timeout",
+                    "message": "The tool timed out after 2s",
+                }
+            })
+        );
+    }
 }
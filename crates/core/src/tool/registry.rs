@@ -0,0 +1,372 @@
+use super::{RetryPolicy, Tool, ToolDefinition, ToolError};
+use crate::executor::RunTrace;
+use crate::mcp::MCPClient;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::Instrument;
+
+/// The separator between the server name and the tool name of namespaced MCP tools.
+///
+/// Dots are not allowed in the function names of the OpenAI API, so `server__tool` is used
+/// instead of `server.tool`.
+pub const NAMESPACE_SEPARATOR: &str = "__";
+
+/// The source which serves a registered tool.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolSource {
+    /// A tool implemented in process.
+    Local,
+    /// A tool served by an MCP server, the server name is `None` for clients added without one.
+    Mcp(Option<String>),
+}
+
+impl fmt::Display for ToolSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToolSource::Local => write!(f, "local"),
+            ToolSource::Mcp(Some(server)) => write!(f, "MCP server `{server}`"),
+            ToolSource::Mcp(None) => write!(f, "MCP client"),
+        }
+    }
+}
+
+enum ToolProvider {
+    Local(Box<dyn Tool>),
+    Mcp {
+        client: Arc<MCPClient>,
+        tool: String,
+    },
+}
+
+/// A tool registered in a [`ToolRegistry`] under its qualified name.
+pub struct RegisteredTool {
+    name: String,
+    source: ToolSource,
+    definition: ToolDefinition,
+    enabled: bool,
+    provider: ToolProvider,
+}
+
+impl RegisteredTool {
+    /// The qualified name of the tool, which is the name offered to the model.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The source which serves the tool.
+    #[inline]
+    pub fn source(&self) -> &ToolSource {
+        &self.source
+    }
+
+    /// The definition offered to the model, named with the qualified name.
+    #[inline]
+    pub fn definition(&self) -> &ToolDefinition {
+        &self.definition
+    }
+
+    /// Whether the tool is offered to the model and may be called.
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The timeout of the tool, MCP tools do not declare one.
+    pub fn timeout(&self) -> Option<Duration> {
+        match &self.provider {
+            ToolProvider::Local(tool) => tool.timeout(),
+            ToolProvider::Mcp { .. } => None,
+        }
+    }
+
+    /// The retry policy of the tool, MCP tools are never retried.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        match &self.provider {
+            ToolProvider::Local(tool) => tool.retry_policy(),
+            ToolProvider::Mcp { .. } => RetryPolicy::default(),
+        }
+    }
+
+    /// Whether calls of the tool must be approved, MCP tools always require approval.
+    pub fn requires_approval(&self) -> bool {
+        match &self.provider {
+            ToolProvider::Local(tool) => tool.requires_approval(),
+            ToolProvider::Mcp { .. } => true,
+        }
+    }
+
+    /// Runs the tool with the JSON arguments of a tool call.
+    pub async fn run(&self, arguments: &str) -> Result<String, ToolError> {
+        Ok(self.run_with_trace(arguments).await?.0)
+    }
+
+    /// Runs the tool with the JSON arguments of a tool call and returns the trace of the
+    /// agent run the call was delegated to, if any.
+    pub async fn run_with_trace(
+        &self,
+        arguments: &str,
+    ) -> Result<(String, Option<RunTrace>), ToolError> {
+        match &self.provider {
+            ToolProvider::Local(tool) => tool.run_with_trace(arguments).await,
+            ToolProvider::Mcp { client, tool } => {
+                let arguments = serde_json::from_str(arguments)?;
+                let server = match &self.source {
+                    ToolSource::Mcp(server) => server.as_deref(),
+                    ToolSource::Local => None,
+                };
+                let span = tracing::info_span!(
+                    "mcp.call_tool",
+                    mcp.server = server,
+                    mcp.tool = tool.as_str()
+                );
+                let response = client
+                    .call_tool(tool, arguments)
+                    .instrument(span)
+                    .await
+                    .map_err(|err| ToolError::NormalError(Box::new(err)))?;
+                let output = response
+                    .content
+                    .first()
+                    .and_then(|content| content.as_text())
+                    .map(|text| text.to_string())
+                    .unwrap_or_default();
+                Ok((output, None))
+            }
+        }
+    }
+}
+
+/// The local and MCP tools of an agent, keyed by their qualified names.
+///
+/// Local tools keep their own name while the tools of named MCP servers are namespaced as
+/// `server__tool`. Registering a tool whose name is already taken, ignoring the case, fails
+/// instead of shadowing the existing tool.
+#[derive(Default)]
+pub struct ToolRegistry {
+    tools: Vec<RegisteredTool>,
+}
+
+impl ToolRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a local tool under its own name.
+    pub fn register(&mut self, tool: Box<dyn Tool>) -> Result<(), ToolRegistryError> {
+        let name = tool.name().to_string();
+        self.insert(RegisteredTool {
+            definition: ToolDefinition {
+                name: name.clone(),
+                ..tool.definition()
+            },
+            name,
+            source: ToolSource::Local,
+            enabled: true,
+            provider: ToolProvider::Local(tool),
+        })
+    }
+
+    /// Registers all tools of an MCP client, namespaced by the server name if there is one.
+    ///
+    /// No tool is registered when one of them conflicts with an already registered tool.
+    pub fn register_mcp(
+        &mut self,
+        server: Option<&str>,
+        client: MCPClient,
+    ) -> Result<(), ToolRegistryError> {
+        let client = Arc::new(client);
+        let mut definitions: Vec<&ToolDefinition> = client.tools.values().collect();
+        definitions.sort_by(|a, b| a.name.cmp(&b.name));
+        let tools = definitions
+            .into_iter()
+            .map(|definition| {
+                let name = match server {
+                    Some(server) => format!("{server}{NAMESPACE_SEPARATOR}{}", definition.name),
+                    None => definition.name.clone(),
+                };
+                RegisteredTool {
+                    definition: ToolDefinition {
+                        name: name.clone(),
+                        ..definition.clone()
+                    },
+                    name,
+                    source: ToolSource::Mcp(server.map(|server| server.to_string())),
+                    enabled: true,
+                    provider: ToolProvider::Mcp {
+                        client: client.clone(),
+                        tool: definition.name.clone(),
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+        for (i, tool) in tools.iter().enumerate() {
+            self.check_conflict(tool)?;
+            if let Some(existing) = tools[..i]
+                .iter()
+                .find(|t| t.name.eq_ignore_ascii_case(&tool.name))
+            {
+                return Err(ToolRegistryError::Conflict {
+                    name: tool.name.clone(),
+                    existing: existing.source.clone(),
+                    new: tool.source.clone(),
+                });
+            }
+        }
+        self.tools.extend(tools);
+        Ok(())
+    }
+
+    /// Removes all MCP tools, keeping the local ones.
+    pub fn remove_mcp_tools(&mut self) {
+        self.tools
+            .retain(|tool| matches!(tool.source, ToolSource::Local));
+    }
+
+    /// Enables a tool, so that it is offered to the model again.
+    pub fn enable(&mut self, name: &str) -> Result<(), ToolRegistryError> {
+        self.set_enabled(name, true)
+    }
+
+    /// Disables a tool, so that it is neither offered to the model nor run.
+    pub fn disable(&mut self, name: &str) -> Result<(), ToolRegistryError> {
+        self.set_enabled(name, false)
+    }
+
+    /// Returns the enabled tool with the given name, matched exactly first and then
+    /// ignoring the case.
+    pub fn get(&self, name: &str) -> Option<&RegisteredTool> {
+        self.tools
+            .iter()
+            .filter(|tool| tool.enabled)
+            .find(|tool| tool.name == name)
+            .or_else(|| {
+                self.tools
+                    .iter()
+                    .filter(|tool| tool.enabled)
+                    .find(|tool| tool.name.eq_ignore_ascii_case(name))
+            })
+    }
+
+    /// Returns all registered tools, including the disabled ones, in registration order.
+    pub fn tools(&self) -> impl Iterator<Item = &RegisteredTool> {
+        self.tools.iter()
+    }
+
+    /// Returns the definitions of the enabled tools.
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.tools
+            .iter()
+            .filter(|tool| tool.enabled)
+            .map(|tool| tool.definition.clone())
+            .collect()
+    }
+
+    /// Returns the number of registered tools.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.tools.len()
+    }
+
+    /// Returns `true` if no tool is registered.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    fn insert(&mut self, tool: RegisteredTool) -> Result<(), ToolRegistryError> {
+        self.check_conflict(&tool)?;
+        self.tools.push(tool);
+        Ok(())
+    }
+
+    fn check_conflict(&self, tool: &RegisteredTool) -> Result<(), ToolRegistryError> {
+        match self
+            .tools
+            .iter()
+            .find(|t| t.name.eq_ignore_ascii_case(&tool.name))
+        {
+            Some(existing) => Err(ToolRegistryError::Conflict {
+                name: tool.name.clone(),
+                existing: existing.source.clone(),
+                new: tool.source.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<(), ToolRegistryError> {
+        let tool = self
+            .tools
+            .iter_mut()
+            .find(|tool| tool.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| ToolRegistryError::NotFound(name.to_string()))?;
+        tool.enabled = enabled;
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ToolRegistryError {
+    #[error("Tool `{name}` from {new} conflicts with the tool registered from {existing}")]
+    Conflict {
+        name: String,
+        existing: ToolSource,
+        new: ToolSource,
+    },
+    #[error("Tool not found: {0}")]
+    NotFound(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tool::StructureTool;
+    use async_trait::async_trait;
+    use schemars::JsonSchema;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(JsonSchema, Serialize, Deserialize)]
+    pub struct EchoInput {
+        pub text: String,
+    }
+
+    pub struct Echo(&'static str);
+
+    #[async_trait]
+    impl StructureTool for Echo {
+        type Input = EchoInput;
+        type Output = String;
+
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        async fn run_with_args(&self, input: Self::Input) -> Result<Self::Output, ToolError> {
+            Ok(input.text)
+        }
+    }
+
+    #[test]
+    fn test_tool_registry_conflicts() {
+        let mut registry = ToolRegistry::new();
+        registry.register(Box::new(Echo("echo"))).unwrap();
+        assert!(matches!(
+            registry.register(Box::new(Echo("Echo"))),
+            Err(ToolRegistryError::Conflict { .. })
+        ));
+        assert_eq!(registry.len(), 1);
+        assert_eq!(registry.get("ECHO").unwrap().source(), &ToolSource::Local);
+
+        registry.disable("ECHO").unwrap();
+        assert!(registry.get("echo").is_none());
+        assert!(registry.definitions().is_empty());
+        registry.enable("Echo").unwrap();
+        assert_eq!(registry.definitions()[0].name, "echo");
+        assert!(registry.disable("missing").is_err());
+    }
+}
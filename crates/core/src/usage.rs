@@ -0,0 +1,460 @@
+use crate::chat::TokenUsage;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The kind of event recorded in a [`UsageLedger`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageKind {
+    /// A request to the model.
+    #[default]
+    Completion,
+    /// A tool call requested by the model.
+    ToolCall,
+}
+
+/// A usage event of an agent run.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    /// The time of the event, in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The ID of the run, shared by all events of one prompt.
+    pub run_id: String,
+    /// The name of the agent.
+    pub agent: String,
+    /// The session of the agent, if any.
+    pub session_id: Option<String>,
+    /// The ID of the model.
+    pub model: String,
+    pub kind: UsageKind,
+    /// The name of the called tool, for tool calls.
+    pub tool: Option<String>,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub cached_tokens: u32,
+    /// The cost of the event, `None` when the model has no price.
+    pub cost: Option<f64>,
+}
+
+impl UsageRecord {
+    /// Creates the record of a model request.
+    pub fn completion(context: &UsageContext, usage: &TokenUsage) -> Self {
+        Self {
+            kind: UsageKind::Completion,
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            cached_tokens: usage.tokens_cached.unwrap_or_default(),
+            ..context.record()
+        }
+    }
+
+    /// Creates the record of a tool call.
+    pub fn tool_call(context: &UsageContext, tool: impl ToString) -> Self {
+        Self {
+            kind: UsageKind::ToolCall,
+            tool: Some(tool.to_string()),
+            ..context.record()
+        }
+    }
+
+    /// The total number of tokens of the event.
+    #[inline]
+    pub fn total_tokens(&self) -> u32 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+/// The attribution of the usage events of a run.
+#[derive(Debug, Clone, Default)]
+pub struct UsageContext {
+    pub run_id: String,
+    pub agent: String,
+    pub session_id: Option<String>,
+    pub model: String,
+}
+
+impl UsageContext {
+    fn record(&self) -> UsageRecord {
+        UsageRecord {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as u64),
+            run_id: self.run_id.clone(),
+            agent: self.agent.clone(),
+            session_id: self.session_id.clone(),
+            model: self.model.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// The price of a model, in currency units per million tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub prompt: f64,
+    pub completion: f64,
+    /// The price of cached prompt tokens, the prompt price by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_prompt: Option<f64>,
+}
+
+impl ModelPrice {
+    /// Computes the cost of a usage record.
+    pub fn cost(&self, record: &UsageRecord) -> f64 {
+        let cached = record.cached_tokens.min(record.prompt_tokens) as f64;
+        let uncached = record.prompt_tokens as f64 - cached;
+        (uncached * self.prompt
+            + cached * self.cached_prompt.unwrap_or(self.prompt)
+            + record.completion_tokens as f64 * self.completion)
+            / 1_000_000.0
+    }
+}
+
+/// The prices of models keyed by their IDs.
+///
+/// A model without an exact entry uses the price of the longest model ID prefix, e.g.
+/// `gpt-4o-2024-08-06` uses the price of `gpt-4o`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PricingTable {
+    prices: HashMap<String, ModelPrice>,
+}
+
+impl PricingTable {
+    /// Creates an empty pricing table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the price of a model.
+    pub fn price(mut self, model: impl ToString, price: ModelPrice) -> Self {
+        self.prices.insert(model.to_string(), price);
+        self
+    }
+
+    /// Parses a pricing table from a JSON object keyed by model ID.
+    pub fn from_json(s: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(s)
+    }
+
+    /// Returns the price of a model.
+    pub fn get(&self, model: &str) -> Option<&ModelPrice> {
+        self.prices.get(model).or_else(|| {
+            self.prices
+                .iter()
+                .filter(|(id, _)| model.starts_with(id.as_str()))
+                .max_by_key(|(id, _)| id.len())
+                .map(|(_, price)| price)
+        })
+    }
+
+    /// Computes the cost of a usage record, `None` when the model has no price.
+    pub fn cost(&self, record: &UsageRecord) -> Option<f64> {
+        self.get(&record.model).map(|price| price.cost(record))
+    }
+}
+
+/// A filter of the records of a [`UsageLedger`], all fields set must match.
+#[derive(Debug, Clone, Default)]
+pub struct UsageQuery {
+    run_id: Option<String>,
+    agent: Option<String>,
+    session_id: Option<String>,
+    model: Option<String>,
+    kind: Option<UsageKind>,
+    since: Option<u64>,
+    until: Option<u64>,
+}
+
+impl UsageQuery {
+    /// Creates a query matching all records.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn run_id(mut self, run_id: impl ToString) -> Self {
+        self.run_id = Some(run_id.to_string());
+        self
+    }
+
+    pub fn agent(mut self, agent: impl ToString) -> Self {
+        self.agent = Some(agent.to_string());
+        self
+    }
+
+    pub fn session_id(mut self, session_id: impl ToString) -> Self {
+        self.session_id = Some(session_id.to_string());
+        self
+    }
+
+    pub fn model(mut self, model: impl ToString) -> Self {
+        self.model = Some(model.to_string());
+        self
+    }
+
+    pub fn kind(mut self, kind: UsageKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Matches the records at or after the timestamp, in milliseconds since the Unix epoch.
+    pub fn since(mut self, timestamp: u64) -> Self {
+        self.since = Some(timestamp);
+        self
+    }
+
+    /// Matches the records before the timestamp, in milliseconds since the Unix epoch.
+    pub fn until(mut self, timestamp: u64) -> Self {
+        self.until = Some(timestamp);
+        self
+    }
+
+    /// Returns `true` if the record matches the query.
+    pub fn matches(&self, record: &UsageRecord) -> bool {
+        self.run_id.as_ref().is_none_or(|id| *id == record.run_id)
+            && self
+                .agent
+                .as_ref()
+                .is_none_or(|agent| *agent == record.agent)
+            && self
+                .session_id
+                .as_ref()
+                .is_none_or(|id| Some(id) == record.session_id.as_ref())
+            && self
+                .model
+                .as_ref()
+                .is_none_or(|model| *model == record.model)
+            && self.kind.is_none_or(|kind| kind == record.kind)
+            && self.since.is_none_or(|since| record.timestamp >= since)
+            && self.until.is_none_or(|until| record.timestamp < until)
+    }
+}
+
+/// The dimension the records are grouped by in [`UsageLedger::summary_by`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageGroup {
+    Run,
+    Agent,
+    Session,
+    Model,
+    Tool,
+}
+
+/// The usage totals of a set of records.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageSummary {
+    pub requests: usize,
+    pub tool_calls: usize,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cached_tokens: u64,
+    pub total_tokens: u64,
+    /// The cost of the priced records.
+    pub cost: f64,
+}
+
+impl UsageSummary {
+    fn add(&mut self, record: &UsageRecord) {
+        match record.kind {
+            UsageKind::Completion => self.requests += 1,
+            UsageKind::ToolCall => self.tool_calls += 1,
+        }
+        self.prompt_tokens += record.prompt_tokens as u64;
+        self.completion_tokens += record.completion_tokens as u64;
+        self.cached_tokens += record.cached_tokens as u64;
+        self.total_tokens += record.total_tokens() as u64;
+        self.cost += record.cost.unwrap_or_default();
+    }
+}
+
+/// Records the token usage and cost of agent runs.
+///
+/// A ledger is shared between agents with an `Arc`, so that the usage of all of them can be
+/// queried and exported together, e.g. for chargeback.
+#[derive(Debug, Default)]
+pub struct UsageLedger {
+    records: Mutex<Vec<UsageRecord>>,
+    pricing: PricingTable,
+}
+
+impl UsageLedger {
+    /// Creates an empty ledger without prices.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty ledger computing the cost of the records with the pricing table.
+    pub fn with_pricing(pricing: PricingTable) -> Self {
+        Self {
+            records: Mutex::default(),
+            pricing,
+        }
+    }
+
+    /// The pricing table of the ledger.
+    #[inline]
+    pub fn pricing(&self) -> &PricingTable {
+        &self.pricing
+    }
+
+    /// Records a usage event, computing its cost if it is not set.
+    pub fn record(&self, mut record: UsageRecord) {
+        if record.cost.is_none() {
+            record.cost = self.pricing.cost(&record);
+        }
+        self.records().push(record);
+    }
+
+    /// Returns the records matching the query, in recording order.
+    pub fn query(&self, query: &UsageQuery) -> Vec<UsageRecord> {
+        self.records()
+            .iter()
+            .filter(|record| query.matches(record))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the usage totals of the records matching the query.
+    pub fn summary(&self, query: &UsageQuery) -> UsageSummary {
+        let mut summary = UsageSummary::default();
+        for record in self.records().iter().filter(|record| query.matches(record)) {
+            summary.add(record);
+        }
+        summary
+    }
+
+    /// Returns the usage totals of the records matching the query, grouped by a dimension.
+    ///
+    /// Records without a session or a tool are grouped under an empty key.
+    pub fn summary_by(
+        &self,
+        group: UsageGroup,
+        query: &UsageQuery,
+    ) -> BTreeMap<String, UsageSummary> {
+        let mut summaries: BTreeMap<String, UsageSummary> = BTreeMap::new();
+        for record in self.records().iter().filter(|record| query.matches(record)) {
+            let key = match group {
+                UsageGroup::Run => record.run_id.clone(),
+                UsageGroup::Agent => record.agent.clone(),
+                UsageGroup::Session => record.session_id.clone().unwrap_or_default(),
+                UsageGroup::Model => record.model.clone(),
+                UsageGroup::Tool => record.tool.clone().unwrap_or_default(),
+            };
+            summaries.entry(key).or_default().add(record);
+        }
+        summaries
+    }
+
+    /// Removes all records.
+    pub fn clear(&self) {
+        self.records().clear();
+    }
+
+    /// Exports all records as a JSON array.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(&*self.records())
+    }
+
+    /// Exports all records as CSV with a header row.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "timestamp,run_id,agent,session_id,model,kind,tool,prompt_tokens,completion_tokens,cached_tokens,cost\n",
+        );
+        for record in self.records().iter() {
+            let kind = match record.kind {
+                UsageKind::Completion => "completion",
+                UsageKind::ToolCall => "tool_call",
+            };
+            let fields = [
+                record.timestamp.to_string(),
+                csv_field(&record.run_id),
+                csv_field(&record.agent),
+                csv_field(record.session_id.as_deref().unwrap_or_default()),
+                csv_field(&record.model),
+                kind.to_string(),
+                csv_field(record.tool.as_deref().unwrap_or_default()),
+                record.prompt_tokens.to_string(),
+                record.completion_tokens.to_string(),
+                record.cached_tokens.to_string(),
+                record.cost.map(|cost| cost.to_string()).unwrap_or_default(),
+            ];
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    fn records(&self) -> MutexGuard<'_, Vec<UsageRecord>> {
+        self.records
+            .lock()
+            .unwrap_or_else(|err| panic!("UsageLedger Error - records not available: {err:?}"))
+    }
+}
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_ledger_cost_and_summary() {
+        let pricing = PricingTable::new().price(
+            "gpt-4o",
+            ModelPrice {
+                prompt: 2.5,
+                completion: 10.0,
+                cached_prompt: Some(1.25),
+            },
+        );
+        let ledger = UsageLedger::with_pricing(pricing);
+        let context = UsageContext {
+            run_id: "run-1".to_string(),
+            agent: "support, tier 1".to_string(),
+            session_id: None,
+            model: "gpt-4o-2024-08-06".to_string(),
+        };
+        ledger.record(UsageRecord::completion(
+            &context,
+            &TokenUsage {
+                tokens_cached: Some(400_000),
+                prompt_tokens: 1_000_000,
+                completion_tokens: 100_000,
+                total_tokens: 1_100_000,
+            },
+        ));
+        ledger.record(UsageRecord::tool_call(&context, "search"));
+
+        let summary = ledger.summary(&UsageQuery::new().agent("support, tier 1"));
+        assert_eq!(summary.requests, 1);
+        assert_eq!(summary.tool_calls, 1);
+        assert_eq!(summary.total_tokens, 1_100_000);
+        // 0.6M uncached * 2.5 + 0.4M cached * 1.25 + 0.1M completion * 10
+        assert!((summary.cost - 3.0).abs() < 1e-9);
+        assert_eq!(
+            ledger.summary_by(
+                UsageGroup::Tool,
+                &UsageQuery::new().kind(UsageKind::ToolCall)
+            )["search"]
+                .tool_calls,
+            1
+        );
+        assert!(
+            ledger
+                .to_csv()
+                .lines()
+                .nth(1)
+                .unwrap()
+                .contains("\"support, tier 1\"")
+        );
+    }
+}
@@ -60,7 +60,9 @@ impl Completion for MistralRsEngine {
                 _ => continue, // Just skip unknown roles
             };
         }
-        messages = messages.add_message(TextMessageRole::User, request.effective_prompt());
+        if !request.prompt.is_empty() {
+            messages = messages.add_message(TextMessageRole::User, request.effective_prompt());
+        }
         let mut tools = vec![];
         for tool in &request.tools {
             tools.push(Tool {
@@ -4,8 +4,8 @@ use alith_core::chat::{
     Completion, Message, Request, ResponseContent, ResponseTokenUsage, ResponseToolCalls,
 };
 use alith_core::interface::llms::api::openai::completion::{
-    ChatChoice, ChatCompletionResponseMessage, CompletionRequestMessage, CompletionUsage,
-    FinishReason, OpenAICompletionRequest, OpenAICompletionResponse, Role,
+    ChatChoice, ChatCompletionResponseMessage, CompletionUsage, FinishReason,
+    OpenAICompletionRequest, OpenAICompletionResponse, Role,
 };
 use alith_core::interface::requests::completion::tool::{Function, ToolCall};
 use alith_core::tool::ToolDefinition;
@@ -147,11 +147,14 @@ impl<M: Completion + Send + Sync> Server<M> {
             .completion(Request {
                 prompt: "".to_string(),
                 preamble: "".to_string(),
-                history: unsafe {
-                    std::mem::transmute::<Vec<CompletionRequestMessage>, Vec<Message>>(
-                        messages.clone(),
-                    )
-                },
+                history: messages
+                    .into_iter()
+                    .map(|message| Message {
+                        role: message.role,
+                        content: message.content,
+                        ..Default::default()
+                    })
+                    .collect(),
                 max_tokens,
                 temperature,
                 top_p,
@@ -0,0 +1,251 @@
+//! Record and replay of model calls.
+//!
+//! A [`Cassette`] in record mode appends each request and response pair to a JSON Lines
+//! file. In replay mode it serves the recorded responses by request hash, so that tests run
+//! deterministically without network access or API keys.
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// How replayed requests are matched against the recorded ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchMode {
+    /// A request must hash exactly like a recorded one, so any prompt drift fails the call.
+    Strict,
+    /// A request without an exact match is served the next recording not replayed yet, with
+    /// a warning.
+    Lenient,
+}
+
+/// Whether a cassette records the calls or replays them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    Record,
+    Replay(MatchMode),
+}
+
+/// A recorded request and response pair.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    /// The hash of the request, see [`request_hash`].
+    pub hash: String,
+    pub request: Value,
+    pub response: Value,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CassetteError {
+    #[error("Cassette IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Cassette JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("No recorded interaction matches the request with hash {hash}")]
+    NoMatch { hash: String },
+    #[error("All recorded interactions have been replayed, no match for hash {hash}")]
+    Exhausted { hash: String },
+    #[error("The cassette is not in replay mode")]
+    NotReplaying,
+}
+
+/// A file of recorded request and response pairs, one JSON interaction per line.
+///
+/// Cassettes are shared with `Arc` between the clients recording into or replaying from
+/// them. Each recorded interaction is appended to the file right away.
+#[derive(Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    state: Mutex<CassetteState>,
+}
+
+#[derive(Debug, Default)]
+struct CassetteState {
+    interactions: Vec<Interaction>,
+    /// Whether each interaction has been replayed.
+    replayed: Vec<bool>,
+    /// The file recorded into, created by the first recording.
+    file: Option<File>,
+}
+
+impl Cassette {
+    /// Creates a cassette recording into the file, which is overwritten by the first recording.
+    pub fn record(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            mode: CassetteMode::Record,
+            state: Mutex::new(CassetteState::default()),
+        }
+    }
+
+    /// Loads a cassette replaying the interactions recorded in the file.
+    pub fn replay(path: impl AsRef<Path>, match_mode: MatchMode) -> Result<Self, CassetteError> {
+        let interactions = std::fs::read_to_string(path.as_ref())?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<Interaction>, _>>()?;
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            mode: CassetteMode::Replay(match_mode),
+            state: Mutex::new(CassetteState {
+                replayed: vec![false; interactions.len()],
+                interactions,
+                file: None,
+            }),
+        })
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[inline]
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    #[inline]
+    pub fn is_recording(&self) -> bool {
+        self.mode == CassetteMode::Record
+    }
+
+    /// Returns the recorded interactions.
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.state.lock().unwrap().interactions.clone()
+    }
+
+    /// Returns the number of recorded interactions not replayed yet.
+    pub fn remaining(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        state.replayed.iter().filter(|replayed| !**replayed).count()
+    }
+
+    /// Records a request and response pair and appends it to the cassette file.
+    pub fn record_interaction(&self, request: Value, response: Value) -> Result<(), CassetteError> {
+        let interaction = Interaction {
+            hash: request_hash(&request),
+            request,
+            response,
+        };
+        let mut line = serde_json::to_vec(&interaction)?;
+        line.push(b'\n');
+        let mut state = self.state.lock().unwrap();
+        let file = match &mut state.file {
+            Some(file) => file,
+            file => {
+                if let Some(parent) = self.path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                file.insert(File::create(&self.path)?)
+            }
+        };
+        file.write_all(&line)?;
+        state.interactions.push(interaction);
+        state.replayed.push(false);
+        Ok(())
+    }
+
+    /// Returns the recorded response of a request.
+    ///
+    /// Identical requests are served their recordings in recording order. In lenient mode, a
+    /// request without a match is served the next recording not replayed yet.
+    pub fn replay_interaction(&self, request: &Value) -> Result<Value, CassetteError> {
+        let CassetteMode::Replay(match_mode) = self.mode else {
+            return Err(CassetteError::NotReplaying);
+        };
+        let hash = request_hash(request);
+        let mut state = self.state.lock().unwrap();
+        let index = match (state.next(Some(&hash)), match_mode) {
+            (Some(index), _) => index,
+            (None, MatchMode::Strict) => return Err(CassetteError::NoMatch { hash }),
+            (None, MatchMode::Lenient) => match state.next(None) {
+                Some(index) => {
+                    tracing::warn!(
+                        "Cassette request {hash} replayed with the recording {}, the request changed",
+                        state.interactions[index].hash
+                    );
+                    index
+                }
+                None => return Err(CassetteError::Exhausted { hash }),
+            },
+        };
+        state.replayed[index] = true;
+        Ok(state.interactions[index].response.clone())
+    }
+}
+
+impl CassetteState {
+    /// Returns the index of the first interaction not replayed yet, with the hash if any.
+    fn next(&self, hash: Option<&str>) -> Option<usize> {
+        self.interactions
+            .iter()
+            .zip(&self.replayed)
+            .position(|(interaction, replayed)| {
+                !replayed && hash.is_none_or(|hash| interaction.hash == hash)
+            })
+    }
+}
+
+/// Returns the hash of a request, a 64-bit FNV-1a hash of its JSON encoding.
+///
+/// The hash is stable across runs and platforms, so that recordings can be committed.
+pub fn request_hash(request: &Value) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in request.to_string().bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{hash:016x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_cassette_record_replay() {
+        let path = std::env::temp_dir().join(format!(
+            "alith-cassette-{}-{}.jsonl",
+            std::process::id(),
+            request_hash(&json!(file!()))
+        ));
+        let cassette = Cassette::record(&path);
+        cassette
+            .record_interaction(json!({"prompt": "a"}), json!("A"))
+            .unwrap();
+        cassette
+            .record_interaction(json!({"prompt": "b"}), json!("B"))
+            .unwrap();
+
+        let strict = Cassette::replay(&path, MatchMode::Strict).unwrap();
+        assert_eq!(
+            strict.replay_interaction(&json!({"prompt": "b"})).unwrap(),
+            json!("B")
+        );
+        assert!(matches!(
+            strict.replay_interaction(&json!({"prompt": "c"})),
+            Err(CassetteError::NoMatch { .. })
+        ));
+        assert_eq!(strict.remaining(), 1);
+
+        let lenient = Cassette::replay(&path, MatchMode::Lenient).unwrap();
+        assert_eq!(
+            lenient.replay_interaction(&json!({"prompt": "c"})).unwrap(),
+            json!("A")
+        );
+        assert_eq!(
+            lenient.replay_interaction(&json!({"prompt": "b"})).unwrap(),
+            json!("B")
+        );
+        assert!(matches!(
+            lenient.replay_interaction(&json!({"prompt": "a"})),
+            Err(CassetteError::Exhausted { .. })
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
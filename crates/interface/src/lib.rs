@@ -3,6 +3,7 @@ pub(crate) use anyhow::{Error, Result, anyhow, bail};
 #[allow(unused_imports)]
 pub(crate) use tracing::{Level, debug, error, info, span, trace, warn};
 
+pub mod cassette;
 pub mod llms;
 pub mod requests;
 
@@ -18,8 +19,9 @@ pub use llms::{
 };
 pub use requests::{
     completion::{
-        CompletionError, CompletionFinishReason, CompletionRequest, CompletionResponse,
-        TimingUsage, TokenUsage, ToolChoice, ToolDefinition,
+        CompletionDelta, CompletionDeltaAccumulator, CompletionError, CompletionFinishReason,
+        CompletionRequest, CompletionResponse, CompletionStream, JsonSchemaFormat, ResponseFormat,
+        TimingUsage, TokenUsage, ToolCallDelta, ToolChoice, ToolDefinition,
     },
     embeddings::{EmbeddingsData, EmbeddingsError, EmbeddingsRequest, EmbeddingsResponse},
     logit_bias::{LogitBias, LogitBiasTrait},
@@ -1,4 +1,9 @@
 mod req;
 mod res;
+mod stream;
 pub use req::AnthropicCompletionRequest;
 pub use res::AnthropicCompletionResponse;
+pub use stream::{
+    AnthropicStreamEvent, ContentBlockDelta, ContentBlockStart, MessageDelta, MessageDeltaUsage,
+    MessageStart, completion_stream,
+};
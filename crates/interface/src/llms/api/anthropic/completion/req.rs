@@ -1,5 +1,7 @@
 use crate::requests::completion::{error::CompletionError, request::CompletionRequest};
+use alith_prompt::PromptToolCall;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Clone, Serialize, Default, Debug, Deserialize)]
 pub struct AnthropicCompletionRequest {
@@ -52,44 +54,18 @@ pub struct AnthropicCompletionRequest {
     /// The tools for the request, default: None
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<Tool>>,
+
+    /// Whether to incrementally stream the response using server-sent events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
 }
 
 impl AnthropicCompletionRequest {
     pub fn new(req: &CompletionRequest) -> crate::Result<Self, CompletionError> {
-        let mut messages = Vec::new();
-        let mut system_prompt = None;
-        match req.prompt.get_built_prompt_messages() {
-            Ok(prompt_message) => {
-                for m in prompt_message {
-                    let role = m.get("role").ok_or_else(|| {
-                        CompletionError::RequestBuilderError("Role not found".to_string())
-                    })?;
-                    let content = m.get("content").ok_or_else(|| {
-                        CompletionError::RequestBuilderError("Content not found".to_string())
-                    })?;
-
-                    match role.as_str() {
-                        "user" | "assistant" => messages.push(CompletionRequestMessage {
-                            role: role.to_string(),
-                            content: content.to_string(),
-                        }),
-                        "system" => system_prompt = Some(content.to_string()),
-                        _ => {
-                            return Err(CompletionError::RequestBuilderError(format!(
-                                "Role {} not supported",
-                                role
-                            )));
-                        }
-                    }
-                }
-            }
-            Err(e) => {
-                return Err(CompletionError::RequestBuilderError(format!(
-                    "Error building prompt: {}",
-                    e
-                )));
-            }
-        }
+        let prompt_messages = req.prompt.get_built_prompt_messages().map_err(|e| {
+            CompletionError::RequestBuilderError(format!("Error building prompt: {}", e))
+        })?;
+        let (messages, system_prompt) = request_messages(&prompt_messages)?;
 
         let stop = req.stop_sequences.to_vec();
         let stop_sequences = if stop.is_empty() { None } else { Some(stop) };
@@ -116,10 +92,109 @@ impl AnthropicCompletionRequest {
             } else {
                 None
             },
+            stream: None,
         })
     }
 }
 
+/// Converts the built prompt messages into the input messages and the system prompt.
+fn request_messages(
+    prompt_messages: &[HashMap<String, String>],
+) -> crate::Result<(Vec<CompletionRequestMessage>, Option<String>), CompletionError> {
+    let mut messages = Vec::new();
+    let mut system_prompt = None;
+    for m in prompt_messages {
+        let role = m
+            .get("role")
+            .ok_or_else(|| CompletionError::RequestBuilderError("Role not found".to_string()))?;
+        let content = m
+            .get("content")
+            .ok_or_else(|| CompletionError::RequestBuilderError("Content not found".to_string()))?;
+
+        match role.as_str() {
+            "user" => push_user_content(
+                &mut messages,
+                RequestContentBlock::Text {
+                    text: content.to_string(),
+                },
+            ),
+            "assistant" => {
+                let tool_calls = PromptToolCall::from_built_prompt_message(m)
+                    .map_err(|e| CompletionError::RequestBuilderError(e.to_string()))?;
+                let content = if tool_calls.is_empty() {
+                    RequestContent::Text(content.to_string())
+                } else {
+                    let mut blocks = Vec::with_capacity(tool_calls.len() + 1);
+                    if !content.is_empty() {
+                        blocks.push(RequestContentBlock::Text {
+                            text: content.to_string(),
+                        });
+                    }
+                    for call in tool_calls {
+                        let input = serde_json::from_str(&call.arguments).map_err(|e| {
+                            CompletionError::RequestBuilderError(format!(
+                                "Invalid arguments of tool call {}: {}",
+                                call.id, e
+                            ))
+                        })?;
+                        blocks.push(RequestContentBlock::ToolUse {
+                            id: call.id,
+                            name: call.name,
+                            input,
+                        });
+                    }
+                    RequestContent::Blocks(blocks)
+                };
+                messages.push(CompletionRequestMessage {
+                    role: role.to_string(),
+                    content,
+                });
+            }
+            // Tool results are sent as blocks of the following user message.
+            "tool" => push_user_content(
+                &mut messages,
+                RequestContentBlock::ToolResult {
+                    tool_use_id: m
+                        .get("tool_call_id")
+                        .ok_or_else(|| {
+                            CompletionError::RequestBuilderError(
+                                "Tool call ID not found".to_string(),
+                            )
+                        })?
+                        .to_string(),
+                    content: content.to_string(),
+                },
+            ),
+            "system" => system_prompt = Some(content.to_string()),
+            _ => {
+                return Err(CompletionError::RequestBuilderError(format!(
+                    "Role {} not supported",
+                    role
+                )));
+            }
+        }
+    }
+    Ok((messages, system_prompt))
+}
+
+/// Adds a content block to the trailing user message, or to a new user message if the last
+/// message is not from the user, since the API does not accept consecutive user messages.
+fn push_user_content(messages: &mut Vec<CompletionRequestMessage>, block: RequestContentBlock) {
+    match messages.last_mut() {
+        Some(CompletionRequestMessage {
+            role,
+            content: RequestContent::Blocks(blocks),
+        }) if role == "user" => blocks.push(block),
+        _ => messages.push(CompletionRequestMessage {
+            role: "user".to_string(),
+            content: match block {
+                RequestContentBlock::Text { text } => RequestContent::Text(text),
+                block => RequestContent::Blocks(vec![block]),
+            },
+        }),
+    }
+}
+
 /// Convert the native temperature from 0.0 to 2.0 to 0.0 to 1.0
 fn temperature(value: f32) -> crate::Result<f32, CompletionError> {
     if (0.0..=2.0).contains(&value) {
@@ -149,7 +224,34 @@ fn top_p(value: Option<f32>) -> crate::Result<Option<f32>, CompletionError> {
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct CompletionRequestMessage {
     pub role: String,
-    pub content: String,
+    pub content: RequestContent,
+}
+
+/// The content of an input message, either a single text or a list of content blocks.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum RequestContent {
+    Text(String),
+    Blocks(Vec<RequestContentBlock>),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RequestContentBlock {
+    Text {
+        text: String,
+    },
+    /// A tool call requested by an assistant message.
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    /// The result of a tool call, sent in a user message.
+    ToolResult {
+        tool_use_id: String,
+        content: String,
+    },
 }
 
 #[derive(Clone, Serialize, Default, Debug, Deserialize)]
@@ -158,3 +260,74 @@ pub struct Tool {
     pub description: String,
     pub input_schema: serde_json::Value,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn message(fields: &[(&str, &str)]) -> HashMap<String, String> {
+        fields
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_request_messages_tool_calls() {
+        let tool_calls = json!([
+            {"id": "call_1", "name": "weather", "arguments": r#"{"city":"Paris"}"#},
+            {"id": "call_2", "name": "weather", "arguments": r#"{"city":"Rome"}"#},
+        ])
+        .to_string();
+        let (messages, system) = request_messages(&[
+            message(&[("role", "system"), ("content", "Be brief.")]),
+            message(&[("role", "user"), ("content", "Weather?")]),
+            message(&[
+                ("role", "assistant"),
+                ("content", ""),
+                ("tool_calls", &tool_calls),
+            ]),
+            message(&[
+                ("role", "tool"),
+                ("content", "Sunny"),
+                ("tool_call_id", "call_1"),
+            ]),
+            message(&[
+                ("role", "tool"),
+                ("content", "Rainy"),
+                ("tool_call_id", "call_2"),
+            ]),
+        ])
+        .unwrap();
+        assert_eq!(system.as_deref(), Some("Be brief."));
+        assert_eq!(
+            serde_json::to_value(&messages).unwrap(),
+            json!([
+                {"role": "user", "content": "Weather?"},
+                {"role": "assistant", "content": [
+                    {"type": "tool_use", "id": "call_1", "name": "weather", "input": {"city": "Paris"}},
+                    {"type": "tool_use", "id": "call_2", "name": "weather", "input": {"city": "Rome"}},
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "call_1", "content": "Sunny"},
+                    {"type": "tool_result", "tool_use_id": "call_2", "content": "Rainy"},
+                ]},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_request_messages_invalid_arguments() {
+        let tool_calls =
+            json!([{"id": "call_1", "name": "weather", "arguments": "{\"city\":"}]).to_string();
+        assert!(matches!(
+            request_messages(&[message(&[
+                ("role", "assistant"),
+                ("content", ""),
+                ("tool_calls", &tool_calls),
+            ])]),
+            Err(CompletionError::RequestBuilderError(message)) if message.contains("call_1")
+        ));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::requests::completion::*;
+use crate::requests::{completion::*, stop_sequence::StopSequences};
 use serde::{Deserialize, Serialize};
 use tool::{Function, ToolCall};
 
@@ -7,26 +7,9 @@ impl CompletionResponse {
         req: &CompletionRequest,
         res: AnthropicCompletionResponse,
     ) -> Result<Self, CompletionError> {
-        let finish_reason = match res.stop_reason {
-            StopReason::EndTurn => CompletionFinishReason::Eos,
-            StopReason::StopSequence => {
-                if let Some(stopping_string) = &res.stop_sequence {
-                    if let Some(stop_sequence) =
-                        req.stop_sequences.parse_string_response(stopping_string)
-                    {
-                        CompletionFinishReason::MatchingStoppingSequence(stop_sequence)
-                    } else {
-                        CompletionFinishReason::NonMatchingStoppingSequence(Some(
-                            stopping_string.clone(),
-                        ))
-                    }
-                } else {
-                    CompletionFinishReason::NonMatchingStoppingSequence(None)
-                }
-            }
-            StopReason::MaxTokens => CompletionFinishReason::StopLimit,
-            StopReason::ToolUse => CompletionFinishReason::ToolsCall,
-        };
+        let finish_reason = res
+            .stop_reason
+            .into_completion_finish_reason(res.stop_sequence.as_ref(), &req.stop_sequences);
 
         if res.content.is_empty() {
             return Err(CompletionError::ResponseContentEmpty);
@@ -34,10 +17,30 @@ impl CompletionResponse {
 
         let content = res
             .content
-            .first()
-            .ok_or_else(|| CompletionError::ResponseContentEmpty)?
-            .text()
-            .to_owned();
+            .iter()
+            .map(|content| content.text())
+            .collect::<String>();
+        // A response may contain text before its tool calls and several tool calls.
+        let tool_calls = res
+            .content
+            .iter()
+            .filter_map(|content| match content {
+                CompletionContent::ToolUse {
+                    name,
+                    input,
+                    id,
+                    r#type,
+                } => Some(serde_json::to_string(input).map(|arguments| ToolCall {
+                    id: id.to_owned(),
+                    r#type: r#type.to_owned(),
+                    function: Function {
+                        name: name.to_owned(),
+                        arguments,
+                    },
+                })),
+                _ => None,
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
         Ok(Self {
             id: res.id.to_owned(),
@@ -49,25 +52,7 @@ impl CompletionResponse {
             generation_settings: GenerationSettings::new_from_anthropic(req, &res),
             timing_usage: TimingUsage::new_from_generic(req.start_time),
             token_usage: TokenUsage::new_from_anthropic(&res),
-            tool_calls: match res.content.as_slice() {
-                [
-                    CompletionContent::ToolUse {
-                        name,
-                        input,
-                        id,
-                        r#type,
-                    },
-                    ..,
-                ] => Some(vec![ToolCall {
-                    id: id.to_owned(),
-                    r#type: r#type.to_owned(),
-                    function: Function {
-                        name: name.to_owned(),
-                        arguments: serde_json::to_string(input)?,
-                    },
-                }]),
-                _ => None,
-            },
+            tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
         })
     }
 }
@@ -157,3 +142,32 @@ pub enum StopReason {
     /// Claude wants to use an external tool.
     ToolUse,
 }
+
+impl StopReason {
+    pub(crate) fn into_completion_finish_reason(
+        self,
+        stop_sequence: Option<&String>,
+        stop_sequences: &StopSequences,
+    ) -> CompletionFinishReason {
+        match self {
+            StopReason::EndTurn => CompletionFinishReason::Eos,
+            StopReason::StopSequence => {
+                if let Some(stopping_string) = stop_sequence {
+                    if let Some(stop_sequence) =
+                        stop_sequences.parse_string_response(stopping_string)
+                    {
+                        CompletionFinishReason::MatchingStoppingSequence(stop_sequence)
+                    } else {
+                        CompletionFinishReason::NonMatchingStoppingSequence(Some(
+                            stopping_string.clone(),
+                        ))
+                    }
+                } else {
+                    CompletionFinishReason::NonMatchingStoppingSequence(None)
+                }
+            }
+            StopReason::MaxTokens => CompletionFinishReason::StopLimit,
+            StopReason::ToolUse => CompletionFinishReason::ToolsCall,
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use super::res::{CompletionUsage, StopReason};
+use crate::llms::api::{
+    error::{ApiError, ClientError},
+    sse::SseStream,
+};
+use crate::requests::{completion::*, stop_sequence::StopSequences};
+use futures::StreamExt;
+use serde::Deserialize;
+
+/// A server-sent event of a streamed Anthropic message.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnthropicStreamEvent {
+    /// The message was created, carries the input token usage.
+    MessageStart { message: MessageStart },
+    /// A content block was started at `index`.
+    ContentBlockStart {
+        index: usize,
+        content_block: ContentBlockStart,
+    },
+    /// A fragment of the content block at `index`.
+    ContentBlockDelta {
+        index: usize,
+        delta: ContentBlockDelta,
+    },
+    /// The content block at `index` is complete.
+    ContentBlockStop { index: usize },
+    /// Top-level changes of the message, carries the stop reason and output token usage.
+    MessageDelta {
+        delta: MessageDelta,
+        usage: MessageDeltaUsage,
+    },
+    /// The message is complete.
+    MessageStop,
+    /// An error occurred while streaming, e.g. the API is overloaded.
+    Error { error: ApiError },
+    /// Keep-alive and unknown events are ignored.
+    #[serde(other)]
+    Ping,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MessageStart {
+    /// Unique object identifier.
+    pub id: String,
+    /// The usage so far, only the input tokens are final.
+    pub usage: CompletionUsage,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlockStart {
+    Text {
+        text: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+    },
+    #[serde(other)]
+    Unsupported,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlockDelta {
+    TextDelta {
+        text: String,
+    },
+    InputJsonDelta {
+        partial_json: String,
+    },
+    #[serde(other)]
+    Unsupported,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MessageDelta {
+    /// The reason that we stopped.
+    pub stop_reason: Option<StopReason>,
+    /// Which custom stop sequence was generated, if any.
+    pub stop_sequence: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MessageDeltaUsage {
+    /// The cumulative number of output tokens which were used.
+    pub output_tokens: u32,
+}
+
+/// Converts the server-sent events of an Anthropic message into completion deltas.
+pub fn completion_stream(mut events: SseStream, stop_sequences: StopSequences) -> CompletionStream {
+    Box::pin(async_stream::try_stream! {
+        let mut input_tokens = 0;
+        while let Some(event) = events.next().await {
+            let event: AnthropicStreamEvent = serde_json::from_str(&event?.data)?;
+            let delta = match event {
+                AnthropicStreamEvent::MessageStart { message } => {
+                    input_tokens = message.usage.input_tokens;
+                    continue;
+                }
+                AnthropicStreamEvent::ContentBlockStart {
+                    index,
+                    content_block,
+                } => match content_block {
+                    ContentBlockStart::Text { text } if !text.is_empty() => CompletionDelta {
+                        content: Some(text),
+                        ..Default::default()
+                    },
+                    ContentBlockStart::ToolUse { id, name } => CompletionDelta {
+                        tool_calls: vec![ToolCallDelta {
+                            index,
+                            id: Some(id),
+                            name: Some(name),
+                            arguments: String::new(),
+                        }],
+                        ..Default::default()
+                    },
+                    _ => continue,
+                },
+                AnthropicStreamEvent::ContentBlockDelta { index, delta } => match delta {
+                    ContentBlockDelta::TextDelta { text } => CompletionDelta {
+                        content: Some(text),
+                        ..Default::default()
+                    },
+                    ContentBlockDelta::InputJsonDelta { partial_json } => CompletionDelta {
+                        tool_calls: vec![ToolCallDelta {
+                            index,
+                            arguments: partial_json,
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    },
+                    ContentBlockDelta::Unsupported => continue,
+                },
+                AnthropicStreamEvent::MessageDelta { delta, usage } => CompletionDelta {
+                    finish_reason: delta.stop_reason.map(|stop_reason| {
+                        stop_reason.into_completion_finish_reason(
+                            delta.stop_sequence.as_ref(),
+                            &stop_sequences,
+                        )
+                    }),
+                    token_usage: Some(TokenUsage {
+                        tokens_cached: None,
+                        prompt_tokens: input_tokens,
+                        completion_tokens: usage.output_tokens,
+                        total_tokens: input_tokens + usage.output_tokens,
+                    }),
+                    ..Default::default()
+                },
+                AnthropicStreamEvent::MessageStop => break,
+                AnthropicStreamEvent::Error { error } => {
+                    Err(CompletionError::ClientError(ClientError::ApiError(error)))?
+                }
+                AnthropicStreamEvent::ContentBlockStop { .. } | AnthropicStreamEvent::Ping => {
+                    continue;
+                }
+            };
+            yield delta;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llms::api::sse::fixture_stream;
+    use futures::TryStreamExt;
+
+    const TOOL_USE_FIXTURE: &str = r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_1","type":"message","role":"assistant","content":[],"usage":{"input_tokens":12,"output_tokens":1}}}
+
+event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Let me "}}
+
+event: ping
+data: {"type":"ping"}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"compute."}}
+
+event: content_block_stop
+data: {"type":"content_block_stop","index":0}
+
+event: content_block_start
+data: {"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_a","name":"add","input":{}}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"a\":"}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"1,\"b\":2}"}}
+
+event: content_block_stop
+data: {"type":"content_block_stop","index":1}
+
+event: content_block_start
+data: {"type":"content_block_start","index":2,"content_block":{"type":"tool_use","id":"toolu_b","name":"now","input":{}}}
+
+event: content_block_stop
+data: {"type":"content_block_stop","index":2}
+
+event: message_delta
+data: {"type":"message_delta","delta":{"stop_reason":"tool_use","stop_sequence":null},"usage":{"output_tokens":30}}
+
+event: message_stop
+data: {"type":"message_stop"}
+"#;
+
+    #[tokio::test]
+    async fn test_completion_stream_tool_use() {
+        let deltas: Vec<CompletionDelta> =
+            completion_stream(fixture_stream(TOOL_USE_FIXTURE), StopSequences::default())
+                .try_collect()
+                .await
+                .unwrap();
+        let mut accumulator = CompletionDeltaAccumulator::default();
+        deltas.iter().for_each(|delta| accumulator.push(delta));
+
+        assert_eq!(accumulator.content, "Let me compute.");
+        let tool_calls = accumulator.tool_calls();
+        let tool_calls: Vec<(&str, &str, &str)> = tool_calls
+            .iter()
+            .map(|call| {
+                (
+                    call.id.as_str(),
+                    call.function.name.as_str(),
+                    call.function.arguments.as_str(),
+                )
+            })
+            .collect();
+        // The tool without arguments streams no fragment.
+        assert_eq!(
+            tool_calls,
+            vec![
+                ("toolu_a", "add", r#"{"a":1,"b":2}"#),
+                ("toolu_b", "now", "{}"),
+            ]
+        );
+        assert_eq!(
+            accumulator.finish_reason,
+            Some(CompletionFinishReason::ToolsCall)
+        );
+        // The input tokens of `message_start` are reported with the final output tokens.
+        assert_eq!(
+            accumulator.token_usage,
+            Some(TokenUsage {
+                tokens_cached: None,
+                prompt_tokens: 12,
+                completion_tokens: 30,
+                total_tokens: 42,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_completion_stream_error() {
+        let fixture = r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_1","usage":{"input_tokens":12,"output_tokens":1}}}
+
+event: error
+data: {"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}
+"#;
+        let result: Result<Vec<CompletionDelta>, _> =
+            completion_stream(fixture_stream(fixture), StopSequences::default())
+                .try_collect()
+                .await;
+        assert!(matches!(
+            result,
+            Err(CompletionError::ClientError(ClientError::ApiError(error))) if error.message == "Overloaded"
+        ));
+    }
+}
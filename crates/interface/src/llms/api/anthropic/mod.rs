@@ -5,14 +5,17 @@ use super::{
     client::ApiClient,
     config::{ApiConfig, ApiConfigTrait},
 };
+use crate::cassette::Cassette;
 use crate::requests::completion::{
     error::CompletionError, request::CompletionRequest, response::CompletionResponse,
+    stream::CompletionStream,
 };
 use alith_devices::logging::LoggingConfig;
 use alith_models::api_model::ApiLLMModel;
-use completion::AnthropicCompletionRequest;
+use completion::{AnthropicCompletionRequest, completion_stream};
 use reqwest::header::HeaderMap;
 use secrecy::{ExposeSecret, SecretString};
+use std::sync::Arc;
 
 /// Default v1 API base url
 pub const ANTHROPIC_API_HOST: &str = "api.anthropic.com/v1";
@@ -48,6 +51,18 @@ impl AnthropicBackend {
             Ok(res) => Ok(CompletionResponse::new_from_anthropic(request, res)?),
         }
     }
+
+    pub(crate) async fn completion_stream_request(
+        &self,
+        request: &CompletionRequest,
+    ) -> crate::Result<CompletionStream, CompletionError> {
+        let mut req = AnthropicCompletionRequest::new(request)?;
+        req.stream = Some(true);
+        match self.client.post_stream("/messages", req).await {
+            Err(e) => Err(CompletionError::ClientError(e)),
+            Ok(events) => Ok(completion_stream(events, request.stop_sequences.clone())),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -67,6 +82,7 @@ impl Default for AnthropicConfig {
                 port: None,
                 api_key: None,
                 api_key_env_var: "ANTHROPIC_API_KEY".to_string(),
+                cassette: None,
             },
             logging_config: LoggingConfig {
                 logger_name: "anthropic".to_string(),
@@ -136,4 +152,8 @@ impl ApiConfigTrait for AnthropicConfig {
     fn api_key(&self) -> &Option<SecretString> {
         &self.api_config.api_key
     }
+
+    fn cassette(&self) -> Option<&Arc<Cassette>> {
+        self.api_config.cassette.as_ref()
+    }
 }
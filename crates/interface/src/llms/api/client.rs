@@ -2,9 +2,11 @@ use super::error::map_serialization_error;
 use super::{
     config::ApiConfigTrait,
     error::{ClientError, WrappedError, map_deserialization_error},
+    sse::{SseDecoder, SseEvent, SseStream},
 };
 use bytes::Bytes;
 use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
 
 #[derive(Debug, Clone)]
 pub struct ApiClient<C: ApiConfigTrait> {
@@ -43,7 +45,86 @@ impl<C: ApiConfigTrait> ApiClient<C> {
                 .body(serialized_request);
             Ok(request_builder.build()?)
         };
-        self.execute(request_maker).await
+        match self.config.cassette() {
+            Some(cassette) => {
+                let key = json!({
+                    "method": "POST",
+                    "path": path,
+                    "body": serde_json::to_value(&request).map_err(map_serialization_error)?,
+                });
+                let response = if cassette.is_recording() {
+                    let response: Value = self.execute(request_maker).await?;
+                    cassette.record_interaction(key, response.clone())?;
+                    response
+                } else {
+                    cassette.replay_interaction(&key)?
+                };
+                serde_json::from_value(response).map_err(ClientError::JSONDeserialize)
+            }
+            None => self.execute(request_maker).await,
+        }
+    }
+
+    /// Make a streaming POST request to {path} and decode the response body as server-sent events
+    ///
+    /// Only establishing the connection is retried on rate limit, the events are yielded as
+    /// they arrive.
+    pub async fn post_stream<I>(&self, path: &str, request: I) -> Result<SseStream, ClientError>
+    where
+        I: Serialize + std::fmt::Debug,
+    {
+        let request_maker = || async {
+            let serialized_request =
+                serde_json::to_string(&request).map_err(map_serialization_error)?;
+            crate::trace!("Serialized post stream request: {}", serialized_request);
+            let request_builder = self
+                .http_client
+                .post(self.config.url(path))
+                .headers(self.config.headers())
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(reqwest::header::ACCEPT, "text/event-stream")
+                .body(serialized_request);
+            Ok(request_builder.build()?)
+        };
+        if let Some(cassette) = self.config.cassette() {
+            // The events of a recorded stream are all received before being yielded.
+            let key = json!({
+                "method": "POST",
+                "path": path,
+                "stream": true,
+                "body": serde_json::to_value(&request).map_err(map_serialization_error)?,
+            });
+            let events: Vec<SseEvent> = if cassette.is_recording() {
+                let mut response = self.send(request_maker).await?;
+                let mut decoder = SseDecoder::default();
+                let mut events = Vec::new();
+                while let Some(chunk) = response.chunk().await? {
+                    events.extend(decoder.push(&chunk));
+                }
+                events.extend(decoder.finish());
+                cassette.record_interaction(
+                    key,
+                    serde_json::to_value(&events).map_err(map_serialization_error)?,
+                )?;
+                events
+            } else {
+                serde_json::from_value(cassette.replay_interaction(&key)?)
+                    .map_err(ClientError::JSONDeserialize)?
+            };
+            return Ok(Box::pin(futures::stream::iter(events.into_iter().map(Ok))));
+        }
+        let mut response = self.send(request_maker).await?;
+        Ok(Box::pin(async_stream::try_stream! {
+            let mut decoder = SseDecoder::default();
+            while let Some(chunk) = response.chunk().await? {
+                for event in decoder.push(&chunk) {
+                    yield event;
+                }
+            }
+            if let Some(event) = decoder.finish() {
+                yield event;
+            }
+        }))
     }
 
     /// Make a GET request to {path} and deserialize the response body
@@ -61,15 +142,28 @@ impl<C: ApiConfigTrait> ApiClient<C> {
             // crate::trace!("Serialized post request: {:?}", request_builder); // This will log API keys!
             Ok(request_builder.build()?)
         };
-        self.execute(request_maker).await
+        match self.config.cassette() {
+            Some(cassette) => {
+                let key = json!({ "method": "GET", "path": path });
+                let response = if cassette.is_recording() {
+                    let response: Value = self.execute(request_maker).await?;
+                    cassette.record_interaction(key, response.clone())?;
+                    response
+                } else {
+                    cassette.replay_interaction(&key)?
+                };
+                serde_json::from_value(response).map_err(ClientError::JSONDeserialize)
+            }
+            None => self.execute(request_maker).await,
+        }
     }
 
-    /// Execute a HTTP request and retry on rate limit
+    /// Send a HTTP request and retry on rate limit, returning the successful response
     ///
     /// request_maker serves one purpose: to be able to create request again
     /// to retry API call after getting rate limited. request_maker is async because
     /// reqwest::multipart::Form is created by async calls to read files for uploads.
-    async fn execute_raw<M, Fut>(&self, request_maker: M) -> Result<Bytes, ClientError>
+    async fn send<M, Fut>(&self, request_maker: M) -> Result<reqwest::Response, ClientError>
     where
         M: Fn() -> Fut,
         Fut: core::future::Future<Output = Result<reqwest::Request, ClientError>>,
@@ -85,48 +179,61 @@ impl<C: ApiConfigTrait> ApiClient<C> {
                 .map_err(backoff::Error::Permanent)?;
 
             let status = response.status();
+            if status.is_success() {
+                return Ok(response);
+            }
+
+            // Deserialize the error object from the response body
             let bytes = response
                 .bytes()
                 .await
                 .map_err(ClientError::Reqwest)
                 .map_err(backoff::Error::Permanent)?;
+            let wrapped_error: WrappedError = serde_json::from_slice(bytes.as_ref())
+                .map_err(|e| map_deserialization_error(e, bytes.as_ref()))
+                .map_err(backoff::Error::Permanent)?;
 
-            // Deserialize response body from either error object or actual response object
-            if !status.is_success() {
-                let wrapped_error: WrappedError = serde_json::from_slice(bytes.as_ref())
-                    .map_err(|e| map_deserialization_error(e, bytes.as_ref()))
-                    .map_err(backoff::Error::Permanent)?;
-
-                if status.as_u16() == 429
-                    // API returns 429 also when:
-                    // "You exceeded your current quota, please check your plan and billing details."
-                    && wrapped_error.error.r#type != Some("insufficient_quota".to_string())
-                {
-                    // Rate limited retry...
-                    tracing::warn!("Rate limited: {}", wrapped_error.error.message);
-                    return Err(backoff::Error::Transient {
-                        err: ClientError::ApiError(wrapped_error.error),
-                        retry_after: None,
-                    });
-                } else if status.as_u16() == 503 {
-                    return Err(backoff::Error::Transient {
-                        err: ClientError::ServiceUnavailable {
-                            message: wrapped_error.error.message,
-                        },
-                        retry_after: None,
-                    });
-                } else {
-                    return Err(backoff::Error::Permanent(ClientError::ApiError(
-                        wrapped_error.error,
-                    )));
-                }
+            if status.as_u16() == 429
+                // API returns 429 also when:
+                // "You exceeded your current quota, please check your plan and billing details."
+                && wrapped_error.error.r#type != Some("insufficient_quota".to_string())
+            {
+                // Rate limited retry...
+                tracing::warn!("Rate limited: {}", wrapped_error.error.message);
+                Err(backoff::Error::Transient {
+                    err: ClientError::ApiError(wrapped_error.error),
+                    retry_after: None,
+                })
+            } else if status.as_u16() == 503 {
+                Err(backoff::Error::Transient {
+                    err: ClientError::ServiceUnavailable {
+                        message: wrapped_error.error.message,
+                    },
+                    retry_after: None,
+                })
+            } else {
+                Err(backoff::Error::Permanent(ClientError::ApiError(
+                    wrapped_error.error,
+                )))
             }
-
-            Ok(bytes)
         })
         .await
     }
 
+    /// Execute a HTTP request and retry on rate limit
+    ///
+    /// request_maker serves one purpose: to be able to create request again
+    /// to retry API call after getting rate limited. request_maker is async because
+    /// reqwest::multipart::Form is created by async calls to read files for uploads.
+    async fn execute_raw<M, Fut>(&self, request_maker: M) -> Result<Bytes, ClientError>
+    where
+        M: Fn() -> Fut,
+        Fut: core::future::Future<Output = Result<reqwest::Request, ClientError>>,
+    {
+        let response = self.send(request_maker).await?;
+        Ok(response.bytes().await?)
+    }
+
     /// Execute a HTTP request and retry on rate limit
     ///
     /// request_maker serves one purpose: to be able to create request again
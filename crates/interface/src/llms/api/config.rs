@@ -1,5 +1,7 @@
+use crate::cassette::Cassette;
 use reqwest::header::HeaderMap;
 use secrecy::SecretString;
+use std::sync::Arc;
 
 #[derive(Clone, Debug)]
 pub struct ApiConfig {
@@ -7,6 +9,8 @@ pub struct ApiConfig {
     pub port: Option<String>,
     pub api_key: Option<SecretString>,
     pub api_key_env_var: String,
+    /// The cassette recording or replaying the API calls, see [`crate::cassette`].
+    pub cassette: Option<Arc<Cassette>>,
 }
 
 impl ApiConfig {
@@ -23,6 +27,15 @@ impl ApiConfig {
                 crate::trace!("Successfully loaded api_key from .env");
                 Ok(api_key.into())
             }
+            Err(_)
+                if self
+                    .cassette
+                    .as_ref()
+                    .is_some_and(|cassette| !cassette.is_recording()) =>
+            {
+                crate::trace!("api_key not set, replaying the cassette without it");
+                Ok(SecretString::from(String::new()))
+            }
             Err(_) => {
                 crate::trace!(
                     "{} not found in dotenv, nor was it set manually",
@@ -71,6 +84,15 @@ pub trait LLMApiConfigTrait {
         self.api_base_config_mut().api_key_env_var = api_key_env_var.into();
         self
     }
+
+    /// Record the API calls into the cassette, or replay them from it.
+    fn with_cassette(mut self, cassette: Arc<Cassette>) -> Self
+    where
+        Self: Sized,
+    {
+        self.api_base_config_mut().cassette = Some(cassette);
+        self
+    }
 }
 
 pub trait ApiConfigTrait {
@@ -79,4 +101,9 @@ pub trait ApiConfigTrait {
     fn url(&self, path: &str) -> String;
 
     fn api_key(&self) -> &Option<SecretString>;
+
+    /// The cassette recording or replaying the API calls.
+    fn cassette(&self) -> Option<&Arc<Cassette>> {
+        None
+    }
 }
@@ -1,3 +1,4 @@
+use crate::cassette::CassetteError;
 use serde::Deserialize;
 
 #[derive(Debug, thiserror::Error)]
@@ -24,6 +25,9 @@ pub enum ClientError {
     /// or when builder fails to build request before making API call
     #[error("invalid args: {0}")]
     InvalidArgument(String),
+    /// Error when recording or replaying a cassette
+    #[error("cassette error: {0}")]
+    Cassette(#[from] CassetteError),
 }
 
 /// Wrapper to deserialize the error object nested in "error" JSON key
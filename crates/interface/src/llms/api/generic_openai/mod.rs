@@ -1,11 +1,13 @@
 use super::{
     client::ApiClient,
     config::{ApiConfig, ApiConfigTrait},
-    openai::completion::OpenAICompletionRequest,
+    openai::completion::{OpenAICompletionRequest, completion_stream},
 };
+use crate::cassette::Cassette;
 use crate::requests::{
     completion::{
         error::CompletionError, request::CompletionRequest, response::CompletionResponse,
+        stream::CompletionStream,
     },
     embeddings::{EmbeddingsError, EmbeddingsRequest, EmbeddingsResponse},
 };
@@ -14,6 +16,7 @@ use alith_models::api_model::ApiLLMModel;
 use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderValue};
 use secrecy::{ExposeSecret, SecretString};
 use serde_json::json;
+use std::sync::Arc;
 
 pub struct GenericApiBackend {
     pub(crate) client: ApiClient<GenericApiConfig>,
@@ -49,6 +52,22 @@ impl GenericApiBackend {
         }
     }
 
+    pub(crate) async fn completion_stream_request(
+        &self,
+        request: &CompletionRequest,
+    ) -> crate::Result<CompletionStream, CompletionError> {
+        let mut req = OpenAICompletionRequest::new(request)?;
+        req.stream = Some(true);
+        match self
+            .client
+            .post_stream(&self.client.config.completion_path, req)
+            .await
+        {
+            Err(e) => Err(CompletionError::ClientError(e)),
+            Ok(events) => Ok(completion_stream(events)),
+        }
+    }
+
     pub(crate) async fn embeddings_request(
         &self,
         request: &EmbeddingsRequest,
@@ -86,6 +105,7 @@ impl Default for GenericApiConfig {
                 port: None,
                 api_key: None,
                 api_key_env_var: Default::default(),
+                cassette: None,
             },
             logging_config: LoggingConfig {
                 logger_name: "generic".to_string(),
@@ -139,4 +159,8 @@ impl ApiConfigTrait for GenericApiConfig {
     fn api_key(&self) -> &Option<SecretString> {
         &self.api_config.api_key
     }
+
+    fn cassette(&self) -> Option<&Arc<Cassette>> {
+        self.api_config.cassette.as_ref()
+    }
 }
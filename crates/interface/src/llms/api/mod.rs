@@ -5,3 +5,4 @@ pub mod error;
 pub mod generic_openai;
 pub mod openai;
 pub mod perplexity;
+pub mod sse;
@@ -1,8 +1,15 @@
 mod req;
 mod res;
+mod stream;
 
-pub use req::{CompletionRequestMessage, OpenAICompletionRequest, OpenAIToolDefinition};
+pub use req::{
+    CompletionRequestMessage, OpenAICompletionRequest, OpenAIToolDefinition, StreamOptions,
+};
 pub use res::{
     ChatChoice, ChatCompletionResponseMessage, CompletionUsage, FinishReason,
     OpenAICompletionResponse, Role,
 };
+pub use stream::{
+    ChatChoiceStream, ChatCompletionStreamResponseDelta, FunctionCallStream, OpenAICompletionChunk,
+    ToolCallChunk, completion_stream,
+};
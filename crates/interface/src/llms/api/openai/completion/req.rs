@@ -1,6 +1,8 @@
 use crate::requests::{completion::*, stop_sequence::StopSequences};
+use alith_prompt::PromptToolCall;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use tool::{Function, ToolCall};
 
 #[derive(Clone, Serialize, Default, Debug, Deserialize)]
 pub struct OpenAICompletionRequest {
@@ -62,6 +64,10 @@ pub struct OpenAICompletionRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<OpenAIToolDefinition>>,
 
+    /// The format the model must output, default: None
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+
     /// The tool choice for the request, default: None
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_choice: Option<String>,
@@ -69,6 +75,16 @@ pub struct OpenAICompletionRequest {
     /// Whether to stream back partial progress.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>, // default: false
+
+    /// Options for streaming responses, only set when `stream` is true.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+}
+
+#[derive(Clone, Serialize, Default, Debug, Deserialize)]
+pub struct StreamOptions {
+    /// Whether to stream an additional chunk with the token usage of the whole request.
+    pub include_usage: bool,
 }
 
 #[derive(Clone, Serialize, Debug, Deserialize)]
@@ -114,12 +130,14 @@ impl OpenAICompletionRequest {
             } else {
                 None
             },
+            response_format: req.response_format.clone(),
             tool_choice: if !req.tools.is_empty() {
                 Some("auto".to_string())
             } else {
                 None
             },
             stream: None,
+            stream_options: None,
         })
     }
 }
@@ -128,6 +146,12 @@ impl OpenAICompletionRequest {
 pub struct CompletionRequestMessage {
     pub role: String,
     pub content: String,
+    /// The tool calls requested by an assistant message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    /// The ID of the tool call that a tool message answers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
 }
 
 impl CompletionRequestMessage {
@@ -142,9 +166,48 @@ impl CompletionRequestMessage {
             .ok_or_else(|| CompletionError::RequestBuilderError("Content not found".to_string()))?;
 
         match role.as_str() {
-            "system" | "user" | "assistant" => Ok(CompletionRequestMessage {
+            "system" | "user" => Ok(CompletionRequestMessage {
                 role: role.to_string(),
                 content: content.to_string(),
+                tool_calls: None,
+                tool_call_id: None,
+            }),
+            "assistant" => {
+                let tool_calls = PromptToolCall::from_built_prompt_message(message)
+                    .map_err(|e| CompletionError::RequestBuilderError(e.to_string()))?;
+                Ok(CompletionRequestMessage {
+                    role: role.to_string(),
+                    content: content.to_string(),
+                    tool_calls: (!tool_calls.is_empty()).then(|| {
+                        tool_calls
+                            .into_iter()
+                            .map(|call| ToolCall {
+                                id: call.id,
+                                r#type: "function".to_string(),
+                                function: Function {
+                                    name: call.name,
+                                    arguments: call.arguments,
+                                },
+                            })
+                            .collect()
+                    }),
+                    tool_call_id: None,
+                })
+            }
+            "tool" => Ok(CompletionRequestMessage {
+                role: role.to_string(),
+                content: content.to_string(),
+                tool_calls: None,
+                tool_call_id: Some(
+                    message
+                        .get("tool_call_id")
+                        .ok_or_else(|| {
+                            CompletionError::RequestBuilderError(
+                                "Tool call ID not found".to_string(),
+                            )
+                        })?
+                        .to_string(),
+                ),
             }),
             _ => Err(CompletionError::RequestBuilderError(format!(
                 "Role {} not supported",
@@ -172,3 +235,51 @@ impl Stop {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn message(fields: &[(&str, &str)]) -> HashMap<String, String> {
+        fields
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_request_message_tool_calls() {
+        let tool_calls = json!([
+            {"id": "call_1", "name": "weather", "arguments": r#"{"city":"Paris"}"#},
+        ])
+        .to_string();
+        let assistant = CompletionRequestMessage::new(&message(&[
+            ("role", "assistant"),
+            ("content", ""),
+            ("tool_calls", &tool_calls),
+        ]))
+        .unwrap();
+        let tool = CompletionRequestMessage::new(&message(&[
+            ("role", "tool"),
+            ("content", "Sunny"),
+            ("tool_call_id", "call_1"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value([assistant, tool]).unwrap(),
+            json!([
+                {"role": "assistant", "content": "", "tool_calls": [{
+                    "id": "call_1",
+                    "type": "function",
+                    "function": {"name": "weather", "arguments": r#"{"city":"Paris"}"#},
+                }]},
+                {"role": "tool", "content": "Sunny", "tool_call_id": "call_1"},
+            ])
+        );
+        assert!(
+            CompletionRequestMessage::new(&message(&[("role", "tool"), ("content", "Sunny")]))
+                .is_err()
+        );
+    }
+}
@@ -13,19 +13,7 @@ impl CompletionResponse {
             &res.choices[0]
         };
         let finish_reason = match choice.finish_reason {
-            Some(FinishReason::Stop) => CompletionFinishReason::Eos,
-            Some(FinishReason::Length) => CompletionFinishReason::StopLimit,
-            Some(FinishReason::ToolCalls) => CompletionFinishReason::ToolsCall,
-            Some(FinishReason::ContentFilter) => {
-                return Err(CompletionError::StopReasonUnsupported(
-                    "FinishReason::ContentFilter is not supported".to_owned(),
-                ));
-            }
-            Some(FinishReason::FunctionCall) => {
-                return Err(CompletionError::StopReasonUnsupported(
-                    "FinishReason::FunctionCall is not supported".to_owned(),
-                ));
-            }
+            Some(finish_reason) => finish_reason.into_completion_finish_reason()?,
             None => CompletionFinishReason::Eos,
         };
         Ok(Self {
@@ -104,6 +92,24 @@ pub enum FinishReason {
     FunctionCall,
 }
 
+impl FinishReason {
+    pub(crate) fn into_completion_finish_reason(
+        self,
+    ) -> Result<CompletionFinishReason, CompletionError> {
+        match self {
+            FinishReason::Stop => Ok(CompletionFinishReason::Eos),
+            FinishReason::Length => Ok(CompletionFinishReason::StopLimit),
+            FinishReason::ToolCalls => Ok(CompletionFinishReason::ToolsCall),
+            FinishReason::ContentFilter => Err(CompletionError::StopReasonUnsupported(
+                "FinishReason::ContentFilter is not supported".to_owned(),
+            )),
+            FinishReason::FunctionCall => Err(CompletionError::StopReasonUnsupported(
+                "FinishReason::FunctionCall is not supported".to_owned(),
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct ChatChoiceLogprobs {
     /// A list of message content tokens with log probability information.
@@ -0,0 +1,176 @@
+use super::{CompletionUsage, FinishReason};
+use crate::llms::api::sse::SseStream;
+use crate::requests::completion::*;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+/// The data payload that terminates an OpenAI completion stream.
+const DONE_EVENT: &str = "[DONE]";
+
+impl CompletionDelta {
+    pub fn new_from_openai(chunk: OpenAICompletionChunk) -> Result<Self, CompletionError> {
+        let mut delta = CompletionDelta {
+            token_usage: chunk.usage.map(|usage| TokenUsage {
+                tokens_cached: None,
+                prompt_tokens: usage.prompt_tokens,
+                completion_tokens: usage.completion_tokens,
+                total_tokens: usage.total_tokens,
+            }),
+            ..Default::default()
+        };
+        // The usage chunk requested with `stream_options` has no choices.
+        if let Some(choice) = chunk.choices.into_iter().next() {
+            delta.content = choice.delta.content.filter(|content| !content.is_empty());
+            delta.tool_calls = choice
+                .delta
+                .tool_calls
+                .unwrap_or_default()
+                .into_iter()
+                .map(|call| ToolCallDelta {
+                    index: call.index,
+                    id: call.id,
+                    name: call.function.as_ref().and_then(|f| f.name.clone()),
+                    arguments: call.function.and_then(|f| f.arguments).unwrap_or_default(),
+                })
+                .collect();
+            delta.finish_reason = match choice.finish_reason {
+                Some(finish_reason) => Some(finish_reason.into_completion_finish_reason()?),
+                None => None,
+            };
+        }
+        Ok(delta)
+    }
+}
+
+/// Converts the server-sent events of an OpenAI compatible chat completion into completion deltas.
+pub fn completion_stream(mut events: SseStream) -> CompletionStream {
+    Box::pin(async_stream::try_stream! {
+        while let Some(event) = events.next().await {
+            let event = event?;
+            if event.data == DONE_EVENT {
+                break;
+            }
+            let chunk: OpenAICompletionChunk = serde_json::from_str(&event.data)?;
+            yield CompletionDelta::new_from_openai(chunk)?;
+        }
+    })
+}
+
+/// Represents a streamed chunk of a chat completion response returned by model.
+#[derive(Debug, Deserialize, Clone, PartialEq, Serialize)]
+pub struct OpenAICompletionChunk {
+    /// A unique identifier for the chat completion. Each chunk has the same ID.
+    #[serde(default)]
+    pub id: String,
+    /// A list of chat completion choices. Empty for the last chunk when usage is included.
+    #[serde(default)]
+    pub choices: Vec<ChatChoiceStream>,
+    /// The usage of the whole request, only set on the last chunk when requested.
+    pub usage: Option<CompletionUsage>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ChatChoiceStream {
+    /// The index of the choice in the list of choices.
+    pub index: u32,
+    pub delta: ChatCompletionStreamResponseDelta,
+    /// The reason the model stopped generating tokens, only set on the last chunk of the choice.
+    pub finish_reason: Option<FinishReason>,
+}
+
+/// A chat completion delta generated by streamed model responses.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ChatCompletionStreamResponseDelta {
+    /// The contents of the chunk message.
+    pub content: Option<String>,
+    /// The tool call fragments.
+    pub tool_calls: Option<Vec<ToolCallChunk>>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ToolCallChunk {
+    /// The position of the tool call in the message.
+    pub index: usize,
+    /// The ID of the tool call, only set on its first chunk.
+    pub id: Option<String>,
+    pub function: Option<FunctionCallStream>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct FunctionCallStream {
+    /// The name of the function to call, only set on its first chunk.
+    pub name: Option<String>,
+    /// A fragment of the JSON encoded arguments.
+    pub arguments: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llms::api::sse::fixture_stream;
+    use futures::TryStreamExt;
+
+    const TOOL_CALLS_FIXTURE: &str = r#"data: {"id":"c1","choices":[{"index":0,"delta":{"content":"Let me compute."},"finish_reason":null}]}
+
+data: {"id":"c1","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"id":"call_a","function":{"name":"add","arguments":"{\"a\":"}}]},"finish_reason":null}]}
+
+data: {"id":"c1","choices":[{"index":0,"delta":{"tool_calls":[{"index":1,"id":"call_b","function":{"name":"mul","arguments":"{\"a\":"}}]},"finish_reason":null}]}
+
+data: {"id":"c1","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"1,\"b\":2}"}}]},"finish_reason":null}]}
+
+data: {"id":"c1","choices":[{"index":0,"delta":{"tool_calls":[{"index":1,"function":{"arguments":"3,\"b\":4}"}}]},"finish_reason":null}]}
+
+data: {"id":"c1","choices":[{"index":0,"delta":{},"finish_reason":"tool_calls"}]}
+
+data: {"id":"c1","choices":[],"usage":{"prompt_tokens":10,"completion_tokens":5,"total_tokens":15}}
+
+data: [DONE]
+
+data: not an event
+"#;
+
+    #[tokio::test]
+    async fn test_completion_stream_tool_calls() {
+        let deltas: Vec<CompletionDelta> = completion_stream(fixture_stream(TOOL_CALLS_FIXTURE))
+            .try_collect()
+            .await
+            .unwrap();
+        // The events after `[DONE]` are not decoded.
+        assert_eq!(deltas.len(), 7);
+        let mut accumulator = CompletionDeltaAccumulator::default();
+        deltas.iter().for_each(|delta| accumulator.push(delta));
+
+        assert_eq!(accumulator.content, "Let me compute.");
+        let tool_calls = accumulator.tool_calls();
+        let tool_calls: Vec<(&str, &str, &str)> = tool_calls
+            .iter()
+            .map(|call| {
+                (
+                    call.id.as_str(),
+                    call.function.name.as_str(),
+                    call.function.arguments.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            tool_calls,
+            vec![
+                ("call_a", "add", r#"{"a":1,"b":2}"#),
+                ("call_b", "mul", r#"{"a":3,"b":4}"#),
+            ]
+        );
+        assert_eq!(
+            accumulator.finish_reason,
+            Some(CompletionFinishReason::ToolsCall)
+        );
+        assert_eq!(
+            accumulator.token_usage,
+            Some(TokenUsage {
+                tokens_cached: None,
+                prompt_tokens: 10,
+                completion_tokens: 5,
+                total_tokens: 15,
+            })
+        );
+    }
+}
@@ -5,18 +5,21 @@ use super::{
     client::ApiClient,
     config::{ApiConfig, ApiConfigTrait},
 };
+use crate::cassette::Cassette;
 use crate::requests::{
     completion::{
         error::CompletionError, request::CompletionRequest, response::CompletionResponse,
+        stream::CompletionStream,
     },
     embeddings::{EmbeddingsError, EmbeddingsRequest, EmbeddingsResponse},
 };
 use alith_devices::logging::LoggingConfig;
 use alith_models::api_model::ApiLLMModel;
-use completion::OpenAICompletionRequest;
+use completion::{OpenAICompletionRequest, StreamOptions, completion_stream};
 use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderValue};
 use secrecy::{ExposeSecret, SecretString};
 use serde_json::json;
+use std::sync::Arc;
 
 /// Default v1 API base url
 pub const OPENAI_API_HOST: &str = "api.openai.com/v1";
@@ -54,6 +57,21 @@ impl OpenAIBackend {
         }
     }
 
+    pub(crate) async fn completion_stream_request(
+        &self,
+        request: &CompletionRequest,
+    ) -> crate::Result<CompletionStream, CompletionError> {
+        let mut req = OpenAICompletionRequest::new(request)?;
+        req.stream = Some(true);
+        req.stream_options = Some(StreamOptions {
+            include_usage: true,
+        });
+        match self.client.post_stream("/chat/completions", req).await {
+            Err(e) => Err(CompletionError::ClientError(e)),
+            Ok(events) => Ok(completion_stream(events)),
+        }
+    }
+
     pub(crate) async fn embeddings_request(
         &self,
         request: &EmbeddingsRequest,
@@ -92,6 +110,7 @@ impl Default for OpenAIConfig {
                 port: None,
                 api_key: None,
                 api_key_env_var: "OPENAI_API_KEY".to_string(),
+                cassette: None,
             },
             logging_config: LoggingConfig {
                 logger_name: "openai".to_string(),
@@ -172,4 +191,8 @@ impl ApiConfigTrait for OpenAIConfig {
     fn api_key(&self) -> &Option<SecretString> {
         &self.api_config.api_key
     }
+
+    fn cassette(&self) -> Option<&Arc<Cassette>> {
+        self.api_config.cassette.as_ref()
+    }
 }
@@ -0,0 +1,127 @@
+use super::error::ClientError;
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use std::pin::Pin;
+
+/// A stream of server-sent events decoded from a response body.
+pub type SseStream = Pin<Box<dyn Stream<Item = Result<SseEvent, ClientError>> + Send>>;
+
+/// A single server-sent event.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SseEvent {
+    /// The event type, set by the `event` field.
+    pub event: Option<String>,
+    /// The event payload, the `data` fields joined by newlines.
+    pub data: String,
+}
+
+/// Incrementally decodes server-sent events from raw response body chunks.
+///
+/// Chunks may split lines and UTF-8 sequences at arbitrary positions, so incomplete
+/// lines are buffered until their line terminator arrives.
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseDecoder {
+    /// Feeds a body chunk into the decoder and returns the events it completes.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.is_empty() {
+                if let Some(event) = self.dispatch() {
+                    events.push(event);
+                }
+            } else if !line.starts_with(':') {
+                let (field, value) = match line.split_once(':') {
+                    Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+                    None => (line, ""),
+                };
+                match field {
+                    "event" => self.event = Some(value.to_string()),
+                    "data" => self.data.push(value.to_string()),
+                    _ => {}
+                }
+            }
+        }
+        events
+    }
+
+    /// Returns the pending event when the body ends without a trailing blank line.
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        if !self.buffer.is_empty() {
+            let mut events = self.push(b"\n");
+            if !events.is_empty() {
+                return events.pop();
+            }
+        }
+        self.dispatch()
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        if self.event.is_none() && self.data.is_empty() {
+            return None;
+        }
+        let event = SseEvent {
+            event: self.event.take(),
+            data: self.data.join("\n"),
+        };
+        self.data.clear();
+        Some(event)
+    }
+}
+
+/// Decodes a recorded response body into a stream of events.
+#[cfg(test)]
+pub(crate) fn fixture_stream(body: &str) -> SseStream {
+    let mut decoder = SseDecoder::default();
+    let mut events = decoder.push(body.as_bytes());
+    events.extend(decoder.finish());
+    Box::pin(futures::stream::iter(events.into_iter().map(Ok)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SseDecoder, SseEvent};
+
+    #[test]
+    fn test_decode_split_events() {
+        let mut decoder = SseDecoder::default();
+        assert!(decoder.push(b"event: ping\r\ndata: {\"a\"").is_empty());
+        let events = decoder.push(b": 1}\r\n\r\n: comment\ndata: [DONE]\n\n");
+        assert_eq!(
+            events,
+            vec![
+                SseEvent {
+                    event: Some("ping".to_string()),
+                    data: "{\"a\": 1}".to_string(),
+                },
+                SseEvent {
+                    event: None,
+                    data: "[DONE]".to_string(),
+                },
+            ]
+        );
+        assert_eq!(decoder.finish(), None);
+    }
+
+    #[test]
+    fn test_decode_unterminated_event() {
+        let mut decoder = SseDecoder::default();
+        assert!(decoder.push(b"data: a\ndata: b").is_empty());
+        assert_eq!(
+            decoder.finish(),
+            Some(SseEvent {
+                event: None,
+                data: "a\nb".to_string(),
+            })
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use crate::requests::{
     completion::{
         error::CompletionError, request::CompletionRequest, response::CompletionResponse,
+        stream::CompletionStream,
     },
     embeddings::{EmbeddingsError, EmbeddingsRequest, EmbeddingsResponse},
     logit_bias::LogitBias,
@@ -32,6 +33,17 @@ impl LLMBackend {
         }
     }
 
+    pub(crate) async fn completion_stream_request(
+        &self,
+        request: &CompletionRequest,
+    ) -> crate::Result<CompletionStream, CompletionError> {
+        match self {
+            LLMBackend::OpenAI(b) => b.completion_stream_request(request).await,
+            LLMBackend::Anthropic(b) => b.completion_stream_request(request).await,
+            LLMBackend::GenericApi(b) => b.completion_stream_request(request).await,
+        }
+    }
+
     pub(crate) async fn embeddings_request(
         &self,
         request: &EmbeddingsRequest,
@@ -99,6 +111,20 @@ impl LLMBackend {
         }
     }
 
+    /// Whether the backend supports the JSON schema response format.
+    ///
+    /// OpenAI compatible servers reusing the OpenAI backend with a custom host are not
+    /// assumed to support it.
+    pub fn supports_json_schema(&self) -> bool {
+        match self {
+            LLMBackend::OpenAI(b) => {
+                b.client.config.api_config.host == api::openai::OPENAI_API_HOST
+            }
+            LLMBackend::Anthropic(_) => false,
+            LLMBackend::GenericApi(_) => false,
+        }
+    }
+
     pub fn model_ctx_size(&self) -> u64 {
         match self {
             LLMBackend::OpenAI(b) => b.model.model_base.model_ctx_size,
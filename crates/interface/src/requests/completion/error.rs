@@ -11,6 +11,10 @@ pub enum CompletionError {
     RequestTokenLimitError(#[from] alith_prompt::RequestTokenLimitError),
     #[error("StopReasonUnsupported: {0}")]
     StopReasonUnsupported(String),
+    #[error("Cancelled: The request was cancelled")]
+    Cancelled,
+    #[error("DeadlineExceeded: The request deadline has passed")]
+    DeadlineExceeded,
     #[error("ExceededRetryCount")]
     ExceededRetryCount {
         message: String,
@@ -1,10 +1,12 @@
 pub mod error;
 pub mod request;
 pub mod response;
+pub mod stream;
 pub mod tool;
 
 pub use super::res_components::{GenerationSettings, TimingUsage, TokenUsage};
 pub use error::CompletionError;
-pub use request::CompletionRequest;
+pub use request::{CompletionRequest, JsonSchemaFormat, ResponseFormat};
 pub use response::{CompletionFinishReason, CompletionResponse};
+pub use stream::{CompletionDelta, CompletionDeltaAccumulator, CompletionStream, ToolCallDelta};
 pub use tool::{ToolChoice, ToolDefinition};
@@ -1,4 +1,7 @@
-use super::{ToolChoice, ToolDefinition, error::CompletionError, response::CompletionResponse};
+use super::{
+    ToolChoice, ToolDefinition, error::CompletionError, response::CompletionResponse,
+    stream::CompletionStream,
+};
 use crate::{
     llms::LLMBackend,
     requests::{
@@ -7,7 +10,13 @@ use crate::{
     },
 };
 use alith_prompt::LLMPrompt;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::field::Empty;
+use tracing::{Instrument, Span};
 
 pub struct CompletionRequest {
     pub start_time: std::time::Instant,
@@ -20,6 +29,52 @@ pub struct CompletionRequest {
     pub llm_interface_errors: Vec<CompletionError>,
     pub tools: Vec<ToolDefinition>,
     pub tool_choice: ToolChoice,
+    pub response_format: Option<ResponseFormat>,
+    /// The token cancelling the request, the in-flight backend call is dropped when it is
+    /// cancelled.
+    pub cancellation: Option<CancellationToken>,
+    /// The instant after which the request fails, retries included.
+    pub deadline: Option<Instant>,
+}
+
+/// The format the model must output, only sent to backends supporting it.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    /// Plain text, the default.
+    Text,
+    /// Any valid JSON object.
+    JsonObject,
+    /// JSON matching the given schema.
+    JsonSchema { json_schema: JsonSchemaFormat },
+}
+
+impl ResponseFormat {
+    /// Creates a JSON schema response format.
+    pub fn json_schema(name: impl ToString, schema: serde_json::Value) -> Self {
+        ResponseFormat::JsonSchema {
+            json_schema: JsonSchemaFormat {
+                name: name.to_string(),
+                description: None,
+                schema,
+                strict: None,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct JsonSchemaFormat {
+    /// The name of the response format.
+    pub name: String,
+    /// A description of what the response format is for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The JSON schema of the output.
+    pub schema: serde_json::Value,
+    /// Whether to enable strict schema adherence, which only supports a subset of JSON schema.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
 }
 
 impl Clone for CompletionRequest {
@@ -35,6 +90,9 @@ impl Clone for CompletionRequest {
             llm_interface_errors: Vec::new(),
             tools: Vec::new(),
             tool_choice: ToolChoice::Auto,
+            response_format: self.response_format.clone(),
+            cancellation: self.cancellation.clone(),
+            deadline: self.deadline,
         }
     }
 }
@@ -52,6 +110,9 @@ impl CompletionRequest {
             llm_interface_errors: Vec::new(),
             tools: Vec::new(),
             tool_choice: ToolChoice::default(),
+            response_format: None,
+            cancellation: None,
+            deadline: None,
         }
     }
 
@@ -60,6 +121,81 @@ impl CompletionRequest {
         self.stop_sequences.sequences.clear();
         self.grammar_string = None;
         self.logit_bias = None;
+        self.response_format = None;
+    }
+
+    /// Sends the request and streams the completion back as incremental deltas.
+    ///
+    /// Unlike [`CompletionRequest::request`], the stream is not retried and stop sequences
+    /// are not enforced, the finish reason is reported on the last delta instead.
+    pub async fn stream(&mut self) -> crate::Result<CompletionStream, CompletionError> {
+        self.llm_interface_errors.clear();
+        self.start_time = std::time::Instant::now();
+        self.backend
+            .build_logit_bias(&mut self.logit_bias)
+            .map_err(|e| CompletionError::RequestBuilderError(e.to_string()))?;
+
+        let total_prompt_tokens = self
+            .backend
+            .get_total_prompt_tokens(&self.prompt)
+            .map_err(|e| CompletionError::RequestBuilderError(e.to_string()))?;
+
+        self.config
+            .set_max_tokens_for_request(total_prompt_tokens as u64)
+            .map_err(CompletionError::RequestTokenLimitError)?;
+
+        // Only the stream setup is traced, its deltas arrive after the span is closed.
+        let span = self.attempt_span(1);
+        tracing::debug!(parent: &span, "{}", self);
+        self.interruptible(self.backend.completion_stream_request(self))
+            .instrument(span.clone())
+            .await
+            .inspect_err(|e| record_error(&span, e))
+    }
+
+    /// Creates the span of a model call attempt, the attempts of a request start at 1.
+    fn attempt_span(&self, attempt: u8) -> Span {
+        tracing::info_span!(
+            "llm.completion",
+            otel.kind = "client",
+            gen_ai.operation.name = "chat",
+            gen_ai.request.model = self.backend.model_id(),
+            llm.attempt = attempt,
+            gen_ai.response.finish_reasons = Empty,
+            gen_ai.usage.input_tokens = Empty,
+            gen_ai.usage.output_tokens = Empty,
+            llm.timing.total_ms = Empty,
+            llm.timing.prompt_processing_ms = Empty,
+            llm.timing.generation_ms = Empty,
+            llm.timing.generation_tok_per_sec = Empty,
+            error.message = Empty,
+            otel.status_code = Empty,
+        )
+    }
+
+    /// Awaits a backend call, failing when the request is cancelled or its deadline passes.
+    async fn interruptible<T>(
+        &self,
+        call: impl Future<Output = crate::Result<T, CompletionError>>,
+    ) -> crate::Result<T, CompletionError> {
+        let cancelled = async {
+            match &self.cancellation {
+                Some(token) => token.cancelled().await,
+                None => std::future::pending().await,
+            }
+        };
+        let deadline = async {
+            match self.deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            biased;
+            _ = cancelled => Err(CompletionError::Cancelled),
+            _ = deadline => Err(CompletionError::DeadlineExceeded),
+            result = call => result,
+        }
     }
 
     pub async fn request(&mut self) -> crate::Result<CompletionResponse, CompletionError> {
@@ -90,15 +226,26 @@ impl CompletionRequest {
                 eprintln!("{}", llm_interface_error);
                 return Err(llm_interface_error);
             }
-            tracing::info!("{}", self);
-            match self.backend.completion_request(self).await {
+            let span = self.attempt_span(retry_count + 1);
+            tracing::debug!(parent: &span, "{}", self);
+            let result = self
+                .interruptible(self.backend.completion_request(self))
+                .instrument(span.clone())
+                .await;
+            match &result {
+                Ok(res) => record_response(&span, res),
+                Err(e) => record_error(&span, e),
+            }
+            match result {
                 Err(e) => {
                     tracing::warn!(?e);
                     retry_count += 1;
                     match e {
                         CompletionError::RequestBuilderError { .. }
                         | CompletionError::StopReasonUnsupported { .. }
-                        | CompletionError::ClientError { .. } => {
+                        | CompletionError::ClientError { .. }
+                        | CompletionError::Cancelled
+                        | CompletionError::DeadlineExceeded => {
                             return Err(e);
                         }
 
@@ -108,7 +255,7 @@ impl CompletionRequest {
                     continue;
                 }
                 Ok(res) => {
-                    tracing::info!("{}", res);
+                    tracing::debug!(parent: &span, "{}", res);
                     if self.stop_sequences.required {
                         if matches!(
                             res.finish_reason,
@@ -182,6 +329,37 @@ impl CompletionRequest {
     }
 }
 
+/// Marks the span of a model call as failed with the error.
+fn record_error(span: &Span, err: &CompletionError) {
+    span.record("otel.status_code", "ERROR");
+    span.record("error.message", tracing::field::display(err));
+}
+
+/// Records the finish reason, the token usage and the timing of a model call on its span.
+fn record_response(span: &Span, res: &CompletionResponse) {
+    let timing = &res.timing_usage;
+    let millis = |duration: std::time::Duration| duration.as_secs_f64() * 1000.0;
+    span.record(
+        "gen_ai.response.finish_reasons",
+        tracing::field::display(&res.finish_reason),
+    );
+    span.record("gen_ai.usage.input_tokens", res.token_usage.prompt_tokens);
+    span.record(
+        "gen_ai.usage.output_tokens",
+        res.token_usage.completion_tokens,
+    );
+    span.record("llm.timing.total_ms", millis(timing.total_time));
+    if let Some(duration) = timing.prompt_processing_t {
+        span.record("llm.timing.prompt_processing_ms", millis(duration));
+    }
+    if let Some(duration) = timing.generation_t {
+        span.record("llm.timing.generation_ms", millis(duration));
+    }
+    if let Some(tok_per_sec) = timing.generation_tok_per_sec {
+        span.record("llm.timing.generation_tok_per_sec", tok_per_sec as f64);
+    }
+}
+
 impl std::fmt::Display for CompletionRequest {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f)?;
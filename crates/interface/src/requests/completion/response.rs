@@ -35,7 +35,7 @@ impl std::fmt::Display for CompletionResponse {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum CompletionFinishReason {
     /// The completion finished because the model generated the EOS token.
     Eos,
@@ -0,0 +1,174 @@
+use super::{
+    CompletionError, CompletionFinishReason, TokenUsage,
+    tool::{Function, ToolCall},
+};
+use futures::Stream;
+use std::pin::Pin;
+
+/// A stream of incremental completion updates.
+pub type CompletionStream =
+    Pin<Box<dyn Stream<Item = Result<CompletionDelta, CompletionError>> + Send>>;
+
+/// An incremental update of a streamed completion.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompletionDelta {
+    /// The text generated since the previous delta.
+    pub content: Option<String>,
+    /// The tool call fragments generated since the previous delta.
+    pub tool_calls: Vec<ToolCallDelta>,
+    /// The reason the completion finished, only set once the generation is done.
+    pub finish_reason: Option<CompletionFinishReason>,
+    /// The token usage of the completion, if reported by the backend.
+    pub token_usage: Option<TokenUsage>,
+}
+
+/// A fragment of a streamed tool call.
+///
+/// Fragments with the same `index` belong to the same tool call, their `arguments`
+/// must be concatenated in order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolCallDelta {
+    /// The position of the tool call in the response.
+    pub index: usize,
+    /// The ID of the tool call, usually only set on its first fragment.
+    pub id: Option<String>,
+    /// The name of the called function, usually only set on its first fragment.
+    pub name: Option<String>,
+    /// A fragment of the JSON encoded arguments.
+    pub arguments: String,
+}
+
+/// Accumulates the deltas of a streamed completion into the full response.
+#[derive(Debug, Clone, Default)]
+pub struct CompletionDeltaAccumulator {
+    /// The generated text so far.
+    pub content: String,
+    /// The finish reason, once received.
+    pub finish_reason: Option<CompletionFinishReason>,
+    /// The token usage, once received.
+    pub token_usage: Option<TokenUsage>,
+    tool_calls: Vec<(usize, ToolCall)>,
+}
+
+impl CompletionDeltaAccumulator {
+    /// Merges a delta into the accumulated response.
+    pub fn push(&mut self, delta: &CompletionDelta) {
+        if let Some(content) = &delta.content {
+            self.content.push_str(content);
+        }
+        for fragment in &delta.tool_calls {
+            let position = match self
+                .tool_calls
+                .iter()
+                .position(|(index, _)| *index == fragment.index)
+            {
+                Some(position) => position,
+                None => {
+                    self.tool_calls.push((
+                        fragment.index,
+                        ToolCall {
+                            r#type: "function".to_string(),
+                            ..Default::default()
+                        },
+                    ));
+                    self.tool_calls.len() - 1
+                }
+            };
+            let call = &mut self.tool_calls[position].1;
+            if let Some(id) = &fragment.id {
+                call.id.clone_from(id);
+            }
+            if let Some(name) = &fragment.name {
+                call.function.name.clone_from(name);
+            }
+            call.function.arguments.push_str(&fragment.arguments);
+        }
+        if let Some(finish_reason) = &delta.finish_reason {
+            self.finish_reason = Some(finish_reason.clone());
+        }
+        if let Some(token_usage) = &delta.token_usage {
+            self.token_usage = Some(token_usage.clone());
+        }
+    }
+
+    /// Returns the accumulated tool calls, ordered by their index in the response.
+    pub fn tool_calls(&self) -> Vec<ToolCall> {
+        let mut tool_calls = self.tool_calls.clone();
+        tool_calls.sort_by_key(|(index, _)| *index);
+        tool_calls
+            .into_iter()
+            .map(|(_, call)| ToolCall {
+                function: Function {
+                    // Tools without parameters may stream no argument fragment at all.
+                    arguments: if call.function.arguments.is_empty() {
+                        "{}".to_string()
+                    } else {
+                        call.function.arguments
+                    },
+                    ..call.function
+                },
+                ..call
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fragment(index: usize, head: Option<(&str, &str)>, arguments: &str) -> CompletionDelta {
+        CompletionDelta {
+            tool_calls: vec![ToolCallDelta {
+                index,
+                id: head.map(|(id, _)| id.to_string()),
+                name: head.map(|(_, name)| name.to_string()),
+                arguments: arguments.to_string(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_accumulate_interleaved_tool_calls() {
+        let mut accumulator = CompletionDeltaAccumulator::default();
+        for delta in [
+            fragment(1, Some(("call_b", "mul")), "{\"a\":"),
+            fragment(0, Some(("call_a", "add")), "{\"a\":"),
+            fragment(1, None, "3}"),
+            fragment(0, None, "1}"),
+            fragment(2, Some(("call_c", "now")), ""),
+            CompletionDelta {
+                finish_reason: Some(CompletionFinishReason::ToolsCall),
+                ..Default::default()
+            },
+        ] {
+            accumulator.push(&delta);
+        }
+        let tool_calls = accumulator.tool_calls();
+        let tool_calls: Vec<(&str, &str, &str)> = tool_calls
+            .iter()
+            .map(|call| {
+                (
+                    call.id.as_str(),
+                    call.function.name.as_str(),
+                    call.function.arguments.as_str(),
+                )
+            })
+            .collect();
+        // The calls are ordered by index, a call without arguments gets an empty object.
+        assert_eq!(
+            tool_calls,
+            vec![
+                ("call_a", "add", "{\"a\":1}"),
+                ("call_b", "mul", "{\"a\":3}"),
+                ("call_c", "now", "{}"),
+            ]
+        );
+        assert_eq!(
+            accumulator.finish_reason,
+            Some(CompletionFinishReason::ToolsCall)
+        );
+        assert!(accumulator.content.is_empty());
+    }
+}
@@ -3,6 +3,7 @@ use crate::llms::api::{
     anthropic::completion::AnthropicCompletionResponse,
     openai::completion::OpenAICompletionResponse,
 };
+use serde::{Deserialize, Serialize};
 
 /// The log probability of the completion.
 #[derive(Debug)]
@@ -170,7 +171,7 @@ impl std::fmt::Display for TimingUsage {
 }
 
 /// Token statistics for the completion request.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct TokenUsage {
     /// Number of tokens from the prompt which could be re-used from previous completion (n_past)
     pub tokens_cached: Option<u32>,
@@ -1,35 +1,46 @@
 use html_to_markdown::{TagHandler, markdown};
 use std::io::Read;
-use std::sync::RwLock;
+use std::sync::{Arc, Mutex};
 use std::{cell::RefCell, rc::Rc};
+use tokio::sync::OnceCell;
 use url::Url;
 
 use alith_core::{
     chunking::{ChunkError, Chunker, chunk_text},
     knowledge::{Knowledge, KnowledgeError},
 };
+use async_trait::async_trait;
 
 pub struct HtmlKnowledge<R> {
-    html: RwLock<R>,
+    /// The HTML, read from the reader on the first load so that a failed load can be
+    /// retried.
+    html: Mutex<Html<R>>,
     url: Url,
     to_markdown: bool,
+    content: OnceCell<String>,
+}
+
+enum Html<R> {
+    Reader(R),
+    Bytes(Arc<[u8]>),
 }
 
 impl<R: Read> HtmlKnowledge<R> {
     pub fn new(html: R, url: Url, to_markdown: bool) -> Self {
         Self {
-            html: RwLock::new(html),
+            html: Mutex::new(Html::Reader(html)),
             url,
             to_markdown,
+            content: OnceCell::new(),
         }
     }
 }
 
-impl<R: Read + Send + Sync> Chunker for HtmlKnowledge<R> {
+impl<R: Read + Send> Chunker for HtmlKnowledge<R> {
     fn chunk(&self) -> std::result::Result<Vec<String>, ChunkError> {
         Ok(chunk_text(
             &self
-                .load()
+                .read()
                 .map_err(|err| ChunkError::Normal(err.to_string()))?,
             self.chunk_size() as u32,
             self.overlap_percent(),
@@ -39,26 +50,70 @@ impl<R: Read + Send + Sync> Chunker for HtmlKnowledge<R> {
     }
 }
 
-impl<R: Read + Sync + Send> Knowledge for HtmlKnowledge<R> {
-    fn load(&self) -> Result<String, KnowledgeError> {
+#[async_trait]
+impl<R: Read + Send + 'static> Knowledge for HtmlKnowledge<R> {
+    async fn load(&self) -> Result<String, KnowledgeError> {
+        self.content
+            .get_or_try_init(|| async {
+                // Cleaning the HTML is blocking and CPU bound.
+                let html = self.html()?;
+                let url = self.url.clone();
+                let to_markdown = self.to_markdown;
+                tokio::task::spawn_blocking(move || clean_html(&html, &url, to_markdown))
+                    .await
+                    .map_err(|err| KnowledgeError::LoadError(err.to_string()))?
+            })
+            .await
+            .cloned()
+    }
+
+    async fn enrich(&self, _input: &str) -> Result<String, KnowledgeError> {
+        Ok(format!("<html>{}</html>", self.load().await?))
+    }
+
+    fn source(&self) -> String {
+        self.url.to_string()
+    }
+}
+
+impl<R: Read> HtmlKnowledge<R> {
+    /// Reads and cleans the HTML synchronously, unless it was already loaded.
+    fn read(&self) -> Result<String, KnowledgeError> {
+        if let Some(content) = self.content.get() {
+            return Ok(content.clone());
+        }
+        let content = clean_html(&self.html()?, &self.url, self.to_markdown)?;
+        let _ = self.content.set(content.clone());
+        Ok(content)
+    }
+
+    /// Returns the HTML, reading it from the reader the first time.
+    fn html(&self) -> Result<Arc<[u8]>, KnowledgeError> {
         let mut html = self
             .html
-            .write()
-            .map_err(|err| KnowledgeError::LoadError(err.to_string()))?;
-        let mut html = html.by_ref();
-        let cleaned_html = readability::extractor::extract(&mut html, &self.url)
+            .lock()
             .map_err(|err| KnowledgeError::LoadError(err.to_string()))?;
-        let html = format!("{}\n{}", cleaned_html.title, cleaned_html.text);
-        if self.to_markdown {
-            Ok(html_to_md(&html))
-        } else {
-            Ok(html)
-        }
+        let bytes: Arc<[u8]> = match &mut *html {
+            Html::Bytes(bytes) => return Ok(bytes.clone()),
+            Html::Reader(reader) => {
+                let mut bytes = Vec::new();
+                reader
+                    .read_to_end(&mut bytes)
+                    .map_err(|err| KnowledgeError::LoadError(err.to_string()))?;
+                bytes.into()
+            }
+        };
+        *html = Html::Bytes(bytes.clone());
+        Ok(bytes)
     }
+}
 
-    fn enrich(&self, _input: &str) -> Result<String, KnowledgeError> {
-        Ok(format!("<html>{}</html>", self.load()?))
-    }
+/// Extracts the readable content of the HTML, as Markdown if `to_markdown` is set.
+fn clean_html(mut html: &[u8], url: &Url, to_markdown: bool) -> Result<String, KnowledgeError> {
+    let cleaned_html = readability::extractor::extract(&mut html, url)
+        .map_err(|err| KnowledgeError::LoadError(err.to_string()))?;
+    let html = format!("{}\n{}", cleaned_html.title, cleaned_html.text);
+    Ok(if to_markdown { html_to_md(&html) } else { html })
 }
 
 /// Converts the provided HTML string to Markdown string.
@@ -76,3 +131,60 @@ pub fn html_to_md(html: &str) -> String {
     html_to_markdown::convert_html_to_markdown(html.as_bytes(), &mut handlers)
         .unwrap_or_else(|_| html.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const HTML: &str = "<html><head><title>Alith</title></head><body><article>\
+        <p>Alith is a decentralized AI agent framework written in Rust, with bindings \
+        for Python and Node.js, built for fast inference and on-chain agents.</p>\
+        </article></body></html>";
+
+    #[tokio::test]
+    async fn test_html_knowledge_load() {
+        let knowledge = HtmlKnowledge::new(
+            Cursor::new(HTML),
+            Url::parse("https://alith.example/docs").unwrap(),
+            false,
+        );
+        let content = knowledge.load().await.unwrap();
+        assert!(content.starts_with("Alith\n"));
+        // The reader is consumed, the content is cached.
+        assert_eq!(knowledge.load().await.unwrap(), content);
+        assert_eq!(knowledge.read().unwrap(), content);
+        assert_eq!(knowledge.source(), "https://alith.example/docs");
+    }
+
+    /// A reader failing on its first read.
+    struct FlakyReader {
+        failed: bool,
+        html: Cursor<&'static str>,
+    }
+
+    impl Read for FlakyReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if !self.failed {
+                self.failed = true;
+                return Err(std::io::Error::other("connection reset"));
+            }
+            self.html.read(buf)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_html_knowledge_load_retry() {
+        let knowledge = HtmlKnowledge::new(
+            FlakyReader {
+                failed: false,
+                html: Cursor::new(HTML),
+            },
+            Url::parse("https://alith.example/docs").unwrap(),
+            false,
+        );
+        // A failed load keeps the reader, the next load reads it again.
+        assert!(knowledge.load().await.is_err());
+        assert!(knowledge.load().await.unwrap().starts_with("Alith\n"));
+    }
+}
@@ -1,61 +1,100 @@
 use lopdf::Document;
 use std::path::{Path, PathBuf};
+use tokio::sync::OnceCell;
 
 use alith_core::{
     chunking::{ChunkError, Chunker, chunk_text},
     knowledge::{FileKnowledge, Knowledge, KnowledgeError},
 };
+use async_trait::async_trait;
 
 pub struct PdfFileKnowledge {
     pub path: PathBuf,
+    content: OnceCell<String>,
 }
 
 impl PdfFileKnowledge {
     pub fn new<P: AsRef<Path>>(path: P) -> Self {
         Self {
             path: path.as_ref().to_path_buf(),
+            content: OnceCell::new(),
         }
     }
 }
 
 impl Chunker for PdfFileKnowledge {
     fn chunk(&self) -> std::result::Result<Vec<String>, ChunkError> {
-        Ok(chunk_text(
-            &self
-                .load()
-                .map_err(|err| ChunkError::Normal(err.to_string()))?,
-            self.chunk_size() as u32,
-            self.overlap_percent(),
+        let content = match self.content.get() {
+            Some(content) => content.clone(),
+            None => extract_text(&self.path).map_err(|err| ChunkError::Normal(err.to_string()))?,
+        };
+        Ok(
+            chunk_text(&content, self.chunk_size() as u32, self.overlap_percent())
+                .map_err(|err| ChunkError::Normal(err.to_string()))?
+                .unwrap_or_default(),
         )
-        .map_err(|err| ChunkError::Normal(err.to_string()))?
-        .unwrap_or_default())
     }
 }
 
+#[async_trait]
 impl Knowledge for PdfFileKnowledge {
-    fn load(&self) -> Result<String, KnowledgeError> {
-        let doc =
-            Document::load(&self.path).map_err(|err| KnowledgeError::LoadError(err.to_string()))?;
-        Ok(doc
-            .page_iter()
-            .enumerate()
-            .map(|(page_no, _)| {
-                doc.extract_text(&[page_no as u32 + 1])
-                    .map_err(|err| KnowledgeError::LoadError(err.to_string()))
+    async fn load(&self) -> Result<String, KnowledgeError> {
+        self.content
+            .get_or_try_init(|| async {
+                // Parsing the document is blocking and CPU bound.
+                let path = self.path.clone();
+                tokio::task::spawn_blocking(move || extract_text(&path))
+                    .await
+                    .map_err(|err| KnowledgeError::LoadError(err.to_string()))?
             })
-            .collect::<Result<Vec<String>, KnowledgeError>>()?
-            .into_iter()
-            .collect::<String>())
+            .await
+            .cloned()
     }
 
-    fn enrich(&self, _input: &str) -> Result<String, KnowledgeError> {
-        Ok(format!("<pdffile>{}</pdffile>", self.load()?))
+    async fn enrich(&self, _input: &str) -> Result<String, KnowledgeError> {
+        Ok(format!("<pdffile>{}</pdffile>", self.load().await?))
+    }
+
+    fn source(&self) -> String {
+        self.path.display().to_string()
     }
 }
 
+#[async_trait]
 impl FileKnowledge for PdfFileKnowledge {
-    fn load_with_path(&self) -> Result<(PathBuf, String), KnowledgeError> {
-        let content = self.load()?;
+    async fn load_with_path(&self) -> Result<(PathBuf, String), KnowledgeError> {
+        let content = self.load().await?;
         Ok((self.path.clone(), content))
     }
 }
+
+/// Extracts the text of all pages of a PDF file.
+fn extract_text(path: &Path) -> Result<String, KnowledgeError> {
+    let doc = Document::load(path).map_err(|err| KnowledgeError::LoadError(err.to_string()))?;
+    Ok(doc
+        .page_iter()
+        .enumerate()
+        .map(|(page_no, _)| {
+            doc.extract_text(&[page_no as u32 + 1])
+                .map_err(|err| KnowledgeError::LoadError(err.to_string()))
+        })
+        .collect::<Result<Vec<String>, KnowledgeError>>()?
+        .into_iter()
+        .collect::<String>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pdf_file_knowledge_load_error() {
+        let knowledge = PdfFileKnowledge::new("missing.pdf");
+        assert!(matches!(
+            knowledge.load().await,
+            Err(KnowledgeError::LoadError(_))
+        ));
+        // A failed load is retried.
+        assert!(knowledge.load().await.is_err());
+    }
+}
@@ -3,6 +3,7 @@ use alith_core::{
     knowledge::{Knowledge, KnowledgeError},
 };
 use anyhow::Result;
+use async_trait::async_trait;
 
 #[derive(Debug, Default)]
 pub struct StringKnowledge {
@@ -29,12 +30,13 @@ impl Chunker for StringKnowledge {
     }
 }
 
+#[async_trait]
 impl Knowledge for StringKnowledge {
-    fn load(&self) -> Result<String, KnowledgeError> {
+    async fn load(&self) -> Result<String, KnowledgeError> {
         Ok(self.content.clone())
     }
 
-    fn enrich(&self, _input: &str) -> Result<String, KnowledgeError> {
+    async fn enrich(&self, _input: &str) -> Result<String, KnowledgeError> {
         Ok(self.content.clone())
     }
 }
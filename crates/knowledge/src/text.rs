@@ -1,50 +1,91 @@
-use std::fs::read_to_string;
 use std::path::{Path, PathBuf};
+use tokio::sync::OnceCell;
 
 use alith_core::{
     chunking::{ChunkError, Chunker, chunk_text},
     knowledge::{FileKnowledge, Knowledge, KnowledgeError},
 };
+use async_trait::async_trait;
 
 pub struct TextFileKnowledge {
     pub path: PathBuf,
+    content: OnceCell<String>,
 }
 
 impl TextFileKnowledge {
     pub fn new<P: AsRef<Path>>(path: P) -> Self {
         Self {
             path: path.as_ref().to_path_buf(),
+            content: OnceCell::new(),
         }
     }
 }
 
 impl Chunker for TextFileKnowledge {
     fn chunk(&self) -> std::result::Result<Vec<String>, ChunkError> {
-        Ok(chunk_text(
-            &self
-                .load()
+        let content = match self.content.get() {
+            Some(content) => content.clone(),
+            None => std::fs::read_to_string(&self.path)
                 .map_err(|err| ChunkError::Normal(err.to_string()))?,
-            self.chunk_size() as u32,
-            self.overlap_percent(),
+        };
+        Ok(
+            chunk_text(&content, self.chunk_size() as u32, self.overlap_percent())
+                .map_err(|err| ChunkError::Normal(err.to_string()))?
+                .unwrap_or_default(),
         )
-        .map_err(|err| ChunkError::Normal(err.to_string()))?
-        .unwrap_or_default())
     }
 }
 
+#[async_trait]
 impl Knowledge for TextFileKnowledge {
-    fn load(&self) -> Result<String, KnowledgeError> {
-        Ok(read_to_string(&self.path)?)
+    async fn load(&self) -> Result<String, KnowledgeError> {
+        self.content
+            .get_or_try_init(|| tokio::fs::read_to_string(&self.path))
+            .await
+            .cloned()
+            .map_err(KnowledgeError::IoError)
     }
 
-    fn enrich(&self, _input: &str) -> Result<String, KnowledgeError> {
-        Ok(format!("<textfile>{}</textfile>", self.load()?))
+    async fn enrich(&self, _input: &str) -> Result<String, KnowledgeError> {
+        Ok(format!("<textfile>{}</textfile>", self.load().await?))
+    }
+
+    fn source(&self) -> String {
+        self.path.display().to_string()
     }
 }
 
+#[async_trait]
 impl FileKnowledge for TextFileKnowledge {
-    fn load_with_path(&self) -> Result<(PathBuf, String), KnowledgeError> {
-        let content = self.load()?;
+    async fn load_with_path(&self) -> Result<(PathBuf, String), KnowledgeError> {
+        let content = self.load().await?;
         Ok((self.path.clone(), content))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_text_file_knowledge_load() {
+        let path = std::env::temp_dir().join(format!("alith-knowledge-{}.txt", std::process::id()));
+        std::fs::write(&path, "Alith is an agent framework.").unwrap();
+        let knowledge = TextFileKnowledge::new(&path);
+        assert_eq!(
+            knowledge.load_with_path().await.unwrap(),
+            (path.clone(), "Alith is an agent framework.".to_string())
+        );
+        // The content is cached once loaded.
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            knowledge.enrich("").await.unwrap(),
+            "<textfile>Alith is an agent framework.</textfile>"
+        );
+
+        assert!(matches!(
+            TextFileKnowledge::new(&path).load().await,
+            Err(KnowledgeError::IoError(_))
+        ));
+    }
+}
@@ -0,0 +1,124 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::meta::ParseNestedMeta;
+use syn::{Attribute, Expr, ExprLit, Generics, Ident, Lit, LitBool, LitStr, Meta, Path, Type};
+
+/// The arguments of the `#[tool]` attributes.
+#[derive(Default)]
+pub(crate) struct ToolArgs {
+    pub name: Option<LitStr>,
+    pub description: Option<LitStr>,
+    pub version: Option<LitStr>,
+    pub author: Option<LitStr>,
+    pub requires_approval: Option<LitBool>,
+    pub krate: Option<Path>,
+    /// The input type, only for `#[derive(Tool)]`.
+    pub input: Option<Type>,
+    /// The output type, only for `#[derive(Tool)]`.
+    pub output: Option<Type>,
+    /// The method running the tool, only for `#[derive(Tool)]`.
+    pub run: Option<Ident>,
+}
+
+impl ToolArgs {
+    pub fn parse(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("name") {
+            self.name = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("description") {
+            self.description = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("version") {
+            self.version = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("author") {
+            self.author = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("requires_approval") {
+            self.requires_approval = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("crate") {
+            let krate: LitStr = meta.value()?.parse()?;
+            self.krate = Some(krate.parse()?);
+        } else if meta.path.is_ident("input") {
+            self.input = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("output") {
+            self.output = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("run") {
+            self.run = Some(meta.value()?.parse()?);
+        } else {
+            return Err(meta.error("unsupported tool attribute"));
+        }
+        Ok(())
+    }
+
+    /// The path of the crate exporting the hidden `__private` module.
+    pub fn krate(&self) -> Path {
+        self.krate
+            .clone()
+            .unwrap_or_else(|| syn::parse_quote!(::alith))
+    }
+
+    /// Generates the `StructureTool` impl of a tool type, `items` are its `Input` and `Output`
+    /// types and its `run_with_args` method.
+    pub fn impl_tool(
+        &self,
+        tool: &Ident,
+        generics: &Generics,
+        name: String,
+        docs: Option<String>,
+        items: TokenStream,
+    ) -> syn::Result<TokenStream> {
+        let krate = self.krate();
+        let name = self.name.as_ref().map_or(name, LitStr::value);
+        let Some(description) = self.description.as_ref().map(LitStr::value).or(docs) else {
+            return Err(syn::Error::new_spanned(
+                tool,
+                "a tool needs a description, add doc comments or `#[tool(description = \"...\")]`",
+            ));
+        };
+        let version = self
+            .version
+            .as_ref()
+            .map(|version| quote!(fn version(&self) -> &str { #version }));
+        let author = self
+            .author
+            .as_ref()
+            .map(|author| quote!(fn author(&self) -> &str { #author }));
+        let requires_approval = self.requires_approval.as_ref().map(
+            |requires_approval| quote!(fn requires_approval(&self) -> bool { #requires_approval }),
+        );
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        Ok(quote! {
+            #[#krate::__private::async_trait::async_trait]
+            impl #impl_generics #krate::__private::StructureTool for #tool #ty_generics #where_clause {
+                fn name(&self) -> &str {
+                    #name
+                }
+
+                fn description(&self) -> &str {
+                    #description
+                }
+
+                #version
+                #author
+                #requires_approval
+
+                #items
+            }
+        })
+    }
+}
+
+/// Returns the text of the doc comments, if any.
+pub(crate) fn docs(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(meta) if meta.path.is_ident("doc") => match &meta.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(doc), ..
+                }) => Some(doc.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    let docs = lines.join("\n").trim().to_string();
+    (!docs.is_empty()).then_some(docs)
+}
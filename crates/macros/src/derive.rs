@@ -0,0 +1,59 @@
+use crate::args::{ToolArgs, docs};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::{DeriveInput, Error};
+
+/// Expands `#[derive(Tool)]` on a struct.
+pub(crate) fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let mut args = ToolArgs::default();
+    for attr in &input.attrs {
+        if attr.path().is_ident("tool") {
+            attr.parse_nested_meta(|meta| args.parse(meta))?;
+        }
+    }
+    let (Some(input_ty), Some(output_ty)) = (&args.input, &args.output) else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "`#[derive(Tool)]` needs the input and output types, e.g. `#[tool(input = SearchInput, output = String)]`",
+        ));
+    };
+
+    let krate = args.krate();
+    let run = args.run.clone().unwrap_or_else(|| format_ident!("call"));
+    let items = quote! {
+        type Input = #input_ty;
+        type Output = #output_ty;
+
+        async fn run_with_args(
+            &self,
+            input: Self::Input,
+        ) -> ::core::result::Result<Self::Output, #krate::__private::ToolError> {
+            Self::#run(self, input)
+                .await
+                .map_err(#krate::__private::tool_error)
+        }
+    };
+    args.impl_tool(
+        &input.ident,
+        &input.generics,
+        snake_case(&input.ident.unraw().to_string()),
+        docs(&input.attrs),
+        items,
+    )
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.char_indices() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
@@ -0,0 +1,88 @@
+//! Procedural macros defining Alith tools without the `StructureTool` boilerplate.
+use proc_macro::TokenStream;
+use syn::{DeriveInput, ItemFn, parse_macro_input};
+
+mod args;
+mod derive;
+mod tool;
+
+use args::ToolArgs;
+
+/// Defines a tool from a function.
+///
+/// The macro keeps the function and generates a unit struct named after it in PascalCase,
+/// which implements `StructureTool`, and its input struct with one field per parameter.
+/// The doc comments of the function and of its parameters become the tool and parameter
+/// descriptions. The function may be async or not, and may return a `Result` whose error
+/// is converted into a `ToolError`.
+///
+/// ```ignore
+/// use alith::{ToolError, tool};
+///
+/// /// Adds two numbers.
+/// #[tool]
+/// async fn add(
+///     /// The first number.
+///     x: i64,
+///     /// The second number.
+///     y: i64,
+/// ) -> Result<i64, ToolError> {
+///     Ok(x + y)
+/// }
+///
+/// let agent = Agent::new("calculator", model).tool(Add).await;
+/// ```
+///
+/// # Attributes
+///
+/// - `name = "..."`: the tool name, defaults to the function name.
+/// - `description = "..."`: the tool description, defaults to the doc comments.
+/// - `version = "..."`, `author = "..."`: the tool metadata.
+/// - `requires_approval = false`: skips the tool approval.
+/// - `crate = "..."`: the path of the `alith` crate, defaults to `::alith`.
+#[proc_macro_attribute]
+pub fn tool(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut args = ToolArgs::default();
+    let parser = syn::meta::parser(|meta| args.parse(meta));
+    parse_macro_input!(attr with parser);
+    let item = parse_macro_input!(item as ItemFn);
+    tool::expand(args, item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Implements `StructureTool` for a struct holding the state of a tool.
+///
+/// The struct must have an async method taking the input and returning a `Result` of the
+/// output, `call` by default. The doc comments of the struct become the tool description.
+///
+/// ```ignore
+/// use alith::Tool;
+///
+/// /// Searches the documentation.
+/// #[derive(Tool)]
+/// #[tool(input = SearchInput, output = Vec<String>)]
+/// struct DocSearch {
+///     index: Index,
+/// }
+///
+/// impl DocSearch {
+///     async fn call(&self, input: SearchInput) -> Result<Vec<String>, IndexError> {
+///         self.index.search(&input.query).await
+///     }
+/// }
+/// ```
+///
+/// # Attributes
+///
+/// - `input = Type`, `output = Type`: the input and output types of the tool, required.
+/// - `run = method`: the method running the tool, defaults to `call`.
+/// - `name`, `description`, `version`, `author`, `requires_approval` and `crate` as for
+///   [`macro@tool`], the name defaults to the struct name in snake_case.
+#[proc_macro_derive(Tool, attributes(tool))]
+pub fn derive_tool(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,139 @@
+use crate::args::{ToolArgs, docs};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::{Error, FnArg, GenericArgument, Generics, ItemFn, Pat, PathArguments, ReturnType, Type};
+
+/// Expands `#[tool]` on a function.
+pub(crate) fn expand(args: ToolArgs, mut item: ItemFn) -> syn::Result<TokenStream> {
+    if args.input.is_some() || args.output.is_some() || args.run.is_some() {
+        return Err(Error::new_spanned(
+            &item.sig.ident,
+            "`input`, `output` and `run` are only supported by `#[derive(Tool)]`",
+        ));
+    }
+    if let Some(param) = item.sig.generics.params.first() {
+        return Err(Error::new_spanned(
+            param,
+            "tool functions cannot be generic",
+        ));
+    }
+
+    let mut fields = Vec::new();
+    let mut params = Vec::new();
+    for arg in item.sig.inputs.iter_mut() {
+        let FnArg::Typed(arg) = arg else {
+            return Err(Error::new_spanned(arg, "tool functions cannot take `self`"));
+        };
+        let Pat::Ident(pat) = &*arg.pat else {
+            return Err(Error::new_spanned(
+                &arg.pat,
+                "tool parameters must be identifiers",
+            ));
+        };
+        let param = pat.ident.clone();
+        let ty = &arg.ty;
+        // Doc comments are not allowed on parameters, they describe the input fields instead.
+        let (param_docs, attrs) = arg
+            .attrs
+            .drain(..)
+            .partition::<Vec<_>, _>(|attr| attr.path().is_ident("doc"));
+        arg.attrs = attrs;
+        fields.push(quote!(#(#param_docs)* pub #param: #ty));
+        params.push(param);
+    }
+
+    let krate = args.krate();
+    let ident = &item.sig.ident;
+    let vis = &item.vis;
+    let name = ident.unraw().to_string();
+    let tool = format_ident!("{}", pascal_case(&name), span = ident.span());
+    let input = format_ident!("{tool}Input");
+    let (output, returns_result) = match &item.sig.output {
+        ReturnType::Default => (quote!(()), false),
+        ReturnType::Type(_, ty) => match result_ok_type(ty) {
+            Some(ok) => (quote!(#ok), true),
+            None => (quote!(#ty), false),
+        },
+    };
+    let call = match item.sig.asyncness {
+        Some(_) => quote!(#ident(#(#params),*).await),
+        None => quote!(#ident(#(#params),*)),
+    };
+    let body = if returns_result {
+        quote!(#call.map_err(#krate::__private::tool_error))
+    } else {
+        quote!(::core::result::Result::Ok(#call))
+    };
+    let tool_impl = args.impl_tool(
+        &tool,
+        &Generics::default(),
+        name,
+        docs(&item.attrs),
+        quote! {
+            type Input = #input;
+            type Output = #output;
+
+            async fn run_with_args(
+                &self,
+                input: Self::Input,
+            ) -> ::core::result::Result<Self::Output, #krate::__private::ToolError> {
+                let #input { #(#params),* } = input;
+                #body
+            }
+        },
+    )?;
+
+    let tool_doc = format!("The tool calling [`{ident}`].");
+    let input_doc = format!("The input of [`{tool}`].");
+    let serde = quote!(#krate::__private::serde).to_string();
+    let schemars = quote!(#krate::__private::schemars).to_string();
+    Ok(quote! {
+        #item
+
+        #[doc = #tool_doc]
+        #[derive(Debug, Clone, Copy, Default)]
+        #vis struct #tool;
+
+        #[doc = #input_doc]
+        #[derive(#krate::__private::serde::Deserialize, #krate::__private::schemars::JsonSchema)]
+        #[serde(crate = #serde)]
+        #[schemars(crate = #schemars)]
+        #vis struct #input {
+            #(#fields,)*
+        }
+
+        #tool_impl
+    })
+}
+
+/// Returns `T` if the type is a `Result<T, E>` or a `Result<T>` alias.
+fn result_ok_type(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Result" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(ok) => Some(ok),
+        _ => None,
+    }
+}
+
+fn pascal_case(name: &str) -> String {
+    name.split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect()
+}
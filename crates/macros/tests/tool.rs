@@ -0,0 +1,77 @@
+use alith_core::tool::{StructureTool, ToolError};
+use alith_macros::{Tool, tool};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+
+/// Adds two numbers.
+#[tool(crate = "alith_core")]
+async fn add(
+    /// The first number.
+    x: i64,
+    /// The second number.
+    y: i64,
+) -> Result<i64, ToolError> {
+    Ok(x + y)
+}
+
+#[tool(crate = "alith_core", name = "greet", description = "Greets someone.")]
+fn say_hello(name: String) -> String {
+    format!("Hello, {name}!")
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct LookupInput {
+    key: String,
+}
+
+/// Looks a key up.
+#[derive(Tool)]
+#[tool(crate = "alith_core", input = LookupInput, output = String, requires_approval = false)]
+struct KeyValueLookup {
+    entries: Vec<(String, String)>,
+}
+
+impl KeyValueLookup {
+    async fn call(&self, input: LookupInput) -> Result<String, String> {
+        self.entries
+            .iter()
+            .find(|(key, _)| *key == input.key)
+            .map(|(_, value)| value.clone())
+            .ok_or_else(|| format!("Unknown key {}", input.key))
+    }
+}
+
+#[tokio::test]
+async fn test_tool_fn() {
+    assert_eq!(StructureTool::name(&Add), "add");
+    assert_eq!(StructureTool::description(&Add), "Adds two numbers.");
+    let definition = StructureTool::definition(&Add);
+    assert_eq!(
+        definition.parameters["properties"]["x"]["description"],
+        json!("The first number.")
+    );
+    assert_eq!(Add.run(r#"{"x": 1, "y": 2}"#).await.unwrap(), "3");
+    assert_eq!(add(2, 3).await.unwrap(), 5);
+
+    assert_eq!(StructureTool::name(&SayHello), "greet");
+    assert_eq!(
+        SayHello.run(r#"{"name": "Alith"}"#).await.unwrap(),
+        r#""Hello, Alith!""#
+    );
+}
+
+#[tokio::test]
+async fn test_derive_tool() {
+    let lookup = KeyValueLookup {
+        entries: vec![("a".to_string(), "1".to_string())],
+    };
+    assert_eq!(StructureTool::name(&lookup), "key_value_lookup");
+    assert_eq!(StructureTool::description(&lookup), "Looks a key up.");
+    assert!(!StructureTool::requires_approval(&lookup));
+    assert_eq!(lookup.run(r#"{"key": "a"}"#).await.unwrap(), r#""1""#);
+    assert!(matches!(
+        lookup.run(r#"{"key": "b"}"#).await,
+        Err(ToolError::NormalError(_))
+    ));
+}
@@ -13,6 +13,6 @@ pub use api_prompt::ApiPrompt;
 pub use concatenator::{TextConcatenator, TextConcatenatorTrait};
 pub use llm_prompt::LLMPrompt;
 pub use local_prompt::{LocalPrompt, apply_chat_template};
-pub use prompt_message::{PromptMessage, PromptMessageType};
+pub use prompt_message::{PromptMessage, PromptMessageType, PromptToolCall};
 pub use prompt_tokenizer::PromptTokenizer;
 pub use token_count::{MaxTokenState, RequestTokenLimitError, check_and_get_max_tokens};
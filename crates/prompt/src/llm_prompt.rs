@@ -175,6 +175,39 @@ impl LLMPrompt {
         Ok(self.last_message())
     }
 
+    /// Adds a tool message to the prompt, carrying the result of a tool call.
+    ///
+    /// Must follow an assistant message with tool calls or another tool message.
+    /// Returns an error if attempting to add it anywhere else.
+    ///
+    /// # Returns
+    ///
+    /// A reference to the newly created message for setting content and the tool call ID,
+    /// or an error if validation fails.
+    pub fn add_tool_message(&self) -> Result<Arc<PromptMessage>, crate::Error> {
+        {
+            let mut messages = self.messages();
+
+            match messages.last() {
+                Some(last) if last.message_type == PromptMessageType::Tool => {}
+                Some(last)
+                    if last.message_type == PromptMessageType::Assistant
+                        && !last.tool_calls().is_empty() => {}
+                _ => crate::bail!(
+                    "Tool message must follow an assistant message with tool calls or a tool message."
+                ),
+            }
+
+            let message = Arc::new(PromptMessage::new(
+                PromptMessageType::Tool,
+                &self.concatenator,
+            ));
+            messages.push(message);
+        }
+        self.clear_built_prompt();
+        Ok(self.last_message())
+    }
+
     /// Sets a prefix to be added before generation for local LLMs.
     ///
     /// This is typically used to prime the model's response.
@@ -340,6 +373,11 @@ impl LLMPrompt {
                     (Some(PromptMessageType::User), PromptMessageType::Assistant) => {}
                     (Some(PromptMessageType::Assistant), PromptMessageType::User) => {}
                     (Some(PromptMessageType::System), PromptMessageType::User) => {}
+                    // Tool results follow the assistant message which requested the calls.
+                    (Some(PromptMessageType::Assistant), PromptMessageType::Tool) => {}
+                    (Some(PromptMessageType::Tool), PromptMessageType::Tool) => {}
+                    (Some(PromptMessageType::Tool), PromptMessageType::Assistant) => {}
+                    (Some(PromptMessageType::Tool), PromptMessageType::User) => {}
                     _ => panic!(
                         "Messages must alternate between User and Assistant after the first message (which can be System)."
                     ),
@@ -347,14 +385,29 @@ impl LLMPrompt {
             }
             last_message_type = Some(message_type.clone());
 
-            if let Some(built_message_string) = &*message.built_prompt_message() {
-                built_prompt_messages.push(HashMap::from([
-                    ("role".to_string(), message.message_type.as_str().to_owned()),
-                    ("content".to_string(), built_message_string.to_owned()),
-                ]));
-            } else {
-                crate::bail!("message.built_content is empty and skipped");
+            let tool_calls = message.tool_calls();
+            // Assistant messages which only call tools and empty tool results have no content.
+            let content = match &*message.built_prompt_message() {
+                Some(built_message_string) => built_message_string.to_owned(),
+                None if !tool_calls.is_empty() || *message_type == PromptMessageType::Tool => {
+                    String::new()
+                }
+                None => crate::bail!("message.built_content is empty and skipped"),
+            };
+            let mut built_message = HashMap::from([
+                ("role".to_string(), message.message_type.as_str().to_owned()),
+                ("content".to_string(), content),
+            ]);
+            if !tool_calls.is_empty() {
+                built_message.insert(
+                    "tool_calls".to_string(),
+                    serde_json::to_string(&*tool_calls)?,
+                );
+            }
+            if let Some(tool_call_id) = &*message.tool_call_id() {
+                built_message.insert("tool_call_id".to_string(), tool_call_id.to_owned());
             }
+            built_prompt_messages.push(built_message);
         }
 
         *self.built_prompt_messages.lock().unwrap_or_else(|e| {
@@ -1,5 +1,6 @@
 use super::TextConcatenator;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex, MutexGuard};
 
 /// Represents the type of message in a prompt sequence.
@@ -19,6 +20,9 @@ pub enum PromptMessageType {
     Assistant,
     /// A function calling message.
     Function,
+    /// The result of a tool call. Must follow the assistant message which requested the call
+    /// or another tool message.
+    Tool,
 }
 
 impl PromptMessageType {
@@ -28,6 +32,31 @@ impl PromptMessageType {
             PromptMessageType::User => "user",
             PromptMessageType::Assistant => "assistant",
             PromptMessageType::Function => "function",
+            PromptMessageType::Tool => "tool",
+        }
+    }
+}
+
+/// A tool call requested by an assistant message.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PromptToolCall {
+    /// The ID of the call, answered by the `tool_call_id` of a tool message.
+    pub id: String,
+    /// The name of the called tool.
+    pub name: String,
+    /// The arguments of the call, as a JSON string.
+    pub arguments: String,
+}
+
+impl PromptToolCall {
+    /// Returns the tool calls of a built prompt message, stored as JSON under the
+    /// `tool_calls` key.
+    pub fn from_built_prompt_message(
+        message: &HashMap<String, String>,
+    ) -> Result<Vec<Self>, crate::Error> {
+        match message.get("tool_calls") {
+            Some(tool_calls) => Ok(serde_json::from_str(tool_calls)?),
+            None => Ok(Vec::new()),
         }
     }
 }
@@ -80,6 +109,10 @@ pub struct PromptMessage {
     pub built_prompt_message: Mutex<Option<String>>,
     pub message_type: PromptMessageType,
     pub concatenator: TextConcatenator,
+    /// The tool calls requested by an assistant message.
+    pub tool_calls: Mutex<Vec<PromptToolCall>>,
+    /// The ID of the tool call that a tool message answers.
+    pub tool_call_id: Mutex<Option<String>>,
 }
 
 impl PromptMessage {
@@ -89,6 +122,8 @@ impl PromptMessage {
             built_prompt_message: None.into(),
             message_type,
             concatenator: concatenator.clone(),
+            tool_calls: Vec::new().into(),
+            tool_call_id: None.into(),
         }
     }
 
@@ -186,6 +221,34 @@ impl PromptMessage {
         self
     }
 
+    /// Sets the tool calls requested by an assistant message.
+    ///
+    /// # Arguments
+    ///
+    /// * `tool_calls` - The tool calls of the message
+    ///
+    /// # Returns
+    ///
+    /// A reference to self for method chaining
+    pub fn set_tool_calls(&self, tool_calls: Vec<PromptToolCall>) -> &Self {
+        *self.tool_calls() = tool_calls;
+        self
+    }
+
+    /// Sets the ID of the tool call that a tool message answers.
+    ///
+    /// # Arguments
+    ///
+    /// * `tool_call_id` - The ID of the answered tool call
+    ///
+    /// # Returns
+    ///
+    /// A reference to self for method chaining
+    pub fn set_tool_call_id<T: AsRef<str>>(&self, tool_call_id: T) -> &Self {
+        *self.tool_call_id() = Some(tool_call_id.as_ref().to_owned());
+        self
+    }
+
     // Getter methods
     //
 
@@ -235,6 +298,18 @@ impl PromptMessage {
             .unwrap_or_else(|e| panic!("PromptMessage Error - content not available: {:?}", e))
     }
 
+    pub(crate) fn tool_calls(&self) -> MutexGuard<'_, Vec<PromptToolCall>> {
+        self.tool_calls
+            .lock()
+            .unwrap_or_else(|e| panic!("PromptMessage Error - tool_calls not available: {:?}", e))
+    }
+
+    pub(crate) fn tool_call_id(&self) -> MutexGuard<'_, Option<String>> {
+        self.tool_call_id
+            .lock()
+            .unwrap_or_else(|e| panic!("PromptMessage Error - tool_call_id not available: {:?}", e))
+    }
+
     pub(crate) fn built_prompt_message(&self) -> MutexGuard<'_, Option<String>> {
         self.built_prompt_message.lock().unwrap_or_else(|e| {
             panic!(
@@ -252,6 +327,8 @@ impl Clone for PromptMessage {
             built_prompt_message: self.built_prompt_message().clone().into(),
             message_type: self.message_type.clone(),
             concatenator: self.concatenator.clone(),
+            tool_calls: self.tool_calls().clone().into(),
+            tool_call_id: self.tool_call_id().clone().into(),
         }
     }
 }
@@ -263,6 +340,7 @@ impl std::fmt::Display for PromptMessage {
             PromptMessageType::User => "User",
             PromptMessageType::Assistant => "Assistant",
             PromptMessageType::Function => "Function",
+            PromptMessageType::Tool => "Tool",
         };
         let message = match &*self.built_prompt_message() {
             Some(built_message_string) => {
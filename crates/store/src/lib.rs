@@ -6,3 +6,5 @@ pub mod milvus;
 pub mod pgvector;
 #[cfg(feature = "qdrant")]
 pub mod qdrant;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
@@ -0,0 +1,257 @@
+use alith_core::memory::{Memory, Message};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use std::str::FromStr;
+use tokio::sync::{mpsc, oneshot};
+
+pub use sqlx::SqlitePool;
+
+pub const DEFAULT_SQLITE_MEMORY_TABLE_NAME: &str = "alith_messages";
+
+/// A memory which persists the messages of a session into an embedded SQLite database.
+///
+/// All sessions share one table, each row stores a serialized [`Message`] of a session.
+/// The messages are loaded when the memory is opened and writes are performed in order by
+/// a background task, since [`Memory::add_message`] is synchronous. Use
+/// [`SqliteMemory::flush`] to wait for the pending writes and get their errors, including
+/// the messages which could not be serialized or queued.
+pub struct SqliteMemory {
+    session_id: String,
+    messages: Vec<Message>,
+    sender: mpsc::UnboundedSender<Command>,
+}
+
+enum Command {
+    Add(String),
+    Clear,
+    Fail(sqlx::Error),
+    Flush(oneshot::Sender<Result<(), sqlx::Error>>),
+}
+
+impl SqliteMemory {
+    /// Opens the memory of a session in the database at `url`, e.g. `sqlite://memory.db`,
+    /// creating the database if it does not exist.
+    pub async fn connect(url: &str, session_id: impl ToString) -> Result<Self, sqlx::Error> {
+        let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+        let pool = SqlitePoolOptions::new().connect_with(options).await?;
+        Self::from_pool(pool, session_id).await
+    }
+
+    /// Opens the memory of a session in the default table of the database.
+    pub async fn from_pool(
+        pool: SqlitePool,
+        session_id: impl ToString,
+    ) -> Result<Self, sqlx::Error> {
+        Self::from_pool_and_table(pool, DEFAULT_SQLITE_MEMORY_TABLE_NAME, session_id).await
+    }
+
+    /// Opens the memory of a session in the given table of the database, creating the
+    /// table if it does not exist.
+    ///
+    /// The table name must be a plain identifier, and the memory must be opened within a
+    /// Tokio runtime which runs its writes.
+    pub async fn from_pool_and_table(
+        pool: SqlitePool,
+        table: &str,
+        session_id: impl ToString,
+    ) -> Result<Self, sqlx::Error> {
+        validate_table_name(table)?;
+        let runtime = tokio::runtime::Handle::try_current()
+            .map_err(|err| sqlx::Error::Configuration(Box::new(err)))?;
+        let session_id = session_id.to_string();
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id TEXT NOT NULL,
+                message TEXT NOT NULL
+            )"
+        ))
+        .execute(&pool)
+        .await?;
+        sqlx::query(&format!(
+            "CREATE INDEX IF NOT EXISTS {table}_session_id ON {table} (session_id)"
+        ))
+        .execute(&pool)
+        .await?;
+        let rows: Vec<(String,)> = sqlx::query_as(&format!(
+            "SELECT message FROM {table} WHERE session_id = ? ORDER BY id"
+        ))
+        .bind(&session_id)
+        .fetch_all(&pool)
+        .await?;
+        let messages = rows
+            .into_iter()
+            .map(|(message,)| serde_json::from_str(&message))
+            .collect::<Result<Vec<Message>, _>>()
+            .map_err(|err| sqlx::Error::Decode(Box::new(err)))?;
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        runtime.spawn(write_messages(
+            pool,
+            table.to_string(),
+            session_id.clone(),
+            receiver,
+        ));
+        Ok(Self {
+            session_id,
+            messages,
+            sender,
+        })
+    }
+
+    /// Get the session ID.
+    #[inline]
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Waits until all pending writes are done and returns the first error which occurred
+    /// since the last call, if any.
+    pub async fn flush(&self) -> Result<(), sqlx::Error> {
+        let (sender, receiver) = oneshot::channel();
+        self.sender
+            .send(Command::Flush(sender))
+            .map_err(|_| sqlx::Error::PoolClosed)?;
+        receiver.await.map_err(|_| sqlx::Error::PoolClosed)?
+    }
+}
+
+impl Memory for SqliteMemory {
+    /// Returns all messages of the session.
+    fn messages(&self) -> Vec<Message> {
+        self.messages.clone()
+    }
+
+    /// Adds a message to the session and queues its insertion into the database.
+    fn add_message(&mut self, message: Message) {
+        let command = match serde_json::to_string(&message) {
+            Ok(row) => Command::Add(row),
+            Err(err) => Command::Fail(sqlx::Error::Encode(Box::new(err))),
+        };
+        self.send(command);
+        self.messages.push(message);
+    }
+
+    /// Clears all messages of the session and queues their deletion from the database.
+    fn clear(&mut self) {
+        self.messages.clear();
+        self.send(Command::Clear);
+    }
+}
+
+impl SqliteMemory {
+    /// Queues a write, the writer is only gone when its runtime was shut down, which
+    /// [`SqliteMemory::flush`] then reports.
+    fn send(&self, command: Command) {
+        if self.sender.send(command).is_err() {
+            tracing::warn!(
+                session_id = %self.session_id,
+                "SQLite memory writer stopped, the write is lost"
+            );
+        }
+    }
+}
+
+/// Checks that the table name is a plain identifier, since it is interpolated into the
+/// queries.
+fn validate_table_name(table: &str) -> Result<(), sqlx::Error> {
+    let mut chars = table.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(sqlx::Error::Configuration(
+            format!("invalid SQLite table name `{table}`").into(),
+        ))
+    }
+}
+
+/// Performs the writes of a session in order until the memory is dropped.
+async fn write_messages(
+    pool: SqlitePool,
+    table: String,
+    session_id: String,
+    mut receiver: mpsc::UnboundedReceiver<Command>,
+) {
+    let mut error = None;
+    while let Some(command) = receiver.recv().await {
+        let result = match command {
+            Command::Add(message) => sqlx::query(&format!(
+                "INSERT INTO {table} (session_id, message) VALUES (?, ?)"
+            ))
+            .bind(&session_id)
+            .bind(message)
+            .execute(&pool)
+            .await
+            .map(|_| ()),
+            Command::Clear => sqlx::query(&format!("DELETE FROM {table} WHERE session_id = ?"))
+                .bind(&session_id)
+                .execute(&pool)
+                .await
+                .map(|_| ()),
+            Command::Fail(err) => Err(err),
+            Command::Flush(sender) => {
+                let _ = sender.send(error.take().map_or(Ok(()), Err));
+                continue;
+            }
+        };
+        if let Err(err) = result {
+            error.get_or_insert(err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An in-memory database is private to its connection, so the pool keeps only one.
+    async fn pool() -> SqlitePool {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_memory_round_trip() {
+        let pool = pool().await;
+        let mut memory = SqliteMemory::from_pool(pool.clone(), "a").await.unwrap();
+        memory.add_user_message("Hi");
+        memory.add_ai_message("Hello!");
+        let mut other = SqliteMemory::from_pool(pool.clone(), "b").await.unwrap();
+        other.add_user_message("Bye");
+        memory.flush().await.unwrap();
+        other.flush().await.unwrap();
+
+        let reopened = SqliteMemory::from_pool(pool.clone(), "a").await.unwrap();
+        assert_eq!(reopened.messages(), memory.messages());
+        assert_eq!(reopened.messages().len(), 2);
+
+        memory.clear();
+        memory.flush().await.unwrap();
+        let reopened = SqliteMemory::from_pool(pool.clone(), "a").await.unwrap();
+        assert!(reopened.messages().is_empty());
+        let reopened = SqliteMemory::from_pool(pool, "b").await.unwrap();
+        assert_eq!(reopened.messages(), other.messages());
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_memory_table_name() {
+        let pool = pool().await;
+        for table in ["", "1messages", "messages; DROP TABLE users", "my-messages"] {
+            assert!(matches!(
+                SqliteMemory::from_pool_and_table(pool.clone(), table, "a").await,
+                Err(sqlx::Error::Configuration(_))
+            ));
+        }
+        assert!(
+            SqliteMemory::from_pool_and_table(pool, "_Messages_2", "a")
+                .await
+                .is_ok()
+        );
+    }
+}
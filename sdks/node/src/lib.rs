@@ -1,4 +1,4 @@
-use alith::{Agent, Chat, LLM, Tool};
+use alith::{Agent, Chat, ChatMessage, LLM, Tool};
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
 
@@ -88,9 +88,7 @@ impl DelegateAgent {
             },
             tools,
         );
-        let history = unsafe {
-            std::mem::transmute::<Vec<Message>, Vec<alith::core::chat::Message>>(history)
-        };
+        let history = history.into_iter().map(Message::into).collect();
         agent.preamble = self.preamble.clone();
         let result = GLOBAL_RUNTIME.block_on(async {
             if !self.mcp_config_path.is_empty() {
@@ -119,3 +117,13 @@ pub struct Message {
     pub role: String,
     pub content: String,
 }
+
+impl From<Message> for ChatMessage {
+    fn from(message: Message) -> Self {
+        ChatMessage {
+            role: message.role,
+            content: message.content,
+            ..Default::default()
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use alith::{Agent, Chat, ClientConfig, LLM, TaskError, Tool};
+use alith::{Agent, Chat, ChatMessage, ClientConfig, LLM, TaskError, Tool};
 use pyo3::exceptions::PyException;
 use pyo3::prelude::*;
 use std::collections::HashMap;
@@ -85,9 +85,7 @@ impl DelegateAgent {
                     .map_err(TaskError::MCPError)?;
             }
             self.agent
-                .chat(prompt, unsafe {
-                    std::mem::transmute::<Vec<Message>, Vec<alith::core::chat::Message>>(history)
-                })
+                .chat(prompt, history.into_iter().map(Message::into).collect())
                 .await
         });
         result.map_err(|e| PyErr::new::<PyException, _>(e.to_string()))
@@ -112,6 +110,16 @@ impl Message {
     }
 }
 
+impl From<Message> for ChatMessage {
+    fn from(message: Message) -> Self {
+        ChatMessage {
+            role: message.role,
+            content: message.content,
+            ..Default::default()
+        }
+    }
+}
+
 /// Runs the text chunker on the incoming text and returns the chunks as a vector of strings.
 ///
 /// * `text` - The natural language text to chunk.